@@ -0,0 +1,62 @@
+use bn254::Signature;
+use serde::{Deserialize, Serialize};
+use substrate_bn::{Group, G1};
+use crate::bitmap::SignerBitmap;
+
+// 签名聚合: 所有签名在 G1 上求和
+pub fn aggregate_signatures(signatures: &[Signature]) -> Signature {
+    signatures.iter().fold(Signature(G1::zero()), |acc, sig| acc + *sig)
+}
+
+// 聚合签名以及参与签名的节点位图
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AggregatedSignature {
+    #[serde(with = "crate::keys::signature_hex")]
+    pub signature: Signature,
+    pub signers: SignerBitmap,
+}
+
+// 收集各节点返回的签名并聚合
+#[derive(Clone, Debug, Default)]
+pub struct Aggregator {
+    signers: SignerBitmap,
+    signatures: Vec<Signature>,
+}
+
+impl Aggregator {
+    pub fn new(registry_size: usize) -> Self {
+        Aggregator {
+            signers: SignerBitmap::new(registry_size),
+            signatures: Vec::new(),
+        }
+    }
+
+    // 记录登记表中第 signer_index 个公钥的签名; 同一签名者重复提交时返回 false
+    pub fn add(&mut self, signer_index: usize, signature: Signature) -> bool {
+        if self.signers.contains(signer_index) {
+            return false;
+        }
+        self.signers.set(signer_index);
+        self.signatures.push(signature);
+        true
+    }
+
+    pub fn signers(&self) -> &SignerBitmap {
+        &self.signers
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    pub fn aggregate(&self) -> AggregatedSignature {
+        AggregatedSignature {
+            signature: aggregate_signatures(&self.signatures),
+            signers: self.signers.clone(),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// 签名请求和 DKG 协调消息的主节点认证
+//
+// 主节点用长期身份密钥 (secp256k1 ECDSA) 对每个签名请求签名，签名内容为
+// 域标签 || 时间戳 || 随机数 || SHA-256(请求内容)。节点只接受允许列表中的公钥，
+// 时间戳须在允许的时钟偏差内，同一公钥的随机数在时间窗口内只能使用一次，以防重放。
+// DKG 和重分享的协调者 (keytool dkg / reshare) 用同样的方式对发给节点的 DKG 帧签名，域标签不同
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use k256::ecdsa::signature::{Signer as _, Verifier as _};
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use rand::RngCore;
+use rand_core::OsRng;
+use zeroize::Zeroizing;
+use crate::keys::invalid_data;
+use crate::keystore::write_private;
+use crate::protocol::{message_digest, RequestAuth};
+
+pub const AUTH_DOMAIN: &[u8] = b"DSS_SIGN_REQUEST_V1:";
+pub const DKG_AUTH_DOMAIN: &[u8] = b"DSS_DKG_FRAME_V1:";
+// 节点默认允许的主节点时钟偏差
+pub const DEFAULT_MAX_SKEW_MS: u64 = 30_000;
+
+// 压缩的 secp256k1 公钥
+pub type AggregatorKey = [u8; 33];
+
+pub fn parse_aggregator_key(value: &str) -> io::Result<AggregatorKey> {
+    let bytes = hex::decode(value).map_err(invalid_data)?;
+    // 只接受压缩编码，允许列表按字节比较
+    if bytes.len() != 33 || !matches!(bytes[0], 0x02 | 0x03) {
+        return Err(invalid_data("aggregator key must be a 33-byte compressed secp256k1 public key"));
+    }
+    VerifyingKey::from_sec1_bytes(&bytes).map_err(|_| invalid_data("invalid secp256k1 public key"))?;
+    Ok(bytes.try_into().expect("length checked above"))
+}
+
+fn signed_content(domain: &[u8], timestamp: u64, nonce: &[u8; 16], body: &[u8]) -> Vec<u8> {
+    let mut content = domain.to_vec();
+    content.extend_from_slice(&timestamp.to_be_bytes());
+    content.extend_from_slice(nonce);
+    content.extend_from_slice(&message_digest(body));
+    content
+}
+
+// 主节点的长期身份密钥，密钥文件保存十六进制私钥
+pub struct AggregatorIdentity {
+    key: SigningKey,
+}
+
+impl AggregatorIdentity {
+    pub fn random() -> Self {
+        AggregatorIdentity { key: SigningKey::random(&mut OsRng) }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = Zeroizing::new(fs::read_to_string(path)?);
+        let bytes = Zeroizing::new(hex::decode(contents.trim()).map_err(invalid_data)?);
+        let key = SigningKey::from_slice(&bytes).map_err(|_| invalid_data("invalid secp256k1 private key"))?;
+        Ok(AggregatorIdentity { key })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = Zeroizing::new(hex::encode(self.key.to_bytes()));
+        write_private(path, contents.as_bytes())
+    }
+
+    // 文件存在则加载，否则生成并保存; 返回的 bool 表示是否新生成
+    pub fn load_or_generate(path: &Path) -> io::Result<(Self, bool)> {
+        if path.exists() {
+            return Ok((Self::load(path)?, false));
+        }
+        let identity = Self::random();
+        identity.save(path)?;
+        Ok((identity, true))
+    }
+
+    pub fn public_key(&self) -> AggregatorKey {
+        let point = self.key.verifying_key().to_encoded_point(true);
+        point.as_bytes().try_into().expect("compressed secp256k1 point is 33 bytes")
+    }
+
+    // body 为 protocol::sign_request_body 编码的请求内容
+    pub fn authorize(&self, body: &[u8], timestamp: u64) -> RequestAuth {
+        self.authorize_in(AUTH_DOMAIN, body, timestamp)
+    }
+
+    // body 为 protocol::dkg_body 编码的 DKG 消息
+    pub fn authorize_dkg(&self, body: &[u8], timestamp: u64) -> RequestAuth {
+        self.authorize_in(DKG_AUTH_DOMAIN, body, timestamp)
+    }
+
+    fn authorize_in(&self, domain: &[u8], body: &[u8], timestamp: u64) -> RequestAuth {
+        let mut nonce = [0u8; 16];
+        OsRng.fill_bytes(&mut nonce);
+        let signature: Signature = self.key.sign(&signed_content(domain, timestamp, &nonce, body));
+        RequestAuth { public_key: self.public_key(), timestamp, nonce, signature: signature.to_bytes().into() }
+    }
+}
+
+// 节点一侧: 校验签名请求来自允许的主节点，并拒绝重放
+pub struct RequestAuthenticator {
+    allowed: HashSet<AggregatorKey>,
+    max_skew_ms: u64,
+    // 时间窗口内见过的 (公钥, 随机数) 及其时间戳
+    seen: Mutex<HashMap<(AggregatorKey, [u8; 16]), u64>>,
+}
+
+impl RequestAuthenticator {
+    pub fn new(allowed: Vec<AggregatorKey>) -> Self {
+        RequestAuthenticator {
+            allowed: allowed.into_iter().collect(),
+            max_skew_ms: DEFAULT_MAX_SKEW_MS,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_max_skew(mut self, max_skew_ms: u64) -> Self {
+        self.max_skew_ms = max_skew_ms;
+        self
+    }
+
+    // 通过时返回主节点的公钥; 失败时返回拒绝原因
+    pub fn check(&self, auth: Option<&RequestAuth>, body: &[u8], now: u64) -> Result<AggregatorKey, String> {
+        self.check_in(AUTH_DOMAIN, "sign request", auth.ok_or("sign request is not authenticated")?, body, now)
+    }
+
+    // DKG 协调消息的认证，通过时返回协调者的公钥
+    pub fn check_dkg(&self, auth: Option<&RequestAuth>, body: &[u8], now: u64) -> Result<AggregatorKey, String> {
+        let auth = auth.ok_or("DKG message is not authenticated by a coordinator")?;
+        self.check_in(DKG_AUTH_DOMAIN, "DKG message", auth, body, now)
+    }
+
+    fn check_in(&self, domain: &[u8], kind: &str, auth: &RequestAuth, body: &[u8], now: u64) -> Result<AggregatorKey, String> {
+        if !self.allowed.contains(&auth.public_key) {
+            return Err(format!("aggregator key {} is not authorized", hex::encode(auth.public_key)));
+        }
+        if auth.timestamp.abs_diff(now) > self.max_skew_ms {
+            return Err(format!(
+                "request timestamp {} is more than {} ms away from the node clock ({})",
+                auth.timestamp, self.max_skew_ms, now
+            ));
+        }
+        let key = VerifyingKey::from_sec1_bytes(&auth.public_key).map_err(|_| "invalid aggregator key".to_string())?;
+        let signature = Signature::from_slice(&auth.signature).map_err(|_| "malformed request signature".to_string())?;
+        key.verify(&signed_content(domain, auth.timestamp, &auth.nonce, body), &signature)
+            .map_err(|_| "invalid request signature".to_string())?;
+
+        // 超出时间窗口的随机数不会再被接受，无需保留
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, timestamp| timestamp.saturating_add(self.max_skew_ms) >= now);
+        if seen.insert((auth.public_key, auth.nonce), auth.timestamp).is_some() {
+            return Err(format!("replayed {}", kind));
+        }
+        Ok(auth.public_key)
+    }
+}
@@ -0,0 +1,106 @@
+// 批量验证: 用随机系数把多个签名的验证方程合并为一次多配对
+//
+// 对每个条目 i 选取随机标量 r_i，检查
+//   e(Σ r_i·sig_i, -g2) · Π_i Π_j e(r_i·H(m_ij), pk_ij) == 1
+// 全部有效时等式成立; 存在无效条目时，只有在随机系数恰好抵消的情况下 (概率约 1/r) 才会成立。
+// 批量检查失败时对半拆分递归检查，找出所有无效条目
+use bn254::{PublicKey, Signature};
+use rand_core::OsRng;
+use substrate_bn::{pairing_batch, Fr, Group, Gt, G1, G2};
+use crate::hash::HashScheme;
+
+// 一个待验证的签名: 单消息时为 (消息, 签名, 聚合公钥)，多消息聚合签名时包含每个签名者的 (公钥, 消息)
+#[derive(Clone, Debug)]
+pub struct BatchEntry {
+    pub scheme: HashScheme,
+    pub signature: Signature,
+    pub pairs: Vec<(PublicKey, Vec<u8>)>,
+}
+
+impl BatchEntry {
+    pub fn new(scheme: &HashScheme, message: &[u8], signature: Signature, public_key: PublicKey) -> Self {
+        BatchEntry { scheme: scheme.clone(), signature, pairs: vec![(public_key, message.to_vec())] }
+    }
+
+    pub fn multi_message(scheme: &HashScheme, signature: Signature, pairs: Vec<(PublicKey, Vec<u8>)>) -> Self {
+        BatchEntry { scheme: scheme.clone(), signature, pairs }
+    }
+}
+
+// 哈希到 G1 之后的条目，拆分重试时不必重新哈希
+struct Prepared {
+    signature: G1,
+    points: Vec<(G1, G2)>,
+}
+
+// 消息无法哈希、没有公钥或含无穷远点的条目直接视为无效: 零签名配零公钥时验证方程恒成立
+fn prepare(entry: &BatchEntry) -> Option<Prepared> {
+    if entry.pairs.is_empty()
+        || entry.signature.0.is_zero()
+        || entry.pairs.iter().any(|(public_key, _)| public_key.0.is_zero())
+    {
+        return None;
+    }
+    let points = entry
+        .pairs
+        .iter()
+        .map(|(public_key, message)| entry.scheme.hash_to_g1(message).ok().map(|point| (point, public_key.0)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Prepared { signature: entry.signature.0, points })
+}
+
+fn check(entries: &[&Prepared]) -> bool {
+    let mut signature = G1::zero();
+    let mut terms = Vec::new();
+    for entry in entries {
+        let r = Fr::random(&mut OsRng);
+        signature = signature + entry.signature * r;
+        terms.extend(entry.points.iter().map(|(point, public_key)| (*point * r, *public_key)));
+    }
+    terms.push((signature, -G2::one()));
+    pairing_batch(&terms) == Gt::one()
+}
+
+// 所有条目都有效时返回 true; 空批次视为有效
+pub fn verify_batch(entries: &[BatchEntry]) -> bool {
+    let prepared: Option<Vec<Prepared>> = entries.iter().map(prepare).collect();
+    match prepared {
+        Some(prepared) => prepared.is_empty() || check(&prepared.iter().collect::<Vec<_>>()),
+        None => false,
+    }
+}
+
+// 返回无效条目的下标 (升序)
+pub fn find_invalid(entries: &[BatchEntry]) -> Vec<usize> {
+    let mut invalid = Vec::new();
+    let mut candidates = Vec::new();
+    let prepared: Vec<Option<Prepared>> = entries.iter().map(prepare).collect();
+    for (index, entry) in prepared.iter().enumerate() {
+        match entry {
+            Some(entry) => candidates.push((index, entry)),
+            None => invalid.push(index),
+        }
+    }
+    bisect(&candidates, false, &mut invalid);
+    invalid.sort_unstable();
+    invalid
+}
+
+// failed 为 true 表示已知这组条目中至少有一个无效，不必再检查
+fn bisect(entries: &[(usize, &Prepared)], failed: bool, invalid: &mut Vec<usize>) {
+    if entries.is_empty() {
+        return;
+    }
+    if !failed && check(&entries.iter().map(|(_, entry)| *entry).collect::<Vec<_>>()) {
+        return;
+    }
+    if entries.len() == 1 {
+        invalid.push(entries[0].0);
+        return;
+    }
+    // 前一半全部有效时，无效条目一定在后一半
+    let (left, right) = entries.split_at(entries.len() / 2);
+    let before = invalid.len();
+    bisect(left, false, invalid);
+    bisect(right, invalid.len() == before, invalid);
+}
@@ -1,9 +1,18 @@
 use clap::{Parser, Subcommand};
 use reqwest::Client;
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use bn254::{PrivateKey, PublicKey, ECDSA, Signature};
-use substrate_bn::{Group, G2};
+use dss_core::{AggregatedSignature, KeySet, PublicKey, Quorum, Verifier};
+use dss_core::batch::{self, BatchEntry};
+use dss_core::collector::KeyCollectorClient;
+use dss_core::evm::{self, VerifyCall};
+use dss_core::registry::Registry;
+use dss_core::request::{RequestRecord, RequestState, SigningMode};
+use dss_core::scheme::SignatureScheme;
+use dss_core::threshold::ThresholdGroup;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -24,6 +33,11 @@ enum Commands {
         #[arg(short, long)]
         message: String,
     },
+    // 多消息请求: 每个节点签自己的消息，格式为 <节点地址>=<消息>，可重复
+    SendMessages {
+        #[arg(short, long = "message", required = true, value_parser = parse_node_message)]
+        messages: Vec<(String, String)>,
+    },
     Check {
         #[arg(short, long)]
         request_id: String,
@@ -31,8 +45,38 @@ enum Commands {
     Verify {
         #[arg(short, long)]
         request_id: String,
+        // 多消息请求使用主节点记录中各签名者的消息，不需要此参数
+        #[arg(short, long)]
+        message: Option<String>, // 添加消息参数
+        // 门限模式下可信的群信息文件; 未指定时使用公钥收集服务登记的群记录
+        #[arg(long)]
+        threshold_group: Option<PathBuf>,
+        // 要求的最少签名者: 绝对数量或比例 (按请求的节点数计算)，在记录的门限之外额外检查
+        #[arg(long)]
+        quorum: Option<Quorum>,
+    },
+    // 批量验证已保存的请求记录 (check 命令输出的 JSON 数组，或每行一个记录)，打印验证失败的请求
+    VerifyBatch {
+        #[arg(short, long)]
+        file: PathBuf,
+        // 门限模式下可信的群信息文件; 未指定时使用公钥收集服务登记的群记录
+        #[arg(long)]
+        threshold_group: Option<PathBuf>,
+    },
+    // 生成链上验证合约
+    Solidity {
+        #[arg(long, default_value = "DssBlsVerifier")]
+        contract_name: String,
+        // 输出文件; 未指定时打印到标准输出
         #[arg(short, long)]
-        message: String, // 添加消息参数
+        out: Option<PathBuf>,
+    },
+    // 为已完成的请求生成验证合约 verify 函数的 calldata
+    Calldata {
+        #[arg(short, long)]
+        request_id: String,
+        #[arg(long)]
+        threshold_group: Option<PathBuf>,
     },
 }
 
@@ -53,67 +97,327 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             println!("Request sent. Request ID: {}", res["request_id"]);
         }
+        Commands::SendMessages { messages } => {
+            let messages: BTreeMap<&str, &str> =
+                messages.iter().map(|(node, message)| (node.as_str(), message.as_str())).collect();
+            let res = client
+                .post(format!("{}/recv_messages", cli.server))
+                .json(&json!({ "messages": messages }))
+                .send()
+                .await?
+                .json::<serde_json::Value>()
+                .await?;
+            match res.get("request_id") {
+                Some(request_id) => println!("Request sent. Request ID: {}", request_id),
+                None => println!("Request rejected: {}", res["error"]),
+            }
+        }
         Commands::Check { request_id } => {
             loop {
-                let res = client
-                    .get(format!("{}/check_status/{}", cli.server, request_id))
-                    .send()
-                    .await?
-                    .text()
-                    .await?;
-
-                if res == "processing" {
-                    println!("Still processing...");
-                    tokio::time::sleep(Duration::from_secs(5)).await;
-                } else {
-                    println!("Processing complete. Result: {}", res);
-                    break;
+                let record = match fetch_record(&client, &cli.server, request_id).await? {
+                    Some(record) => record,
+                    None => {
+                        println!("Unknown request ID: {}", request_id);
+                        break;
+                    }
+                };
+
+                match record.state {
+                    RequestState::Accepted | RequestState::Collecting => {
+                        println!("Still processing... ({} of {} signatures)", record.signer_count(), record.required);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                    RequestState::Complete => {
+                        println!("Processing complete. Result: {}", serde_json::to_string_pretty(&record)?);
+                        break;
+                    }
+                    RequestState::Failed | RequestState::Expired => {
+                        println!(
+                            "Request {:?}: {}",
+                            record.state,
+                            record.error.as_deref().unwrap_or("unknown error")
+                        );
+                        for (node, outcome) in &record.nodes {
+                            println!("  {}: {:?}", node, outcome);
+                        }
+                        break;
+                    }
                 }
             }
         }
-        Commands::Verify { request_id, message } => {
-            // 获取签名
-            let signature_res = client
-                .get(format!("{}/check_status/{}", cli.server, request_id))
-                .send()
+        Commands::Verify { request_id, message, threshold_group, quorum } => {
+            // 获取签名及签名者位图
+            let record = fetch_record(&client, &cli.server, request_id)
                 .await?
-                .json::<String>()
-                .await?;
-
-            println!("signature_res: {}", signature_res);
+                .ok_or_else(|| format!("unknown request ID: {}", request_id))?;
+            let aggregated = match completed_result(&record) {
+                Some(aggregated) => aggregated,
+                None => return Ok(()),
+            };
 
-            // 解析签名
-            let signature_bytes = hex::decode(signature_res)?;
-            println!("signature_bytes: {:?}", signature_bytes);
-            let signature = Signature::from_compressed(&signature_bytes)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            println!("signature: {:?}", aggregated.signature);
+            println!("signers: {:?}", aggregated.signers.indices());
+            println!("hash scheme: {}", record.hash_scheme);
+            println!("signature scheme: {}", record.signature_scheme);
+            record.check_signers(&aggregated, quorum.as_ref())?;
 
-            println!("signature: {:?}", signature);
-
-            // 获取所有公钥
-            let public_keys_res = client
-                .get(format!("{}/public_keys", cli.key_collector)) // 从公钥收集服务获取公钥
-                .send()
-                .await?
-                .json::<Vec<String>>() // 获取公钥列表
-                .await?;
-            println!("public_keys_res: {:?}", public_keys_res);
-
-            // 聚合公钥
-            let public_keys = public_keys_res[1..].iter().map(|public_key_hex| {
-                let public_key_bytes = hex::decode(public_key_hex).unwrap();
-                PublicKey::from_compressed(&public_key_bytes).unwrap()
-            }).collect::<Vec<PublicKey>>();
-            let aggregated_public_key = public_keys.iter().fold(PublicKey(G2::zero()), |acc, x| acc + *x);
+            let verifier = record_verifier(&cli.key_collector, &record, &aggregated, threshold_group.as_deref()).await?;
 
             // 验证签名
-            if let Ok(_) = ECDSA::verify(&message, &signature, &aggregated_public_key) {
+            let valid = match (&record.signing_mode, message) {
+                (SigningMode::MultiMessage, _) => {
+                    let signed = record.signed_messages();
+                    for (signer_index, message) in &signed {
+                        println!("  signer {}: {}", signer_index, message);
+                    }
+                    let messages: Vec<&[u8]> = signed.iter().map(|(_, message)| message.as_bytes()).collect();
+                    verifier.verify_messages_with_scheme(
+                        &record.hash_scheme,
+                        record.signature_scheme,
+                        &messages,
+                        &aggregated.signature,
+                    )
+                }
+                (_, Some(message)) => verifier.verify_with_scheme(
+                    &record.hash_scheme,
+                    record.signature_scheme,
+                    message.as_bytes(),
+                    &aggregated.signature,
+                ),
+                (_, None) => return Err("--message is required for single-message requests".into()),
+            };
+            if valid {
                 println!("Signature is valid.");
             } else {
                 println!("Signature is invalid.");
             }
         }
+        Commands::VerifyBatch { file, threshold_group } => {
+            let records = read_records(file)?;
+            let groups = trusted_groups(&cli.key_collector, threshold_group.as_deref()).await?;
+            let registry = KeyCollectorClient::new(&cli.key_collector).registry().await?;
+            println!("registered nodes: {}", registry.len());
+
+            let mut request_ids = Vec::new();
+            let mut entries = Vec::new();
+            for record in &records {
+                match batch_entry(&registry, &groups, record) {
+                    Ok(entry) => {
+                        request_ids.push(record.request_id.as_str());
+                        entries.push(entry);
+                    }
+                    Err(reason) => println!("Skipping {}: {}", record.request_id, reason),
+                }
+            }
+
+            let invalid = batch::find_invalid(&entries);
+            for index in &invalid {
+                println!("Signature is invalid: {}", request_ids[*index]);
+            }
+            println!("{} of {} signatures are valid.", entries.len() - invalid.len(), entries.len());
+            if !invalid.is_empty() {
+                return Err(format!("{} signatures failed verification", invalid.len()).into());
+            }
+        }
+        Commands::Solidity { contract_name, out } => {
+            let source = evm::solidity_verifier(contract_name)?;
+            match out {
+                Some(path) => {
+                    fs::write(path, source)?;
+                    println!("Wrote verifier contract {} to {}", contract_name, path.display());
+                }
+                None => print!("{}", source),
+            }
+        }
+        Commands::Calldata { request_id, threshold_group } => {
+            let record = fetch_record(&client, &cli.server, request_id)
+                .await?
+                .ok_or_else(|| format!("unknown request ID: {}", request_id))?;
+            let aggregated = match completed_result(&record) {
+                Some(aggregated) => aggregated,
+                None => return Ok(()),
+            };
+
+            // 验证合约只检查单个消息在聚合公钥下的签名
+            if matches!(record.signing_mode, SigningMode::MultiMessage)
+                || record.signature_scheme == SignatureScheme::MessageAugmentation
+            {
+                return Err("calldata is only available for single-message proof-of-possession requests".into());
+            }
+            record.check_signers(&aggregated, None)?;
+            let verifier = record_verifier(&cli.key_collector, &record, &aggregated, threshold_group.as_deref()).await?;
+            let call = VerifyCall::new(
+                &record.hash_scheme,
+                record.message.as_bytes(),
+                aggregated.signature,
+                verifier.aggregated_public_key(),
+            )?;
+
+            let words: Vec<String> = call.words()?.iter().map(evm::word_to_decimal).collect();
+            println!("hash scheme: {}", record.hash_scheme);
+            println!("signature: [{}]", words[0..2].join(", "));
+            println!("public key: [{}]", words[2..6].join(", "));
+            println!("message point: [{}]", words[6..8].join(", "));
+            println!("calldata: 0x{}", hex::encode(call.encode()?));
+        }
     }
 
     Ok(())
 }
+
+// 已完成请求的聚合签名; 未完成时打印原因并返回 None
+fn completed_result(record: &RequestRecord) -> Option<AggregatedSignature> {
+    match (record.state, &record.result) {
+        (RequestState::Complete, Some(aggregated)) => Some(aggregated.clone()),
+        (state, _) => {
+            println!("Request did not complete: {:?} {}", state, record.error.as_deref().unwrap_or_default());
+            None
+        }
+    }
+}
+
+// 多签只聚合位图中标记的签名者公钥; 门限签名使用固定的群公钥
+async fn record_verifier(
+    key_collector: &str,
+    record: &RequestRecord,
+    aggregated: &AggregatedSignature,
+    threshold_group: Option<&Path>,
+) -> Result<Verifier, Box<dyn std::error::Error>> {
+    match &record.signing_mode {
+        SigningMode::Multisig | SigningMode::MultiMessage => {
+            // 获取所有公钥，按请求记录的纪元解析
+            let collector = KeyCollectorClient::new(key_collector); // 从公钥收集服务获取公钥
+            let registry = collector.registry().await?;
+            println!("registered public keys: {}", registry.len());
+            if let Some(epoch) = record.epoch {
+                println!("key epoch: {}", epoch);
+            }
+            Ok(Verifier::for_signers(&epoch_keys(&registry, record, aggregated)?, aggregated)?)
+        }
+        SigningMode::Threshold { threshold, group_public_key } => {
+            println!("threshold: {}", threshold);
+            let groups = trusted_groups(key_collector, threshold_group).await?;
+            let group_public_key = trusted_group_key(&groups, *threshold, group_public_key)?;
+            Ok(Verifier::new(KeySet::new(vec![group_public_key])))
+        }
+    }
+}
+
+// 可信的门限群: --threshold-group 指定的文件，或公钥收集服务登记的群记录
+async fn trusted_groups(
+    key_collector: &str,
+    threshold_group: Option<&Path>,
+) -> Result<Vec<ThresholdGroup>, Box<dyn std::error::Error>> {
+    match threshold_group {
+        Some(path) => Ok(vec![ThresholdGroup::read(path)?]),
+        None => {
+            let records = KeyCollectorClient::new(key_collector).groups().await?;
+            Ok(records.into_iter().map(|record| record.group).collect())
+        }
+    }
+}
+
+// 主节点记录中的群公钥和门限必须与某个可信的群一致，不一致时拒绝验证
+fn trusted_group_key(groups: &[ThresholdGroup], threshold: usize, group_public_key: &PublicKey) -> Result<PublicKey, String> {
+    let group = groups
+        .iter()
+        .find(|group| group.group_public_key.0 == group_public_key.0)
+        .ok_or("the aggregator reports a group public key that is neither registered with the key collector nor given by --threshold-group")?;
+    if group.threshold != threshold {
+        return Err(format!(
+            "the aggregator reports threshold {} but the trusted group requires {}",
+            threshold, group.threshold
+        ));
+    }
+    Ok(group.group_public_key)
+}
+
+// 读取请求记录: JSON 数组或每行一个 JSON 记录
+fn read_records(path: &Path) -> Result<Vec<RequestRecord>, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)?;
+    if contents.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&contents)?);
+    }
+    let mut records = Vec::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        records.push(serde_json::from_str(line)?);
+    }
+    Ok(records)
+}
+
+// 请求创建时所在纪元的公钥集合; 没有记录纪元的旧请求使用各节点最近登记的公钥。
+// 持有证明方案的结果不能包含没有持有证明登记的签名者
+fn epoch_keys(registry: &Registry, record: &RequestRecord, aggregated: &AggregatedSignature) -> Result<KeySet, String> {
+    registry
+        .check_scheme(&aggregated.signers.indices(), record.epoch, record.signature_scheme)
+        .map_err(|e| e.to_string())?;
+    Ok(match record.epoch {
+        Some(epoch) => registry.key_set_at(epoch),
+        None => registry.key_set(),
+    })
+}
+
+// 按记录的签名方式确定公钥，不打印也不逐条请求公钥收集服务
+fn batch_entry(registry: &Registry, groups: &[ThresholdGroup], record: &RequestRecord) -> Result<BatchEntry, String> {
+    let aggregated = match (record.state, &record.result) {
+        (RequestState::Complete, Some(aggregated)) => aggregated,
+        (state, _) => return Err(format!("request did not complete: {:?}", state)),
+    };
+    record.check_signers(aggregated, None)?;
+    match &record.signing_mode {
+        SigningMode::Threshold { threshold, group_public_key } => {
+            let public_key = trusted_group_key(groups, *threshold, group_public_key)?;
+            Ok(BatchEntry::new(&record.hash_scheme, record.message.as_bytes(), aggregated.signature, public_key))
+        }
+        SigningMode::Multisig if record.signature_scheme == SignatureScheme::ProofOfPossession => {
+            let verifier = Verifier::for_signers(&epoch_keys(registry, record, aggregated)?, aggregated).map_err(|e| e.to_string())?;
+            let public_key = verifier.aggregated_public_key();
+            Ok(BatchEntry::new(&record.hash_scheme, record.message.as_bytes(), aggregated.signature, public_key))
+        }
+        // 多消息或消息增强: 每个签名者签的内容不同，条目包含每个签名者的 (公钥, 实际签名内容)
+        _ => {
+            let signers = epoch_keys(registry, record, aggregated)?.select(&aggregated.signers).map_err(|e| e.to_string())?;
+            let messages: Vec<&str> = match &record.signing_mode {
+                SigningMode::MultiMessage => record.signed_messages().into_iter().map(|(_, message)| message).collect(),
+                _ => vec![record.message.as_str(); signers.len()],
+            };
+            if signers.len() != messages.len() {
+                return Err("signer bitmap does not match the recorded messages".to_string());
+            }
+            let pairs = signers
+                .keys()
+                .iter()
+                .zip(messages)
+                .map(|(public_key, message)| {
+                    let signed = record.signature_scheme.signing_message(message.as_bytes(), public_key)?;
+                    Ok((*public_key, signed))
+                })
+                .collect::<std::io::Result<Vec<_>>>()
+                .map_err(|e| e.to_string())?;
+            Ok(BatchEntry::multi_message(&record.hash_scheme, aggregated.signature, pairs))
+        }
+    }
+}
+
+fn parse_node_message(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((node, message)) if !node.is_empty() => Ok((node.to_string(), message.to_string())),
+        _ => Err(format!("expected <node address>=<message>, got {}", value)),
+    }
+}
+
+// 查询请求记录，未知的请求 ID 返回 None
+async fn fetch_record(
+    client: &Client,
+    server: &str,
+    request_id: &str,
+) -> Result<Option<RequestRecord>, Box<dyn std::error::Error>> {
+    let res = client
+        .get(format!("{}/check_status/{}", server, request_id))
+        .send()
+        .await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(res.error_for_status()?.json::<RequestRecord>().await?))
+}
@@ -1,36 +1,243 @@
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use clap::{Parser};
+use serde_json::json;
+use dss_core::collector::{Deregistration, GroupRegistration, KeyRegistration};
+use dss_core::epoch::{epoch_at, EpochInfo, DEFAULT_EPOCH_LENGTH_MS};
+use dss_core::registry::{GroupRecord, NodeRecord, RegisterOutcome, Registry, RegistryError};
+use dss_core::request::now_millis;
+use dss_core::scheme::SignatureScheme;
+use dss_core::store::{FileStore, MemoryStore, Store};
 
 #[derive(Parser)]
 struct Args {
     #[arg(short, long, default_value = "127.0.0.1:8081")]
     addr: String, // 新增地址和端口参数
+
+    // 登记表持久化文件; 未指定时只保存在内存中，重启即丢失
+    #[arg(short, long)]
+    store: Option<PathBuf>,
+
+    // 门限群登记的持久化文件; 未指定时只保存在内存中
+    #[arg(long)]
+    group_store: Option<PathBuf>,
+
+    // 密钥纪元长度 (毫秒)，轮换的公钥在纪元边界生效; 修改会改变已有请求记录的纪元划分
+    #[arg(long, default_value_t = DEFAULT_EPOCH_LENGTH_MS)]
+    epoch_length_ms: u64,
+
+    // 部署使用的防御流氓公钥的签名方案: "proof_of_possession" 要求登记附带持有证明;
+    // "message_augmentation" 也接受没有持有证明的公钥，登记表记录其登记方式，这些公钥只能用于消息增强签名
+    #[arg(long, default_value = "proof_of_possession")]
+    signature_scheme: SignatureScheme,
+}
+
+// 登记表及其持久化存储，同一把锁保证两者一致
+struct RegistryState {
+    registry: Registry,
+    store: Box<dyn Store<NodeRecord>>,
+}
+
+impl RegistryState {
+    // 先在副本上修改并写入存储，成功后才替换内存中的登记表
+    fn update<F, R>(&mut self, node_id: &str, update: F) -> Result<R, HttpResponse>
+    where
+        F: FnOnce(&mut Registry) -> Result<R, RegistryError>,
+    {
+        let mut next = self.registry.clone();
+        let result = update(&mut next).map_err(|e| registry_error_response(&e))?;
+        if let Some(record) = next.get(node_id) {
+            if let Err(e) = self.store.put(node_id, record) {
+                eprintln!("Failed to persist node {}: {}", node_id, e);
+                return Err(HttpResponse::InternalServerError().json(json!({ "error": "failed to persist registry" })));
+            }
+        }
+        self.registry = next;
+        Ok(result)
+    }
+}
+
+// 门限群记录及其持久化存储
+struct GroupState {
+    groups: HashMap<String, GroupRecord>,
+    store: Box<dyn Store<GroupRecord>>,
 }
 
 struct AppState {
-    public_keys: Mutex<Vec<String>>, // 存储公钥
+    registry: Mutex<RegistryState>, // 按节点 ID 存储公钥
+    groups: Mutex<GroupState>,
+    epoch_length_ms: u64,
 }
 
-async fn receive_key(key: web::Json<String>, state: web::Data<Arc<AppState>>) -> impl Responder {
-    let mut public_keys = state.public_keys.lock().unwrap();
-    public_keys.push(key.clone());
-    println!("Received public key: {}", key.clone());
+impl AppState {
+    fn epoch(&self) -> u64 {
+        epoch_at(now_millis(), self.epoch_length_ms)
+    }
+}
 
-    HttpResponse::Ok().body("Public key received.")
+fn registry_error_response(e: &RegistryError) -> HttpResponse {
+    let body = json!({ "error": e.to_string() });
+    match e {
+        RegistryError::InvalidProof | RegistryError::ProofRequired(_) => HttpResponse::BadRequest().json(body),
+        RegistryError::Unauthorized(_) => HttpResponse::Forbidden().json(body),
+        RegistryError::StaleNonce { .. } => HttpResponse::Conflict().json(body),
+        RegistryError::KeyInUse(_) | RegistryError::KeyRetired(_) => HttpResponse::Conflict().json(body),
+        RegistryError::EpochStarted { .. } => HttpResponse::BadRequest().json(body),
+        RegistryError::GroupKeyChanged(_) | RegistryError::StaleGeneration { .. } | RegistryError::ShareRetired(_) => {
+            HttpResponse::Conflict().json(body)
+        }
+        RegistryError::UnknownNode(_) | RegistryError::UnknownGroup(_) => HttpResponse::NotFound().json(body),
+    }
+}
+
+async fn receive_key(registration: web::Json<KeyRegistration>, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let epoch = state.epoch();
+    let mut registry = state.registry.lock().unwrap();
+    // 校验持有证明，拒绝无法证明持有私钥的公钥 (消息增强部署也接受没有持有证明的公钥)
+    match registry.update(&registration.node_id, |registry| registry.register(&registration, now_millis(), epoch)) {
+        Ok(outcome) => {
+            let record = registry.registry.get(&registration.node_id).unwrap();
+            println!("Registration of node {} ({}) in epoch {}: {:?}", record.node_id, record.address, epoch, outcome);
+            if let Some(key) = record.keys.last().filter(|key| key.epoch > epoch) {
+                println!("Node {} rotates to a new key in epoch {}", record.node_id, key.epoch);
+            }
+            match outcome {
+                RegisterOutcome::Created => HttpResponse::Created().json(record),
+                RegisterOutcome::Unchanged | RegisterOutcome::Updated => HttpResponse::Ok().json(record),
+            }
+        }
+        Err(response) => {
+            eprintln!("Rejected registration of node {}", registration.node_id);
+            response
+        }
+    }
 }
 
 async fn get_public_keys(state: web::Data<Arc<AppState>>) -> impl Responder {
-    let public_keys = state.public_keys.lock().unwrap();
-    HttpResponse::Ok().json(public_keys.clone())
+    let registry = state.registry.lock().unwrap();
+    HttpResponse::Ok().json(registry.registry.records())
+}
+
+async fn get_public_key(node_id: web::Path<String>, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let registry = state.registry.lock().unwrap();
+    match registry.registry.get(&node_id) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => registry_error_response(&RegistryError::UnknownNode(node_id.into_inner())),
+    }
+}
+
+async fn deregister_key(
+    node_id: web::Path<String>,
+    deregistration: web::Json<Deregistration>,
+    state: web::Data<Arc<AppState>>,
+) -> impl Responder {
+    let epoch = state.epoch();
+    let mut registry = state.registry.lock().unwrap();
+    let result = registry.update(&node_id, |registry| {
+        registry.deregister(&node_id, &deregistration.signature, deregistration.nonce, now_millis(), epoch).map(|_| ())
+    });
+    match result {
+        Ok(()) => {
+            println!("Deregistered node {}", node_id);
+            HttpResponse::Ok().json(registry.registry.get(&node_id).unwrap())
+        }
+        Err(response) => {
+            eprintln!("Rejected deregistration of node {}", node_id);
+            response
+        }
+    }
+}
+
+async fn get_epoch(state: web::Data<Arc<AppState>>) -> impl Responder {
+    HttpResponse::Ok().json(EpochInfo::at(now_millis(), state.epoch_length_ms))
+}
+
+async fn register_group(registration: web::Json<GroupRegistration>, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let mut groups = state.groups.lock().unwrap();
+    let group_id = &registration.group_id;
+    // 校验群公钥的持有证明，登记成功后才写入内存
+    let (record, outcome) = match GroupRecord::apply(groups.groups.get(group_id), &registration, now_millis()) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Rejected registration of group {}: {}", group_id, e);
+            return registry_error_response(&e);
+        }
+    };
+    if outcome != RegisterOutcome::Unchanged {
+        if let Err(e) = groups.store.put(group_id, &record) {
+            eprintln!("Failed to persist group {}: {}", group_id, e);
+            return HttpResponse::InternalServerError().json(json!({ "error": "failed to persist group" }));
+        }
+        groups.groups.insert(group_id.clone(), record.clone());
+    }
+    println!(
+        "Registration of group {} ({} of {}): {:?}",
+        group_id, record.group.threshold, record.group.shares.len(), outcome
+    );
+    match outcome {
+        RegisterOutcome::Created => HttpResponse::Created().json(record),
+        RegisterOutcome::Unchanged | RegisterOutcome::Updated => HttpResponse::Ok().json(record),
+    }
+}
+
+async fn get_groups(state: web::Data<Arc<AppState>>) -> impl Responder {
+    let groups = state.groups.lock().unwrap();
+    let mut records: Vec<&GroupRecord> = groups.groups.values().collect();
+    records.sort_by(|a, b| a.group_id.cmp(&b.group_id));
+    HttpResponse::Ok().json(records)
+}
+
+async fn get_group(group_id: web::Path<String>, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let groups = state.groups.lock().unwrap();
+    match groups.groups.get(group_id.as_str()) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => registry_error_response(&RegistryError::UnknownGroup(group_id.into_inner())),
+    }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let args = Args::parse(); // 解析命令行参数
 
+    // 从持久化存储恢复登记表
+    let mut store: Box<dyn Store<NodeRecord>> = match &args.store {
+        Some(path) => {
+            println!("Using registry store {}", path.display());
+            Box::new(FileStore::open(path)?)
+        }
+        None => {
+            println!("No --store given, registry is kept in memory only");
+            Box::new(MemoryStore::new())
+        }
+    };
+    let records = store.load()?.into_iter().map(|(_, record)| record).collect();
+    let registry = Registry::from_records(records)?.with_signature_scheme(args.signature_scheme);
+    println!("Loaded {} registered nodes, signature scheme {}", registry.len(), args.signature_scheme);
+
+    let mut group_store: Box<dyn Store<GroupRecord>> = match &args.group_store {
+        Some(path) => {
+            println!("Using group store {}", path.display());
+            Box::new(FileStore::open(path)?)
+        }
+        None => Box::new(MemoryStore::new()),
+    };
+    let groups: HashMap<String, GroupRecord> = group_store.load()?.into_iter().collect();
+    println!("Loaded {} threshold groups", groups.len());
+    if args.epoch_length_ms == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "--epoch-length-ms must be positive"));
+    }
+    println!(
+        "Key epoch length {} ms, current epoch {}",
+        args.epoch_length_ms,
+        epoch_at(now_millis(), args.epoch_length_ms)
+    );
+
     let state = web::Data::new(Arc::new(AppState {
-        public_keys: Mutex::new(Vec::new()), // 初始化公钥存储
+        registry: Mutex::new(RegistryState { registry, store }), // 初始化公钥存储
+        groups: Mutex::new(GroupState { groups, store: group_store }),
+        epoch_length_ms: args.epoch_length_ms,
     }));
 
     HttpServer::new(move || {
@@ -38,8 +245,14 @@ async fn main() -> std::io::Result<()> {
             .app_data(state.clone())
             .route("/receive_key", web::post().to(receive_key)) // 接收公钥的路由
             .route("/public_keys", web::get().to(get_public_keys)) // 获取所有公钥的路由
+            .route("/public_keys/{node_id}", web::get().to(get_public_key)) // 按节点 ID 查询公钥
+            .route("/public_keys/{node_id}", web::delete().to(deregister_key)) // 注销节点
+            .route("/epoch", web::get().to(get_epoch)) // 当前密钥纪元
+            .route("/groups", web::post().to(register_group)) // 登记 DKG 产生的门限群公钥
+            .route("/groups", web::get().to(get_groups))
+            .route("/groups/{group_id}", web::get().to(get_group))
     })
     .bind(&args.addr)? // 使用命令行参数中的地址和端口
     .run()
     .await
-}
\ No newline at end of file
+}
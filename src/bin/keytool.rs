@@ -0,0 +1,387 @@
+use clap::{Args, Parser, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use dss_core::Signer;
+use dss_core::auth::AggregatorIdentity;
+use dss_core::collector::KeyCollectorClient;
+use dss_core::dkg;
+use dss_core::keys::public_key_to_hex;
+use dss_core::keystore::{self, EncryptedKeystore, PASSWORD_ENV};
+use dss_core::reshare;
+use dss_core::threshold;
+use dss_core::tls::{self, parse_peer_pin, Fingerprint, TlsClient, TlsIdentity, Transport};
+
+// 导出文件口令的环境变量
+const TRANSFER_PASSWORD_ENV: &str = "DSS_TRANSFER_PASSWORD";
+
+#[derive(Parser)]
+#[command(author, version, about = "Manage encrypted BN254 node keystores", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    // 生成新的节点密钥
+    Generate {
+        #[arg(short, long)]
+        keystore: PathBuf,
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+    },
+    // 打印密钥文件中的公钥，不需要口令
+    Inspect {
+        #[arg(short, long)]
+        keystore: PathBuf,
+    },
+    // 用传输口令重新加密密钥，便于拷贝到其他机器
+    Export {
+        #[arg(short, long)]
+        keystore: PathBuf,
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+        #[arg(short, long)]
+        out: PathBuf,
+        #[arg(long)]
+        transfer_password_file: Option<PathBuf>,
+    },
+    // 导入 export 生成的文件或旧版十六进制明文私钥，并用本机口令加密保存
+    Import {
+        #[arg(short, long)]
+        input: PathBuf,
+        #[arg(long)]
+        transfer_password_file: Option<PathBuf>,
+        #[arg(short, long)]
+        keystore: PathBuf,
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+        #[arg(long)]
+        force: bool,
+    },
+    // 门限模式的分发者: 把主私钥拆分为每个节点的份额密钥文件 share-<i>.json，
+    // 并写出群信息 group.json (群公钥和份额公钥，不含秘密)
+    Deal {
+        #[arg(short, long)]
+        threshold: usize,
+        #[arg(short, long)]
+        nodes: usize,
+        #[arg(short, long)]
+        out_dir: PathBuf,
+        // 份额密钥文件的口令
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+        // 拆分已有的密钥; 未指定时生成随机主私钥，拆分后不保存
+        #[arg(long)]
+        master_keystore: Option<PathBuf>,
+        #[arg(long)]
+        master_password_file: Option<PathBuf>,
+    },
+    // 协调节点之间的分布式密钥生成，不需要可信分发者: 各节点 (以 --dkg-dir 启动) 保存自己的份额，
+    // 这里只写出群信息 group.json，并可在公钥收集服务中登记群公钥
+    Dkg {
+        #[arg(short, long)]
+        threshold: usize,
+        // 参与节点的节点服务地址，按顺序对应份额序号 1..n
+        #[arg(short, long, required = true)]
+        node: Vec<String>,
+        #[arg(short, long)]
+        session: String,
+        #[arg(short, long)]
+        out: PathBuf,
+        // 在公钥收集服务中登记的群 ID，默认使用会话名
+        #[arg(long)]
+        group_id: Option<String>,
+        #[arg(long)]
+        key_collector: Option<String>,
+        // 协调者身份密钥 (identity-generate 生成)，节点只接受其 --authorized-aggregator 中的协调者
+        #[arg(long)]
+        identity: PathBuf,
+        #[command(flatten)]
+        tls: TlsArgs,
+    },
+    // 旧委员会节点的操作员批准一次重分享: 把计划写入节点的 --dkg-dir，节点只按与计划一致的请求分发一次
+    ResharePlan {
+        #[arg(long)]
+        dkg_dir: PathBuf,
+        #[arg(short, long)]
+        threshold: usize,
+        // 新委员会的节点服务地址，顺序须与 keytool reshare 的 --node 相同
+        #[arg(short, long, required = true)]
+        node: Vec<String>,
+        #[arg(short, long)]
+        session: String,
+        // 旧群信息文件
+        #[arg(long)]
+        group: PathBuf,
+    },
+    // 旧委员会把现有份额重新分发给新委员会 (可改变成员和门限)，群公钥不变;
+    // 新旧委员会相同时即为定期刷新份额。新节点的份额保存为 <--dkg-dir>/<session>.json
+    Reshare {
+        // 新委员会的门限
+        #[arg(short, long)]
+        threshold: usize,
+        // 持有旧份额的节点服务地址，不在线的节点会被跳过
+        #[arg(long, required = true)]
+        dealer: Vec<String>,
+        // 新委员会的节点服务地址，按顺序对应新份额序号 1..n
+        #[arg(short, long, required = true)]
+        node: Vec<String>,
+        #[arg(short, long)]
+        session: String,
+        #[arg(short, long)]
+        out: PathBuf,
+        // 旧群信息文件，不指定时从公钥收集服务按 --group-id 获取
+        #[arg(long)]
+        group: Option<PathBuf>,
+        #[arg(long)]
+        group_id: String,
+        #[arg(long)]
+        key_collector: Option<String>,
+        #[arg(long)]
+        identity: PathBuf,
+        #[command(flatten)]
+        tls: TlsArgs,
+    },
+    // 在公钥收集服务中注销节点，需要节点私钥签名
+    Deregister {
+        #[arg(short, long)]
+        keystore: PathBuf,
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+        #[arg(long)]
+        node_id: String,
+        #[arg(long)]
+        key_collector: String,
+    },
+    // 在目录中生成本地 CA (ca.pem、ca-key.pem)，用于离线签发节点通道的 TLS 证书
+    CaInit {
+        #[arg(short, long)]
+        dir: PathBuf,
+    },
+    // 用本地 CA 签发证书 <name>.pem 和私钥 <name>-key.pem，同时可用于节点服务和主节点; 打印证书指纹
+    CaIssue {
+        // 本地 CA 所在目录
+        #[arg(long)]
+        ca_dir: PathBuf,
+        #[arg(short, long)]
+        name: String,
+        // 证书中的主机名或 IP，须包含节点服务地址的主机部分，可重复
+        #[arg(long = "host", required = true)]
+        hosts: Vec<String>,
+        // 输出目录，默认为 CA 所在目录
+        #[arg(short, long)]
+        out_dir: Option<PathBuf>,
+    },
+    // 打印证书的 SHA-256 指纹，用于 --tls-pin 和 --tls-allow-client
+    Fingerprint {
+        #[arg(short, long)]
+        cert: PathBuf,
+    },
+    // 生成主节点身份密钥 (secp256k1)，打印的公钥用于节点的 --authorized-aggregator
+    IdentityGenerate {
+        #[arg(short, long)]
+        out: PathBuf,
+        #[arg(long)]
+        force: bool,
+    },
+    // 打印主节点身份密钥的公钥
+    IdentityInspect {
+        #[arg(short, long)]
+        key: PathBuf,
+    },
+}
+
+// 与启用双向 TLS 的节点通信所需的证书，三个文件须同时指定
+#[derive(Args)]
+struct TlsArgs {
+    #[arg(long, requires_all = ["tls_cert", "tls_key"])]
+    tls_ca: Option<PathBuf>,
+    #[arg(long, requires = "tls_ca")]
+    tls_cert: Option<PathBuf>,
+    #[arg(long, requires = "tls_ca")]
+    tls_key: Option<PathBuf>,
+    // <节点地址>=<SHA-256 指纹>，可重复
+    #[arg(long = "tls-pin", requires = "tls_ca", value_parser = parse_peer_pin)]
+    tls_pins: Vec<(String, Fingerprint)>,
+}
+
+impl TlsArgs {
+    fn transport(&self) -> std::io::Result<Transport> {
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (Some(ca), Some(cert), Some(key)) => {
+                let identity = TlsIdentity::load(ca, cert, key)?;
+                let pins = self.tls_pins.iter().cloned().collect();
+                Ok(Transport::Tls(Arc::new(TlsClient::new(identity, pins)?)))
+            }
+            _ => Ok(Transport::Plain),
+        }
+    }
+}
+
+fn ensure_absent(path: &Path, force: bool) -> std::io::Result<()> {
+    if path.exists() && !force {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists, pass --force to overwrite", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Commands::Generate { keystore, password_file } => {
+            ensure_absent(keystore, false)?;
+            let password = keystore::read_password(password_file.as_deref(), PASSWORD_ENV)?;
+            let signer = Signer::random();
+            keystore::save(keystore, &signer, &password)?;
+            println!("Generated keystore {}", keystore.display());
+            println!("public key: {}", public_key_to_hex(&signer.public_key())?);
+        }
+        Commands::Inspect { keystore } => {
+            let envelope = EncryptedKeystore::read(keystore)?;
+            println!("version: {}", envelope.version);
+            println!("public key: {}", public_key_to_hex(&envelope.public_key()?)?);
+            println!("kdf: {} (log_n={}, r={}, p={})", envelope.kdf.name, envelope.kdf.log_n, envelope.kdf.r, envelope.kdf.p);
+            println!("cipher: {}", envelope.cipher.name);
+        }
+        Commands::Export { keystore, password_file, out, transfer_password_file } => {
+            ensure_absent(out, false)?;
+            let password = keystore::read_password(password_file.as_deref(), PASSWORD_ENV)?;
+            let signer = keystore::load(keystore, &password)?;
+            let transfer_password = keystore::read_password(transfer_password_file.as_deref(), TRANSFER_PASSWORD_ENV)?;
+            keystore::save(out, &signer, &transfer_password)?;
+            println!("Exported {} to {}", keystore.display(), out.display());
+        }
+        Commands::Import { input, transfer_password_file, keystore, password_file, force } => {
+            ensure_absent(keystore, *force)?;
+            let contents = fs::read_to_string(input)?;
+            let signer = if contents.trim_start().starts_with('{') {
+                let transfer_password = keystore::read_password(transfer_password_file.as_deref(), TRANSFER_PASSWORD_ENV)?;
+                keystore::load(input, &transfer_password)?
+            } else {
+                // 旧版明文密钥文件: 十六进制编码的私钥
+                println!("Importing plaintext private key from {}", input.display());
+                Signer::from_bytes(&hex::decode(contents.trim())?)?
+            };
+            let password = keystore::read_password(password_file.as_deref(), PASSWORD_ENV)?;
+            keystore::save(keystore, &signer, &password)?;
+            println!("Imported key into {}", keystore.display());
+            println!("public key: {}", public_key_to_hex(&signer.public_key())?);
+        }
+        Commands::Deal { threshold, nodes, out_dir, password_file, master_keystore, master_password_file } => {
+            let group_path = out_dir.join("group.json");
+            let share_paths: Vec<PathBuf> = (1..=*nodes).map(|index| out_dir.join(format!("share-{}.json", index))).collect();
+            ensure_absent(&group_path, false)?;
+            for path in &share_paths {
+                ensure_absent(path, false)?;
+            }
+
+            let master = match master_keystore {
+                Some(path) => {
+                    let password = keystore::read_password(master_password_file.as_deref(), PASSWORD_ENV)?;
+                    keystore::load(path, &password)?
+                }
+                None => Signer::random(),
+            };
+            let dealing = threshold::deal(master.private_key(), *threshold, *nodes)?;
+
+            let password = keystore::read_password(password_file.as_deref(), PASSWORD_ENV)?;
+            fs::create_dir_all(out_dir)?;
+            for ((index, share), path) in dealing.shares.iter().zip(&share_paths) {
+                keystore::save(path, share, &password)?;
+                println!("share {}: {} ({})", index, path.display(), public_key_to_hex(&share.public_key())?);
+            }
+            dealing.group.write(&group_path)?;
+            println!("Wrote {} of {} threshold group to {}", threshold, nodes, group_path.display());
+            println!("group public key: {}", public_key_to_hex(&dealing.group.group_public_key)?);
+        }
+        Commands::Dkg { threshold, node, session, out, group_id, key_collector, identity, tls } => {
+            ensure_absent(out, false)?;
+            let group_id = group_id.as_deref().unwrap_or(session);
+            let coordinator = dkg::DkgCoordinator::new(tls.transport()?, AggregatorIdentity::load(identity)?)?;
+            let outcome = dkg::run_dkg(&coordinator, session, *threshold, node, group_id).await?;
+            outcome.group.write(out)?;
+            println!("Qualified dealers: {:?}", outcome.qualified);
+            println!("Wrote {} of {} threshold group to {}", threshold, node.len(), out.display());
+            println!("group public key: {}", public_key_to_hex(&outcome.group.group_public_key)?);
+            if let Some(key_collector) = key_collector {
+                KeyCollectorClient::new(key_collector).register_group(&outcome.registration).await?;
+                println!("Registered group {} with {}", group_id, key_collector);
+            }
+        }
+        Commands::Reshare { threshold, dealer, node, session, out, group, group_id, key_collector, identity, tls } => {
+            ensure_absent(out, false)?;
+            let record = match key_collector {
+                Some(key_collector) => KeyCollectorClient::new(key_collector).group(group_id).await?,
+                None => None,
+            };
+            let old_group = match (group, &record, key_collector) {
+                (Some(path), _, _) => threshold::ThresholdGroup::read(path)?,
+                (None, Some(record), _) => record.group.clone(),
+                (None, None, Some(key_collector)) => {
+                    return Err(format!("group {} is not registered with {}", group_id, key_collector).into())
+                }
+                (None, None, None) => return Err("either --group or --key-collector is required".into()),
+            };
+            // 新群记录的代数; 没有登记记录时按旧群为第 0 代
+            let generation = record.as_ref().map_or(1, |record| record.generation + 1);
+            let plan = reshare::ResharePlan::new(session, &old_group, *threshold, node);
+            let coordinator = dkg::DkgCoordinator::new(tls.transport()?, AggregatorIdentity::load(identity)?)?;
+            let outcome = reshare::run_reshare(&coordinator, &plan, &old_group, dealer, group_id, generation).await?;
+            outcome.group.write(out)?;
+            println!("Qualified dealers: {:?}", outcome.qualified);
+            println!("Wrote {} of {} threshold group to {}", threshold, node.len(), out.display());
+            println!("group public key: {}", public_key_to_hex(&outcome.group.group_public_key)?);
+            if let Some(key_collector) = key_collector {
+                let record = KeyCollectorClient::new(key_collector).register_group(&outcome.registration).await?;
+                println!("Recorded generation {} of group {} with {}", record.generation, group_id, key_collector);
+            }
+        }
+        Commands::ResharePlan { dkg_dir, threshold, node, session, group } => {
+            let old_group = threshold::ThresholdGroup::read(group)?;
+            let path = reshare::ResharePlan::path(dkg_dir, session);
+            ensure_absent(&path, false)?;
+            reshare::ResharePlan::new(session, &old_group, *threshold, node).write(&path)?;
+            println!("Approved reshare {} ({} of {} new shares) in {}", session, threshold, node.len(), path.display());
+            println!("group public key: {}", public_key_to_hex(&old_group.group_public_key)?);
+        }
+        Commands::Deregister { keystore, password_file, node_id, key_collector } => {
+            let password = keystore::read_password(password_file.as_deref(), PASSWORD_ENV)?;
+            let signer = keystore::load(keystore, &password)?;
+            KeyCollectorClient::new(key_collector).deregister(&signer, node_id).await?;
+            println!("Deregistered node {}", node_id);
+        }
+        Commands::CaInit { dir } => {
+            let cert = tls::create_ca(dir)?;
+            println!("Created local CA {}", cert.display());
+            println!("fingerprint: {}", hex::encode(tls::fingerprint(&tls::load_certs(&cert)?[0])));
+        }
+        Commands::CaIssue { ca_dir, name, hosts, out_dir } => {
+            let issued = tls::issue_certificate(ca_dir, name, hosts, out_dir.as_deref().unwrap_or(ca_dir))?;
+            println!("certificate: {}", issued.cert.display());
+            println!("private key: {}", issued.key.display());
+            println!("fingerprint: {}", hex::encode(issued.fingerprint));
+        }
+        Commands::Fingerprint { cert } => {
+            println!("{}", hex::encode(tls::fingerprint(&tls::load_certs(cert)?[0])));
+        }
+        Commands::IdentityGenerate { out, force } => {
+            ensure_absent(out, *force)?;
+            let identity = AggregatorIdentity::random();
+            identity.save(out)?;
+            println!("Generated aggregator identity {}", out.display());
+            println!("public key: {}", hex::encode(identity.public_key()));
+        }
+        Commands::IdentityInspect { key } => {
+            println!("public key: {}", hex::encode(AggregatorIdentity::load(key)?.public_key()));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::io;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::keys::invalid_data;
+
+// 参与签名的节点位图，第 i 位对应公钥收集服务登记表中的第 i 个公钥
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignerBitmap {
+    bits: Vec<u8>,
+}
+
+impl SignerBitmap {
+    pub fn new(size: usize) -> Self {
+        SignerBitmap { bits: vec![0u8; size.div_ceil(8)] }
+    }
+
+    pub fn set(&mut self, index: usize) {
+        let byte = index / 8;
+        if byte >= self.bits.len() {
+            self.bits.resize(byte + 1, 0);
+        }
+        self.bits[byte] |= 1 << (index % 8);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 8)
+            .map(|byte| byte & (1 << (index % 8)) != 0)
+            .unwrap_or(false)
+    }
+
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    // 按升序返回所有置位的下标
+    pub fn indices(&self) -> Vec<usize> {
+        (0..self.bits.len() * 8).filter(|i| self.contains(*i)).collect()
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.bits)
+    }
+
+    pub fn from_hex(bitmap_hex: &str) -> io::Result<Self> {
+        Ok(SignerBitmap { bits: hex::decode(bitmap_hex).map_err(invalid_data)? })
+    }
+}
+
+impl Serialize for SignerBitmap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for SignerBitmap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bitmap_hex = String::deserialize(deserializer)?;
+        SignerBitmap::from_hex(&bitmap_hex).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,306 @@
+use std::io;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use bn254::{PublicKey, Signature};
+use crate::epoch::EpochInfo;
+use crate::keys::KeySet;
+use crate::pop::{authorization_message, group_pop_message, prove_deregistration, prove_possession, verify_possession};
+use crate::registry::{GroupRecord, NodeRecord, Registry};
+use crate::request::now_millis;
+use crate::signer::Signer;
+use crate::threshold::ThresholdGroup;
+use crate::verifier::verify_signature;
+
+fn request_error(e: reqwest::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+async fn check_status(res: reqwest::Response) -> io::Result<reqwest::Response> {
+    if res.status().is_success() {
+        return Ok(res);
+    }
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    Err(io::Error::other(format!(
+        "key collector responded with status {}: {}",
+        status, body
+    )))
+}
+
+// 公钥登记请求: 节点身份、公钥及其持有证明
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyRegistration {
+    pub node_id: String,
+    pub address: String,
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub public_key: PublicKey,
+    // 没有持有证明的登记只被以消息增强方案运行的公钥收集服务接受
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::keys::optional_signature_hex")]
+    pub proof_of_possession: Option<Signature>,
+    // 公钥开始生效的纪元; 未指定时新节点从当前纪元、轮换的公钥从下一纪元生效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<u64>,
+    // 登记序号，必须大于登记表中该节点上次记录的序号
+    #[serde(default)]
+    pub nonce: u64,
+    // 更换公钥时节点当前登记的公钥对本次登记的授权签名
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::keys::optional_signature_hex")]
+    pub authorization: Option<Signature>,
+}
+
+impl KeyRegistration {
+    // 以当前毫秒时间为序号的登记
+    pub fn new(signer: &Signer, node_id: &str, address: &str) -> io::Result<Self> {
+        KeyRegistration::at(signer, node_id, address, None, now_millis())
+    }
+
+    pub fn at(signer: &Signer, node_id: &str, address: &str, epoch: Option<u64>, nonce: u64) -> io::Result<Self> {
+        Ok(KeyRegistration {
+            node_id: node_id.to_string(),
+            address: address.to_string(),
+            public_key: signer.public_key(),
+            proof_of_possession: Some(prove_possession(signer, node_id, address, epoch, nonce)?),
+            epoch,
+            nonce,
+            authorization: None,
+        })
+    }
+
+    // 不附带持有证明的登记，用于无法进行持有证明登记的消息增强部署
+    pub fn unproven(public_key: PublicKey, node_id: &str, address: &str, epoch: Option<u64>, nonce: u64) -> Self {
+        KeyRegistration {
+            node_id: node_id.to_string(),
+            address: address.to_string(),
+            public_key,
+            proof_of_possession: None,
+            epoch,
+            nonce,
+            authorization: None,
+        }
+    }
+
+    // 由节点当前登记的密钥授权轮换到本次登记的公钥
+    pub fn authorize(mut self, current: &Signer) -> io::Result<Self> {
+        self.authorization = Some(current.sign(&self.authorization_message()?)?);
+        Ok(self)
+    }
+
+    fn authorization_message(&self) -> io::Result<Vec<u8>> {
+        authorization_message(&self.public_key, &self.node_id, &self.address, self.epoch, self.nonce)
+    }
+
+    // 附带的持有证明是否有效; 没有持有证明时返回 false
+    pub fn verify(&self) -> bool {
+        self.proof_of_possession.as_ref().is_some_and(|proof| {
+            verify_possession(&self.public_key, &self.node_id, &self.address, self.epoch, self.nonce, proof)
+        })
+    }
+
+    // 授权签名是否由 public_key 签出
+    pub fn is_authorized_by(&self, public_key: &PublicKey) -> bool {
+        match (&self.authorization, self.authorization_message()) {
+            (Some(authorization), Ok(message)) => verify_signature(&message, authorization, public_key),
+            _ => false,
+        }
+    }
+}
+
+// 门限群登记请求: 群信息及群公钥的持有证明，持有证明由门限个份额的部分签名合成
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupRegistration {
+    pub group_id: String,
+    pub group: ThresholdGroup,
+    // 登记后群记录的代数: 首次登记为 0，每次重分享加一
+    #[serde(default)]
+    pub generation: u64,
+    #[serde(with = "crate::keys::signature_hex")]
+    pub proof_of_possession: Signature,
+}
+
+impl GroupRegistration {
+    pub fn verify(&self) -> bool {
+        self.group.validate().is_ok()
+            && group_pop_message(&self.group, &self.group_id, self.generation)
+                .is_ok_and(|message| verify_signature(&message, &self.proof_of_possession, &self.group.group_public_key))
+    }
+}
+
+// 注销请求: 节点私钥对注销消息的签名，序号规则与登记相同
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Deregistration {
+    #[serde(with = "crate::keys::signature_hex")]
+    pub signature: Signature,
+    #[serde(default)]
+    pub nonce: u64,
+}
+
+// 公钥收集服务的 HTTP 客户端
+#[derive(Clone)]
+pub struct KeyCollectorClient {
+    base_url: String,
+    client: Client,
+}
+
+impl KeyCollectorClient {
+    pub fn new(base_url: &str) -> Self {
+        KeyCollectorClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // 将节点身份、公钥及持有证明发送到公钥收集服务; 重复登记是幂等的
+    pub async fn register(&self, signer: &Signer, node_id: &str, address: &str) -> io::Result<()> {
+        self.submit(&KeyRegistration::new(signer, node_id, address)?).await.map(|_| ())
+    }
+
+    // 不附带持有证明登记公钥，只有以消息增强方案运行的公钥收集服务接受
+    pub async fn register_unproven(&self, public_key: PublicKey, node_id: &str, address: &str) -> io::Result<()> {
+        self.submit(&KeyRegistration::unproven(public_key, node_id, address, None, now_millis())).await.map(|_| ())
+    }
+
+    // 登记在 epoch 纪元生效的新公钥，由节点当前登记的密钥 current 授权，返回登记后的节点记录
+    pub async fn register_at(
+        &self,
+        signer: &Signer,
+        node_id: &str,
+        address: &str,
+        epoch: Option<u64>,
+        current: &Signer,
+    ) -> io::Result<NodeRecord> {
+        let registration = KeyRegistration::at(signer, node_id, address, epoch, now_millis())?.authorize(current)?;
+        self.submit(&registration).await
+    }
+
+    pub async fn submit(&self, registration: &KeyRegistration) -> io::Result<NodeRecord> {
+        let res = self.client
+            .post(format!("{}/receive_key", self.base_url))
+            .json(registration)
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res)
+            .await?
+            .json::<NodeRecord>()
+            .await
+            .map_err(request_error)
+    }
+
+    pub async fn epoch(&self) -> io::Result<EpochInfo> {
+        let res = self.client
+            .get(format!("{}/epoch", self.base_url))
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res)
+            .await?
+            .json::<EpochInfo>()
+            .await
+            .map_err(request_error)
+    }
+
+    pub async fn deregister(&self, signer: &Signer, node_id: &str) -> io::Result<()> {
+        let nonce = now_millis();
+        let deregistration = Deregistration {
+            signature: prove_deregistration(signer, node_id, nonce)?,
+            nonce,
+        };
+        let res = self.client
+            .delete(format!("{}/public_keys/{}", self.base_url, node_id))
+            .json(&deregistration)
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res).await?;
+        Ok(())
+    }
+
+    pub async fn register_group(&self, registration: &GroupRegistration) -> io::Result<GroupRecord> {
+        let res = self.client
+            .post(format!("{}/groups", self.base_url))
+            .json(registration)
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res)
+            .await?
+            .json::<GroupRecord>()
+            .await
+            .map_err(request_error)
+    }
+
+    pub async fn group(&self, group_id: &str) -> io::Result<Option<GroupRecord>> {
+        let res = self.client
+            .get(format!("{}/groups/{}", self.base_url, group_id))
+            .send()
+            .await
+            .map_err(request_error)?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        check_status(res)
+            .await?
+            .json::<GroupRecord>()
+            .await
+            .map(Some)
+            .map_err(request_error)
+    }
+
+    // 获取所有已登记的门限群，按群 ID 排列
+    pub async fn groups(&self) -> io::Result<Vec<GroupRecord>> {
+        let res = self.client
+            .get(format!("{}/groups", self.base_url))
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res)
+            .await?
+            .json::<Vec<GroupRecord>>()
+            .await
+            .map_err(request_error)
+    }
+
+    // 获取所有节点记录，按 index 排列
+    pub async fn nodes(&self) -> io::Result<Vec<NodeRecord>> {
+        let res = self.client
+            .get(format!("{}/public_keys", self.base_url))
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(res)
+            .await?
+            .json::<Vec<NodeRecord>>()
+            .await
+            .map_err(request_error)
+    }
+
+    pub async fn node(&self, node_id: &str) -> io::Result<Option<NodeRecord>> {
+        let res = self.client
+            .get(format!("{}/public_keys/{}", self.base_url, node_id))
+            .send()
+            .await
+            .map_err(request_error)?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        check_status(res)
+            .await?
+            .json::<NodeRecord>()
+            .await
+            .map(Some)
+            .map_err(request_error)
+    }
+
+    pub async fn registry(&self) -> io::Result<Registry> {
+        Registry::from_records(self.nodes().await?)
+    }
+
+    // 按 index 排列的各节点最近登记的公钥，签名者位图据此解析
+    pub async fn key_set(&self) -> io::Result<KeySet> {
+        Ok(self.registry().await?.key_set())
+    }
+}
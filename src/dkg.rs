@@ -0,0 +1,1162 @@
+// 无可信分发者的分布式密钥生成 (联合 Feldman VSS，Pedersen DKG)
+//
+// 每个参与者 i 都是一次分发者: 随机选取 t-1 次多项式 f_i，公开承诺 C_ik = a_ik·g2，
+// 通过节点间通道把份额 f_i(j) 直接发给参与者 j。流程由协调者 (keytool dkg) 分轮驱动:
+//   1. start:    协调者通知各节点开始，节点把份额发给其他节点，并向协调者返回自己的承诺
+//   2. verify:   协调者下发统一的承诺集合，节点检查收到的份额，返回对分发者的投诉
+//   3. reveal:   协调者要求被投诉的分发者公开该份额; 公开的份额仍无效则取消其资格
+//   4. finalize: 节点把合格分发者 (QUAL) 的份额求和得到自己的私钥份额并加密保存，
+//                同时返回对群公钥持有证明的部分签名
+// 群公钥为 Σ_{i∈QUAL} C_i0，任何时刻都没有机器持有主私钥。份额公钥可由承诺公开计算
+//
+// 私密份额只通过双向 TLS 发送; 协调者发给节点的每条消息都用协调者的身份密钥签名 (见 auth.rs)，
+// 节点只接受允许列表中的协调者。节点之间的 Deal 不带协调者签名，以他人名义投递的份额
+// 只是多一份候选，Verify 轮按统一承诺选出有效的一份
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use bn254::{PrivateKey, PublicKey, Signature};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use substrate_bn::{Fr, Group, G2};
+use zeroize::Zeroizing;
+use crate::auth::AggregatorIdentity;
+use crate::collector::GroupRegistration;
+use crate::epoch::KeySchedule;
+use crate::keys::invalid_data;
+use crate::keystore::{self, EncryptedKeystore};
+use crate::pop::group_pop_message;
+use crate::protocol::{dkg_body, read_message, write_message, ErrorCode, Message, ProtocolError, RequestAuth};
+use crate::request::now_millis;
+use crate::reshare::{ReshareDealer, ResharePlan, ReshareRecipient};
+use crate::signer::Signer;
+use crate::threshold::{combine, evaluate, fr_from_u32, SharePublicKey, ThresholdGroup};
+use crate::tls::Transport;
+use crate::verifier::verify_signature;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Participant {
+    pub index: u32,
+    pub address: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DealerCommitments {
+    pub dealer: u32,
+    #[serde(with = "public_keys_hex")]
+    pub commitments: Vec<PublicKey>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RevealedShare {
+    pub dealer: u32,
+    #[serde(with = "fr_hex")]
+    pub share: Fr,
+}
+
+// 节点间通道上的 DKG 消息，以 JSON 编码放在 Dkg 帧的负载中
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum DkgMessage {
+    // 协调者 -> 节点
+    Start { session: String, threshold: usize, index: u32, participants: Vec<Participant> },
+    // 分发者 -> 参与者，包含该参与者的私密份额
+    Deal {
+        session: String,
+        dealer: u32,
+        #[serde(with = "public_keys_hex")]
+        commitments: Vec<PublicKey>,
+        #[serde(with = "fr_hex")]
+        share: Fr,
+    },
+    DealAccepted { session: String },
+    // 节点 -> 协调者，对 Start 的回复
+    Dealt {
+        session: String,
+        #[serde(with = "public_keys_hex")]
+        commitments: Vec<PublicKey>,
+    },
+    // 协调者 -> 节点: 统一的承诺集合
+    Verify { session: String, commitments: Vec<DealerCommitments> },
+    Complaints { session: String, against: Vec<u32> },
+    // 协调者 -> 被投诉的分发者
+    Reveal { session: String, accuser: u32 },
+    Revealed {
+        session: String,
+        #[serde(with = "fr_hex")]
+        share: Fr,
+    },
+    // 协调者 -> 节点: 合格分发者、公开的份额以及群公钥登记用的节点 ID
+    Finalize { session: String, qualified: Vec<u32>, revealed: Vec<RevealedShare>, group_id: String },
+    Finalized {
+        session: String,
+        #[serde(with = "crate::keys::public_key_hex")]
+        share_public_key: PublicKey,
+        #[serde(with = "crate::keys::public_key_hex")]
+        group_public_key: PublicKey,
+        // 份额对群公钥持有证明消息的部分签名
+        #[serde(with = "crate::keys::signature_hex")]
+        proof_share: Signature,
+    },
+    // 协调者 -> 旧委员会节点: 用本节点的份额向新委员会分发份额的份额
+    ReshareStart { session: String, group: ThresholdGroup, threshold: usize, recipients: Vec<Participant> },
+    ReshareDealt {
+        session: String,
+        // 本节点在旧群中的份额序号
+        dealer: u32,
+        #[serde(with = "public_keys_hex")]
+        commitments: Vec<PublicKey>,
+    },
+    // 协调者 -> 新委员会节点: 新份额序号、新门限、新委员会、旧群信息以及合格分发者的承诺
+    ReshareVerify {
+        session: String,
+        index: u32,
+        threshold: usize,
+        recipients: Vec<Participant>,
+        group: ThresholdGroup,
+        commitments: Vec<DealerCommitments>,
+    },
+    // generation 为登记后群记录的代数，写入持有证明
+    ReshareFinalize { session: String, qualified: Vec<u32>, revealed: Vec<RevealedShare>, group_id: String, generation: u64 },
+    // 只作为分发者参与重分享的节点对 ReshareFinalize 的回复
+    Closed { session: String },
+}
+
+impl DkgMessage {
+    pub fn session(&self) -> &str {
+        match self {
+            DkgMessage::Start { session, .. }
+            | DkgMessage::Deal { session, .. }
+            | DkgMessage::DealAccepted { session }
+            | DkgMessage::Dealt { session, .. }
+            | DkgMessage::Verify { session, .. }
+            | DkgMessage::Complaints { session, .. }
+            | DkgMessage::Reveal { session, .. }
+            | DkgMessage::Revealed { session, .. }
+            | DkgMessage::Finalize { session, .. }
+            | DkgMessage::Finalized { session, .. }
+            | DkgMessage::ReshareStart { session, .. }
+            | DkgMessage::ReshareDealt { session, .. }
+            | DkgMessage::ReshareVerify { session, .. }
+            | DkgMessage::ReshareFinalize { session, .. }
+            | DkgMessage::Closed { session } => session,
+        }
+    }
+
+    // 协调者发给节点的消息，节点要求其带有允许的协调者的认证
+    pub fn from_coordinator(&self) -> bool {
+        matches!(
+            self,
+            DkgMessage::Start { .. }
+                | DkgMessage::Verify { .. }
+                | DkgMessage::Reveal { .. }
+                | DkgMessage::Finalize { .. }
+                | DkgMessage::ReshareStart { .. }
+                | DkgMessage::ReshareVerify { .. }
+                | DkgMessage::ReshareFinalize { .. }
+        )
+    }
+}
+
+// 用承诺检查参与者 index 收到的份额: share·g2 == Σ_k C_k · index^k
+pub fn verify_share(commitments: &[PublicKey], index: u32, share: Fr) -> bool {
+    match evaluate_commitments(commitments, index) {
+        Ok(expected) => G2::one() * share == expected,
+        Err(_) => false,
+    }
+}
+
+// Horner 法在 G2 上计算 Σ_k C_k · x^k，即 f(x)·g2
+fn evaluate_commitments(commitments: &[PublicKey], index: u32) -> io::Result<G2> {
+    let x = fr_from_u32(index)?;
+    Ok(commitments.iter().rev().fold(G2::zero(), |acc, commitment| acc * x + commitment.0))
+}
+
+// 合格分发者的份额权重: DKG 中均为 1，重分享中为旧份额序号的拉格朗日系数
+pub type Weights = BTreeMap<u32, Fr>;
+
+pub fn unit_weights(qualified: &BTreeSet<u32>) -> Weights {
+    qualified.iter().map(|dealer| (*dealer, Fr::one())).collect()
+}
+
+// 参与者 index 的份额公钥: Σ_{i∈QUAL} w_i·f_i(index)·g2
+pub fn weighted_share_public_key(commitments: &[DealerCommitments], weights: &Weights, index: u32) -> io::Result<PublicKey> {
+    let mut public_key = G2::zero();
+    for dealer in commitments {
+        if let Some(weight) = weights.get(&dealer.dealer) {
+            public_key = public_key + evaluate_commitments(&dealer.commitments, index)? * *weight;
+        }
+    }
+    Ok(PublicKey(public_key))
+}
+
+// 群公钥: Σ_{i∈QUAL} w_i·C_i0
+pub fn weighted_group_public_key(commitments: &[DealerCommitments], weights: &Weights) -> PublicKey {
+    PublicKey(commitments.iter().fold(G2::zero(), |acc, dealer| match weights.get(&dealer.dealer) {
+        Some(weight) => acc + dealer.commitments[0].0 * *weight,
+        None => acc,
+    }))
+}
+
+pub fn share_public_key(commitments: &[DealerCommitments], qualified: &BTreeSet<u32>, index: u32) -> io::Result<PublicKey> {
+    weighted_share_public_key(commitments, &unit_weights(qualified), index)
+}
+
+pub fn group_public_key(commitments: &[DealerCommitments], qualified: &BTreeSet<u32>) -> PublicKey {
+    weighted_group_public_key(commitments, &unit_weights(qualified))
+}
+
+// 由承诺公开计算的群信息: 群公钥以及 indices 中各参与者的份额公钥
+pub fn weighted_group(
+    commitments: &[DealerCommitments],
+    weights: &Weights,
+    threshold: usize,
+    indices: &[u32],
+) -> io::Result<ThresholdGroup> {
+    let shares = indices
+        .iter()
+        .map(|&index| Ok(SharePublicKey { index, public_key: weighted_share_public_key(commitments, weights, index)? }))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(ThresholdGroup { threshold, group_public_key: weighted_group_public_key(commitments, weights), shares })
+}
+
+// t-1 次随机多项式及其承诺; constant 为常数项，DKG 中随机选取，重分享中为旧份额
+pub(crate) fn random_polynomial(constant: Fr, threshold: usize) -> (Vec<Fr>, Vec<PublicKey>) {
+    let mut polynomial = vec![constant];
+    polynomial.extend((1..threshold).map(|_| Fr::random(&mut OsRng)));
+    let commitments = polynomial.iter().map(|coefficient| PublicKey(G2::one() * *coefficient)).collect();
+    (polynomial, commitments)
+}
+
+// 对照统一的承诺集合检查参与者 index 收到的份额，返回应被投诉的分发者:
+// 未收到份额、收到的承诺与统一承诺不一致或份额校验失败
+pub fn complaints_for(index: u32, commitments: &[DealerCommitments], received: &BTreeMap<u32, ReceivedDeal>) -> Vec<u32> {
+    commitments
+        .iter()
+        .filter(|dealer| match received.get(&dealer.dealer) {
+            Some(deal) => {
+                !same_commitments(&deal.commitments, &dealer.commitments)
+                    || !verify_share(&dealer.commitments, index, deal.share)
+            }
+            None => true,
+        })
+        .map(|dealer| dealer.dealer)
+        .collect()
+}
+
+// 按权重合并合格分发者发给参与者 index 的份额; revealed 中公开的份额优先于私下收到的份额
+pub fn combine_received(
+    index: u32,
+    commitments: &[DealerCommitments],
+    weights: &Weights,
+    received: &BTreeMap<u32, ReceivedDeal>,
+    revealed: &[RevealedShare],
+) -> io::Result<Fr> {
+    let mut secret = Fr::zero();
+    for dealer in commitments {
+        let weight = match weights.get(&dealer.dealer) {
+            Some(weight) => *weight,
+            None => continue,
+        };
+        let share = match revealed.iter().find(|revealed| revealed.dealer == dealer.dealer) {
+            Some(revealed) => revealed.share,
+            None => received
+                .get(&dealer.dealer)
+                .map(|deal| deal.share)
+                .ok_or_else(|| invalid_data(format!("no share from dealer {}", dealer.dealer)))?,
+        };
+        if !verify_share(&dealer.commitments, index, share) {
+            return Err(invalid_data(format!("share from qualified dealer {} does not verify", dealer.dealer)));
+        }
+        secret = secret + share * weight;
+    }
+    Ok(secret)
+}
+
+// 单个参与者在一次 DKG 会话中的状态
+pub struct DkgParticipant {
+    pub session: String,
+    pub threshold: usize,
+    pub index: u32,
+    pub participants: Vec<Participant>,
+    polynomial: Vec<Fr>,
+    commitments: Vec<PublicKey>,
+}
+
+// 参与者收到的某个分发者的份额
+#[derive(Clone, Debug)]
+pub struct ReceivedDeal {
+    pub commitments: Vec<PublicKey>,
+    pub share: Fr,
+}
+
+// DKG 结束后参与者的私钥份额
+pub struct DkgShare {
+    pub signer: Signer,
+    pub group_public_key: PublicKey,
+    pub proof_share: Signature,
+}
+
+impl DkgShare {
+    // proof_share 为份额对群持有证明消息的部分签名; group 由参与者自己从承诺算出，不采信协调者
+    pub fn new(secret: Fr, group: &ThresholdGroup, group_id: &str, generation: u64) -> io::Result<Self> {
+        let signer = Signer::new(PrivateKey(secret));
+        let proof_share = signer.sign(&group_pop_message(group, group_id, generation)?)?;
+        Ok(DkgShare { signer, group_public_key: group.group_public_key, proof_share })
+    }
+}
+
+impl DkgParticipant {
+    pub fn new(session: &str, threshold: usize, index: u32, participants: Vec<Participant>) -> io::Result<Self> {
+        validate_threshold(threshold, participants.len()).map_err(|e| invalid_data(e.to_string()))?;
+        let mut indices: Vec<u32> = participants.iter().map(|participant| participant.index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.len() != participants.len() || indices.contains(&0) || !indices.contains(&index) {
+            return Err(invalid_data("participant indices must be distinct, non-zero and include our own"));
+        }
+
+        let (polynomial, commitments) = random_polynomial(Fr::random(&mut OsRng), threshold);
+        Ok(DkgParticipant {
+            session: session.to_string(),
+            threshold,
+            index,
+            participants,
+            polynomial,
+            commitments,
+        })
+    }
+
+    pub fn commitments(&self) -> &[PublicKey] {
+        &self.commitments
+    }
+
+    // 发给参与者 recipient 的份额 f(recipient)
+    pub fn share_for(&self, recipient: u32) -> io::Result<Fr> {
+        Ok(evaluate(&self.polynomial, fr_from_u32(recipient)?))
+    }
+
+    pub fn complaints(&self, commitments: &[DealerCommitments], received: &BTreeMap<u32, ReceivedDeal>) -> Vec<u32> {
+        complaints_for(self.index, commitments, received)
+    }
+
+    // 把合格分发者的份额求和
+    pub fn finalize(
+        &self,
+        commitments: &[DealerCommitments],
+        qualified: &BTreeSet<u32>,
+        received: &BTreeMap<u32, ReceivedDeal>,
+        revealed: &[RevealedShare],
+        group_id: &str,
+    ) -> io::Result<DkgShare> {
+        let weights = unit_weights(qualified);
+        let secret = combine_received(self.index, commitments, &weights, received, revealed)?;
+        let indices: Vec<u32> = self.participants.iter().map(|participant| participant.index).collect();
+        // DKG 产生的是新群，登记为第 0 代
+        DkgShare::new(secret, &weighted_group(commitments, &weights, self.threshold, &indices)?, group_id, 0)
+    }
+}
+
+// 门限至少为 2: 门限为 1 时多项式是常数，每个参与者的份额都等于主私钥
+fn validate_threshold(threshold: usize, participants: usize) -> io::Result<()> {
+    if threshold < 2 || threshold > participants {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("threshold {} must be between 2 and the number of participants ({})", threshold, participants),
+        ));
+    }
+    Ok(())
+}
+
+fn same_commitments(a: &[PublicKey], b: &[PublicKey]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.0 == b.0)
+}
+
+fn require_tls(transport: &Transport) -> io::Result<()> {
+    match transport {
+        Transport::Tls(_) => Ok(()),
+        Transport::Plain => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "DKG messages carry private shares and are only sent over mutual TLS",
+        )),
+    }
+}
+
+// 通过节点间通道发送一条 DKG 消息并读取回复; 协调者的消息附带 auth
+pub async fn dkg_request(
+    transport: &Transport,
+    addr: &str,
+    message: DkgMessage,
+    auth: Option<RequestAuth>,
+) -> io::Result<DkgMessage> {
+    require_tls(transport)?;
+    let mut stream = transport.connect(addr).await?;
+    write_message(&mut stream, &Message::Dkg { message, auth }).await?;
+    match read_message(&mut stream).await? {
+        Some(Message::Dkg { message: reply, .. }) => Ok(reply),
+        Some(Message::Error(e)) => Err(e.into()),
+        Some(other) => Err(ProtocolError::new(
+            ErrorCode::UnexpectedMessage,
+            format!("expected Dkg, got {:?}", other.message_type()),
+        )
+        .into()),
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "node closed the connection without replying",
+        )),
+    }
+}
+
+// DKG 和重分享的协调者: 只通过双向 TLS 联系节点，发出的每条消息都用身份密钥签名
+pub struct DkgCoordinator {
+    transport: Transport,
+    identity: AggregatorIdentity,
+}
+
+impl DkgCoordinator {
+    pub fn new(transport: Transport, identity: AggregatorIdentity) -> io::Result<Self> {
+        require_tls(&transport)?;
+        Ok(DkgCoordinator { transport, identity })
+    }
+
+    pub fn identity(&self) -> &AggregatorIdentity {
+        &self.identity
+    }
+
+    pub async fn request(&self, addr: &str, message: DkgMessage) -> io::Result<DkgMessage> {
+        let auth = self.identity.authorize_dkg(&dkg_body(&message)?, now_millis());
+        dkg_request(&self.transport, addr, message, Some(auth)).await
+    }
+}
+
+// 会话名用作份额密钥文件名
+pub fn validate_session(session: &str) -> io::Result<()> {
+    let valid = !session.is_empty()
+        && session.len() <= 64
+        && session.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(invalid_data(format!(
+            "invalid DKG session name {:?}, expected 1 to 64 of [A-Za-z0-9_-]",
+            session
+        )));
+    }
+    Ok(())
+}
+
+fn dkg_error(reason: impl ToString) -> ProtocolError {
+    ProtocolError::new(ErrorCode::DkgFailed, reason.to_string())
+}
+
+// 同一会话中以同一分发者名义接受的候选份额上限
+const MAX_DEAL_CANDIDATES: usize = 4;
+
+// 本节点尚未开始的会话可以暂存的份额: 会话数和承诺点总数有上限，超时仍未开始的会话被清理
+const MAX_PENDING_SESSIONS: usize = 16;
+const MAX_PENDING_COMMITMENTS: usize = 1 << 16;
+const PENDING_DEAL_TTL: Duration = Duration::from_secs(600);
+
+struct NodeSession {
+    participant: DkgParticipant,
+    // Verify 轮收到的统一承诺集合，以及据此从候选中选定的份额
+    verified: Option<(Vec<DealerCommitments>, BTreeMap<u32, ReceivedDeal>)>,
+    // 已为其公开份额的投诉者
+    revealed: BTreeSet<u32>,
+}
+
+// 重分享会话中本节点的角色，可以同时是旧委员会的分发者和新委员会的参与者
+#[derive(Default)]
+struct ReshareSession {
+    dealer: Option<ReshareDealer>,
+    recipient: Option<(ReshareRecipient, BTreeMap<u32, ReceivedDeal>)>,
+    revealed: BTreeSet<u32>,
+}
+
+#[derive(Default)]
+struct DkgNodeState {
+    sessions: HashMap<String, NodeSession>,
+    reshares: HashMap<String, ReshareSession>,
+    // 按会话暂存收到的候选份额; 其他分发者的份额可能先于本节点的 Start 到达
+    inbox: HashMap<String, BTreeMap<u32, Vec<ReceivedDeal>>>,
+    // 尚未开始的会话收到第一份份额的时间
+    pending_since: HashMap<String, Instant>,
+}
+
+impl DkgNodeState {
+    // 检查份额的分发者和承诺: 已开始的会话只接受参与者 (重分享为旧群份额) 的份额，承诺长度必须等于门限;
+    // 尚未开始的会话受暂存上限约束
+    fn check_deal(&mut self, session: &str, dealer: u32, commitments: &[PublicKey]) -> Result<(), ProtocolError> {
+        let expected = if let Some(node_session) = self.sessions.get(session) {
+            let participant = &node_session.participant;
+            if !participant.participants.iter().any(|p| p.index == dealer) {
+                return Err(dkg_error(format!("dealer {} is not a participant of session {}", dealer, session)));
+            }
+            participant.threshold
+        } else if let Some(reshare) = self.reshares.get(session) {
+            let reshare_dealer = reshare.dealer.as_ref().ok_or_else(|| dkg_error(format!("session {} no longer accepts deals", session)))?;
+            if !reshare_dealer.is_dealer(dealer) {
+                return Err(dkg_error(format!("dealer {} does not hold a share of the group in session {}", dealer, session)));
+            }
+            reshare_dealer.threshold()
+        } else {
+            return self.check_pending(session, commitments.len());
+        };
+        if commitments.len() != expected {
+            return Err(dkg_error(format!(
+                "dealer {} sent {} commitments, the threshold of session {} is {}",
+                dealer,
+                commitments.len(),
+                session,
+                expected
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_pending(&mut self, session: &str, commitments: usize) -> Result<(), ProtocolError> {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .pending_since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) > PENDING_DEAL_TTL)
+            .map(|(session, _)| session.clone())
+            .collect();
+        for session in expired {
+            println!("DKG {}: dropping deals for a session that never started", session);
+            self.pending_since.remove(&session);
+            self.inbox.remove(&session);
+        }
+        if !self.pending_since.contains_key(session) && self.pending_since.len() >= MAX_PENDING_SESSIONS {
+            return Err(dkg_error(format!("too many sessions awaiting start, rejecting deals for session {}", session)));
+        }
+        let pending: usize = self
+            .pending_since
+            .keys()
+            .filter_map(|session| self.inbox.get(session))
+            .flat_map(|dealers| dealers.values().flatten())
+            .map(|deal| deal.commitments.len())
+            .sum();
+        if pending + commitments > MAX_PENDING_COMMITMENTS {
+            return Err(dkg_error(format!("too many pending commitments, rejecting deals for session {}", session)));
+        }
+        self.pending_since.entry(session.to_string()).or_insert(now);
+        Ok(())
+    }
+
+    // 会话在本节点开始: 不再受暂存上限约束，丢弃不是参与者或承诺长度不符的暂存份额
+    fn adopt_pending(&mut self, session: &str, dealers: impl Fn(u32) -> bool, threshold: usize) {
+        self.pending_since.remove(session);
+        if let Some(inbox) = self.inbox.get_mut(session) {
+            inbox.retain(|dealer, _| dealers(*dealer));
+            for deals in inbox.values_mut() {
+                deals.retain(|deal| deal.commitments.len() == threshold);
+            }
+        }
+    }
+}
+
+// 从每个分发者的候选份额中选出与统一承诺一致且校验通过的一份; 都不符合时保留第一份，由投诉处理
+fn select_deals(
+    index: u32,
+    commitments: &[DealerCommitments],
+    candidates: Option<BTreeMap<u32, Vec<ReceivedDeal>>>,
+) -> BTreeMap<u32, ReceivedDeal> {
+    let mut selected = BTreeMap::new();
+    for (dealer, mut deals) in candidates.unwrap_or_default() {
+        let valid = commitments.iter().find(|agreed| agreed.dealer == dealer).and_then(|agreed| {
+            deals.iter().position(|deal| {
+                same_commitments(&deal.commitments, &agreed.commitments)
+                    && verify_share(&agreed.commitments, index, deal.share)
+            })
+        });
+        if !deals.is_empty() {
+            selected.insert(dealer, deals.swap_remove(valid.unwrap_or(0)));
+        }
+    }
+    selected
+}
+
+// 每个投诉者只公开一次。同一多项式的 threshold 个份额即可插值出常数项 (重分享中即为旧份额)，
+// 因此最多公开 threshold - 1 个，超出时拒绝，由协调者取消本节点的分发资格
+fn record_reveal(revealed: &mut BTreeSet<u32>, accuser: u32, threshold: usize, session: &str) -> Result<(), ProtocolError> {
+    if revealed.contains(&accuser) {
+        return Err(dkg_error(format!("the share for participant {} was already revealed in session {}", accuser, session)));
+    }
+    if revealed.len() + 1 >= threshold {
+        return Err(dkg_error(format!("revealing another share in session {} would expose the dealt secret", session)));
+    }
+    revealed.insert(accuser);
+    Ok(())
+}
+
+// 节点服务中的 DKG 和重分享参与方，完成后把私钥份额加密保存为 <dir>/<session>.json
+pub struct DkgNode {
+    dir: PathBuf,
+    password: Zeroizing<String>,
+    state: Mutex<DkgNodeState>,
+    // 向其他参与者发送份额使用的传输方式
+    transport: Transport,
+    // 节点签名密钥的文件，重分享结束后若其中是旧份额则换成新份额，没有新份额时作废
+    keystore: Option<PathBuf>,
+}
+
+impl DkgNode {
+    pub fn new(dir: PathBuf, password: Zeroizing<String>) -> Self {
+        DkgNode { dir, password, state: Mutex::new(DkgNodeState::default()), transport: Transport::Plain, keystore: None }
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn with_keystore(mut self, keystore: PathBuf) -> Self {
+        self.keystore = Some(keystore);
+        self
+    }
+
+    pub fn share_path(&self, session: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", session))
+    }
+
+    // keys 为节点的签名密钥，重分享时当前密钥作为旧份额，结束后换用新份额
+    pub async fn handle(&self, message: DkgMessage, keys: &KeySchedule) -> Result<DkgMessage, ProtocolError> {
+        match message {
+            DkgMessage::Start { session, threshold, index, participants } => {
+                self.start(session, threshold, index, participants).await
+            }
+            DkgMessage::Deal { session, dealer, commitments, share } => {
+                validate_session(&session).map_err(dkg_error)?;
+                let mut state = self.state.lock().unwrap();
+                let verified = state.sessions.get(&session).is_some_and(|node_session| node_session.verified.is_some())
+                    || state.reshares.get(&session).is_some_and(|reshare| reshare.recipient.is_some());
+                if verified {
+                    return Err(dkg_error(format!("session {} no longer accepts deals", session)));
+                }
+                state.check_deal(&session, dealer, &commitments)?;
+                let candidates = state.inbox.entry(session.clone()).or_default().entry(dealer).or_default();
+                if candidates.len() >= MAX_DEAL_CANDIDATES {
+                    return Err(dkg_error(format!("too many deals from dealer {} in session {}", dealer, session)));
+                }
+                candidates.push(ReceivedDeal { commitments, share });
+                Ok(DkgMessage::DealAccepted { session })
+            }
+            DkgMessage::Verify { session, commitments } => {
+                let mut state = self.state.lock().unwrap();
+                let DkgNodeState { sessions, inbox, .. } = &mut *state;
+                let node_session = sessions.get_mut(&session).ok_or_else(|| dkg_error(format!("unknown session {}", session)))?;
+                if node_session.verified.is_some() {
+                    return Err(dkg_error(format!("session {} has already been verified", session)));
+                }
+                // 群公钥取各承诺的常数项，长度不符的承诺在使用前拒绝
+                let threshold = node_session.participant.threshold;
+                if let Some(dealer) = commitments.iter().find(|dealer| dealer.commitments.len() != threshold) {
+                    return Err(dkg_error(format!(
+                        "dealer {} has {} commitments, the threshold is {}",
+                        dealer.dealer,
+                        dealer.commitments.len(),
+                        threshold
+                    )));
+                }
+                let received = select_deals(node_session.participant.index, &commitments, inbox.remove(&session));
+                let against = node_session.participant.complaints(&commitments, &received);
+                node_session.verified = Some((commitments, received));
+                Ok(DkgMessage::Complaints { session, against })
+            }
+            DkgMessage::Reveal { session, accuser } => {
+                let mut state = self.state.lock().unwrap();
+                let DkgNodeState { sessions, reshares, .. } = &mut *state;
+                // 只在投诉轮之后、为会话中的其他参与者公开
+                let share = if let Some(node_session) = sessions.get_mut(&session) {
+                    let participant = &node_session.participant;
+                    if node_session.verified.is_none() {
+                        return Err(dkg_error(format!("session {} has not collected complaints yet", session)));
+                    }
+                    if accuser == participant.index || !participant.participants.iter().any(|p| p.index == accuser) {
+                        return Err(dkg_error(format!("{} is not another participant of session {}", accuser, session)));
+                    }
+                    let share = participant.share_for(accuser).map_err(dkg_error)?;
+                    record_reveal(&mut node_session.revealed, accuser, participant.threshold, &session)?;
+                    share
+                } else if let Some(reshare) = reshares.get_mut(&session) {
+                    let dealer = reshare.dealer.as_ref().ok_or_else(|| dkg_error(format!("not a dealer of session {}", session)))?;
+                    let share = dealer.share_for(accuser).map_err(dkg_error)?;
+                    record_reveal(&mut reshare.revealed, accuser, dealer.threshold(), &session)?;
+                    share
+                } else {
+                    return Err(dkg_error(format!("unknown session {}", session)));
+                };
+                println!("DKG {}: revealing share for participant {}", session, accuser);
+                Ok(DkgMessage::Revealed { session, share })
+            }
+            DkgMessage::Finalize { session, qualified, revealed, group_id } => self.finalize(session, qualified, revealed, group_id),
+            DkgMessage::ReshareStart { session, group, threshold, recipients } => {
+                self.reshare_start(session, group, threshold, recipients, &keys.current()).await
+            }
+            DkgMessage::ReshareVerify { session, index, threshold, recipients, group, commitments } => {
+                let mut state = self.state.lock().unwrap();
+                if state.sessions.contains_key(&session) || self.share_path(&session).exists() {
+                    return Err(dkg_error(format!("session {} is already in use on this node", session)));
+                }
+                if state.reshares.get(&session).is_some_and(|reshare| reshare.recipient.is_some()) {
+                    return Err(dkg_error(format!("session {} has already been verified", session)));
+                }
+                let recipient =
+                    ReshareRecipient::new(index, threshold, &recipients, group, commitments).map_err(dkg_error)?;
+                state.pending_since.remove(&session);
+                let received = select_deals(index, &recipient.commitments, state.inbox.remove(&session));
+                let against = recipient.complaints(&received);
+                state.reshares.entry(session.clone()).or_default().recipient = Some((recipient, received));
+                Ok(DkgMessage::Complaints { session, against })
+            }
+            DkgMessage::ReshareFinalize { session, qualified, revealed, group_id, generation } => {
+                self.reshare_finalize(session, qualified, revealed, group_id, generation, keys)
+            }
+            other => Err(ProtocolError::new(
+                ErrorCode::UnexpectedMessage,
+                format!("unexpected DKG message {:?}", other),
+            )),
+        }
+    }
+
+    async fn start(
+        &self,
+        session: String,
+        threshold: usize,
+        index: u32,
+        participants: Vec<Participant>,
+    ) -> Result<DkgMessage, ProtocolError> {
+        validate_session(&session).map_err(dkg_error)?;
+        require_tls(&self.transport).map_err(dkg_error)?;
+        if self.share_path(&session).exists() {
+            return Err(dkg_error(format!("session {} already has a share on this node", session)));
+        }
+
+        // 持锁期间只做计算，发送份额前释放
+        let (commitments, deals) = {
+            let mut state = self.state.lock().unwrap();
+            if state.sessions.contains_key(&session) || state.reshares.contains_key(&session) {
+                return Err(dkg_error(format!("session {} already started", session)));
+            }
+            let participant = DkgParticipant::new(&session, threshold, index, participants).map_err(dkg_error)?;
+            state.adopt_pending(&session, |dealer| participant.participants.iter().any(|p| p.index == dealer), threshold);
+            let commitments = participant.commitments().to_vec();
+            let mut deals = Vec::new();
+            for peer in &participant.participants {
+                let share = participant.share_for(peer.index).map_err(dkg_error)?;
+                if peer.index == index {
+                    state
+                        .inbox
+                        .entry(session.clone())
+                        .or_default()
+                        .entry(index)
+                        .or_default()
+                        .insert(0, ReceivedDeal { commitments: commitments.clone(), share });
+                    continue;
+                }
+                let deal = DkgMessage::Deal { session: session.clone(), dealer: index, commitments: commitments.clone(), share };
+                deals.push((peer.address.clone(), deal));
+            }
+            state.sessions.insert(session.clone(), NodeSession { participant, verified: None, revealed: BTreeSet::new() });
+            (commitments, deals)
+        };
+
+        println!("DKG {}: dealing shares as participant {}", session, index);
+        for (address, deal) in deals {
+            // 送达失败的接收者会在 Verify 轮投诉，由公开份额解决
+            match dkg_request(&self.transport, &address, deal, None).await {
+                Ok(DkgMessage::DealAccepted { .. }) => {}
+                Ok(reply) => eprintln!("DKG {}: unexpected reply from {}: {:?}", session, address, reply),
+                Err(e) => eprintln!("DKG {}: failed to deliver share to {}: {}", session, address, e),
+            }
+        }
+        Ok(DkgMessage::Dealt { session, commitments })
+    }
+
+    fn finalize(
+        &self,
+        session: String,
+        qualified: Vec<u32>,
+        revealed: Vec<RevealedShare>,
+        group_id: String,
+    ) -> Result<DkgMessage, ProtocolError> {
+        let mut state = self.state.lock().unwrap();
+        let node_session = state.sessions.get(&session).ok_or_else(|| dkg_error(format!("unknown session {}", session)))?;
+        let (commitments, received) = node_session
+            .verified
+            .as_ref()
+            .ok_or_else(|| dkg_error(format!("session {} has not been verified", session)))?;
+        let qualified: BTreeSet<u32> = qualified.into_iter().collect();
+        let share = node_session
+            .participant
+            .finalize(commitments, &qualified, received, &revealed, &group_id)
+            .map_err(dkg_error)?;
+
+        self.save_share(&session, &share)?;
+        state.sessions.remove(&session);
+        Ok(finalized(session, share))
+    }
+
+    fn save_share(&self, session: &str, share: &DkgShare) -> Result<(), ProtocolError> {
+        let path = self.share_path(session);
+        if path.exists() {
+            return Err(dkg_error(format!("{} already exists", path.display())));
+        }
+        keystore::save(&path, &share.signer, &self.password).map_err(dkg_error)?;
+        println!("DKG {}: saved key share to {}", session, path.display());
+        Ok(())
+    }
+
+    async fn reshare_start(
+        &self,
+        session: String,
+        group: ThresholdGroup,
+        threshold: usize,
+        recipients: Vec<Participant>,
+        signer: &Signer,
+    ) -> Result<DkgMessage, ProtocolError> {
+        validate_session(&session).map_err(dkg_error)?;
+        require_tls(&self.transport).map_err(dkg_error)?;
+        // 只按操作员批准的计划分发，计划用过即删除
+        let plan_path = ResharePlan::path(&self.dir, &session);
+        let plan = ResharePlan::read(&plan_path)
+            .map_err(|e| dkg_error(format!("no approved reshare plan for session {}: {}", session, e)))?;
+        plan.check(&session, &group, threshold, &recipients).map_err(dkg_error)?;
+        let (dealer_index, commitments, deals) = {
+            let mut state = self.state.lock().unwrap();
+            if state.sessions.contains_key(&session) || state.reshares.get(&session).is_some_and(|r| r.dealer.is_some()) {
+                return Err(dkg_error(format!("session {} already started", session)));
+            }
+            let dealer = ReshareDealer::new(signer, &group, threshold, &recipients).map_err(dkg_error)?;
+            fs::remove_file(&plan_path).map_err(dkg_error)?;
+            state.adopt_pending(&session, |index| dealer.is_dealer(index), threshold);
+            let commitments = dealer.commitments().to_vec();
+            let mut deals = Vec::with_capacity(recipients.len());
+            for recipient in &recipients {
+                let share = dealer.share_for(recipient.index).map_err(dkg_error)?;
+                let deal = DkgMessage::Deal { session: session.clone(), dealer: dealer.index, commitments: commitments.clone(), share };
+                deals.push((recipient.address.clone(), deal));
+            }
+            let dealer_index = dealer.index;
+            state.reshares.entry(session.clone()).or_default().dealer = Some(dealer);
+            (dealer_index, commitments, deals)
+        };
+
+        println!("Reshare {}: dealing share {} to {} recipients", session, dealer_index, deals.len());
+        for (address, deal) in deals {
+            match dkg_request(&self.transport, &address, deal, None).await {
+                Ok(DkgMessage::DealAccepted { .. }) => {}
+                Ok(reply) => eprintln!("Reshare {}: unexpected reply from {}: {:?}", session, address, reply),
+                Err(e) => eprintln!("Reshare {}: failed to deliver share to {}: {}", session, address, e),
+            }
+        }
+        Ok(DkgMessage::ReshareDealt { session, dealer: dealer_index, commitments })
+    }
+
+    // 新委员会的参与者保存新份额; 只作为分发者的节点丢弃会话状态。分发过的节点随后作废旧份额
+    fn reshare_finalize(
+        &self,
+        session: String,
+        qualified: Vec<u32>,
+        revealed: Vec<RevealedShare>,
+        group_id: String,
+        generation: u64,
+        keys: &KeySchedule,
+    ) -> Result<DkgMessage, ProtocolError> {
+        let mut state = self.state.lock().unwrap();
+        let reshare = state.reshares.get(&session).ok_or_else(|| dkg_error(format!("unknown session {}", session)))?;
+        let old_share = reshare.dealer.as_ref().map(|dealer| dealer.share_public_key);
+        let (reply, new_share) = match &reshare.recipient {
+            Some((recipient, received)) => {
+                let qualified: BTreeSet<u32> = qualified.into_iter().collect();
+                let share =
+                    recipient.finalize(&qualified, received, &revealed, &group_id, generation).map_err(dkg_error)?;
+                self.save_share(&session, &share)?;
+                let new_share = Signer::new(PrivateKey(share.signer.private_key().0));
+                (finalized(session.clone(), share), Some(new_share))
+            }
+            None => {
+                state.inbox.remove(&session);
+                (DkgMessage::Closed { session: session.clone() }, None)
+            }
+        };
+        state.reshares.remove(&session);
+        if let Some(old_share) = old_share {
+            self.retire_share(&session, &old_share, new_share, keys);
+        }
+        Ok(reply)
+    }
+
+    // 作废旧份额。节点密钥文件中是旧份额时: 有新份额则把文件换成新份额，并让运行中的节点换用新份额签名;
+    // 没有新份额 (节点离开委员会) 则作废文件并留下标记，重启时不会在该路径静默生成新密钥。
+    // DKG 目录中公钥为旧份额公钥的份额文件用零覆盖后删除。
+    // 信封中的公钥不需要口令即可读取; 新份额已经保存，作废失败只记录日志，不影响会话结果
+    fn retire_share(&self, session: &str, old_share: &PublicKey, new_share: Option<Signer>, keys: &KeySchedule) {
+        let holds_old_share = |path: &PathBuf| {
+            EncryptedKeystore::read(path)
+                .and_then(|envelope| envelope.public_key())
+                .is_ok_and(|public_key| public_key.0 == old_share.0)
+        };
+        if let Some(path) = self.keystore.as_ref().filter(|path| holds_old_share(path)) {
+            let result = match &new_share {
+                Some(share) => keystore::replace(path, share, &self.password),
+                None => keystore::retire(path, &format!("its share left the committee in reshare session {}", session)),
+            };
+            match result {
+                Ok(()) if new_share.is_some() => println!("Reshare {}: replaced the old share in {}", session, path.display()),
+                Ok(()) => println!("Reshare {}: retired the old share in {}", session, path.display()),
+                Err(e) => eprintln!("Reshare {}: failed to retire old share {}: {}", session, path.display(), e),
+            }
+        }
+        match new_share.map(|share| keys.replace(old_share, Arc::new(share))) {
+            Some(true) => println!("Reshare {}: signing with the new share", session),
+            Some(false) => {}
+            None => eprintln!("Reshare {}: this node left the committee and no longer holds a share", session),
+        }
+
+        let Ok(entries) = fs::read_dir(&self.dir) else { return };
+        let paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect();
+        for path in paths.iter().filter(|path| holds_old_share(path)) {
+            match keystore::destroy(path) {
+                Ok(()) => println!("Reshare {}: retired old share {}", session, path.display()),
+                Err(e) => eprintln!("Reshare {}: failed to retire old share {}: {}", session, path.display(), e),
+            }
+        }
+    }
+}
+
+fn finalized(session: String, share: DkgShare) -> DkgMessage {
+    DkgMessage::Finalized {
+        session,
+        share_public_key: share.signer.public_key(),
+        group_public_key: share.group_public_key,
+        proof_share: share.proof_share,
+    }
+}
+
+fn unexpected_reply(addr: &str, reply: &DkgMessage) -> io::Error {
+    invalid_data(format!("unexpected DKG reply from {}: {:?}", addr, reply))
+}
+
+// 要求被投诉的分发者公开份额: 有效的公开份额按投诉者归类返回，无效或拒绝公开的分发者从 qualified 中移除
+pub(crate) async fn resolve_complaints(
+    coordinator: &DkgCoordinator,
+    session: &str,
+    complaints: &[(u32, u32)],
+    commitments: &[DealerCommitments],
+    dealers: &BTreeMap<u32, String>,
+    qualified: &mut BTreeSet<u32>,
+) -> BTreeMap<u32, Vec<RevealedShare>> {
+    let mut revealed: BTreeMap<u32, Vec<RevealedShare>> = BTreeMap::new();
+    for &(accuser, dealer) in complaints {
+        if !qualified.contains(&dealer) {
+            continue;
+        }
+        let (dealer_commitments, address) = match (commitments.iter().find(|c| c.dealer == dealer), dealers.get(&dealer)) {
+            (Some(dealer_commitments), Some(address)) => (&dealer_commitments.commitments, address),
+            _ => continue,
+        };
+        let reveal = DkgMessage::Reveal { session: session.to_string(), accuser };
+        match coordinator.request(address, reveal).await {
+            Ok(DkgMessage::Revealed { share, .. }) if verify_share(dealer_commitments, accuser, share) => {
+                println!("DKG {}: dealer {} revealed a valid share for participant {}", session, dealer, accuser);
+                revealed.entry(accuser).or_default().push(RevealedShare { dealer, share });
+            }
+            _ => {
+                println!("DKG {}: dealer {} disqualified", session, dealer);
+                qualified.remove(&dealer);
+            }
+        }
+    }
+    revealed
+}
+
+// DKG 结果: 群信息以及带门限持有证明的群登记请求
+pub struct DkgOutcome {
+    pub group: ThresholdGroup,
+    pub qualified: Vec<u32>,
+    pub registration: GroupRegistration,
+}
+
+// 协调一次 DKG; nodes 中第 i 个节点的份额序号为 i + 1，所有节点都必须在线。
+// 协调者只转发承诺、投诉和公开的份额，不接触任何私密份额
+pub async fn run_dkg(
+    coordinator: &DkgCoordinator,
+    session: &str,
+    threshold: usize,
+    nodes: &[String],
+    group_id: &str,
+) -> io::Result<DkgOutcome> {
+    validate_session(session)?;
+    let participants: Vec<Participant> = nodes
+        .iter()
+        .enumerate()
+        .map(|(position, address)| Participant { index: position as u32 + 1, address: address.clone() })
+        .collect();
+    validate_threshold(threshold, participants.len())?;
+
+    // 第 1 轮: 各节点分发份额并返回承诺
+    let mut commitments = Vec::new();
+    for participant in &participants {
+        let start = DkgMessage::Start {
+            session: session.to_string(),
+            threshold,
+            index: participant.index,
+            participants: participants.clone(),
+        };
+        match coordinator.request(&participant.address, start).await? {
+            DkgMessage::Dealt { commitments: dealer_commitments, .. } if dealer_commitments.len() == threshold => {
+                commitments.push(DealerCommitments { dealer: participant.index, commitments: dealer_commitments });
+            }
+            reply => return Err(unexpected_reply(&participant.address, &reply)),
+        }
+    }
+
+    // 第 2 轮: 下发统一的承诺集合，收集投诉
+    let mut complaints = Vec::new();
+    for participant in &participants {
+        let verify = DkgMessage::Verify { session: session.to_string(), commitments: commitments.clone() };
+        match coordinator.request(&participant.address, verify).await? {
+            DkgMessage::Complaints { against, .. } => {
+                for dealer in against {
+                    println!("DKG {}: participant {} complains about dealer {}", session, participant.index, dealer);
+                    complaints.push((participant.index, dealer));
+                }
+            }
+            reply => return Err(unexpected_reply(&participant.address, &reply)),
+        }
+    }
+
+    // 第 3 轮: 被投诉的分发者公开份额，公开的份额无效或拒绝公开则取消资格
+    let mut qualified: BTreeSet<u32> = participants.iter().map(|participant| participant.index).collect();
+    let dealers: BTreeMap<u32, String> =
+        participants.iter().map(|participant| (participant.index, participant.address.clone())).collect();
+    let mut revealed = resolve_complaints(coordinator, session, &complaints, &commitments, &dealers, &mut qualified).await;
+    if qualified.len() < threshold {
+        return Err(invalid_data(format!(
+            "only {} qualified dealers remain, fewer than the threshold {}",
+            qualified.len(),
+            threshold
+        )));
+    }
+
+    // 第 4 轮: 各节点计算自己的份额，并对群公钥持有证明做部分签名
+    let qualified_list: Vec<u32> = qualified.iter().copied().collect();
+    let indices: Vec<u32> = participants.iter().map(|participant| participant.index).collect();
+    let group = weighted_group(&commitments, &unit_weights(&qualified), threshold, &indices)?;
+    let registration = finalize_round(
+        coordinator,
+        &participants,
+        group,
+        group_id,
+        0,
+        |participant| DkgMessage::Finalize {
+            session: session.to_string(),
+            qualified: qualified_list.clone(),
+            revealed: revealed.remove(&participant.index).unwrap_or_default(),
+            group_id: group_id.to_string(),
+        },
+    )
+    .await?;
+
+    Ok(DkgOutcome { group: registration.group.clone(), qualified: qualified_list, registration })
+}
+
+// 向各参与者发送 finalize 生成的消息，核对返回的份额公钥和群公钥是否与承诺算出的 group 一致，
+// 并把门限个持有证明部分签名合成为群登记请求
+pub(crate) async fn finalize_round<F>(
+    coordinator: &DkgCoordinator,
+    participants: &[Participant],
+    group: ThresholdGroup,
+    group_id: &str,
+    generation: u64,
+    mut finalize: F,
+) -> io::Result<GroupRegistration>
+where
+    F: FnMut(&Participant) -> DkgMessage,
+{
+    let group_public_key = group.group_public_key;
+    let pop = group_pop_message(&group, group_id, generation)?;
+    let mut proof_shares = Vec::new();
+    for (participant, share) in participants.iter().zip(&group.shares) {
+        let expected = share.public_key;
+        match coordinator.request(&participant.address, finalize(participant)).await? {
+            DkgMessage::Finalized { session, share_public_key, group_public_key: reported, proof_share }
+                if share_public_key.0 == expected.0 && reported.0 == group_public_key.0 =>
+            {
+                if verify_signature(&pop, &proof_share, &expected) {
+                    proof_shares.push((participant.index, proof_share));
+                } else {
+                    eprintln!("DKG {}: invalid proof share from participant {}", session, participant.index);
+                }
+            }
+            reply => return Err(unexpected_reply(&participant.address, &reply)),
+        }
+    }
+
+    // 合成群公钥的持有证明，用于在公钥收集服务中登记
+    if proof_shares.len() < group.threshold {
+        return Err(invalid_data("not enough valid proof-of-possession shares"));
+    }
+    proof_shares.truncate(group.threshold);
+    let registration = GroupRegistration {
+        group_id: group_id.to_string(),
+        proof_of_possession: combine(&proof_shares)?,
+        group,
+        generation,
+    };
+    if !registration.verify() {
+        return Err(invalid_data("combined proof of possession does not verify under the group public key"));
+    }
+    Ok(registration)
+}
+
+// Fr::to_big_endian 输出的是 Montgomery 形式，与 Fr::from_slice 不互逆，这里按标准形式编码
+pub mod fr_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use substrate_bn::arith::U256;
+    use substrate_bn::Fr;
+
+    pub fn serialize<S: Serializer>(value: &Fr, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = [0u8; 32];
+        value
+            .into_u256()
+            .to_big_endian(&mut bytes)
+            .map_err(|e| serde::ser::Error::custom(format!("{:?}", e)))?;
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+        let bytes = hex::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)?;
+        let value = U256::from_slice(&bytes).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))?;
+        Fr::new(value).ok_or_else(|| serde::de::Error::custom("scalar is not reduced modulo the group order"))
+    }
+}
+
+pub mod public_keys_hex {
+    use bn254::PublicKey;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::ser::SerializeSeq;
+    use crate::keys::{public_key_from_hex, public_key_to_hex};
+
+    pub fn serialize<S: Serializer>(public_keys: &[PublicKey], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(public_keys.len()))?;
+        for public_key in public_keys {
+            seq.serialize_element(&public_key_to_hex(public_key).map_err(serde::ser::Error::custom)?)?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PublicKey>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|public_key| public_key_from_hex(public_key).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
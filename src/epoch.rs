@@ -0,0 +1,137 @@
+// 密钥纪元
+//
+// 纪元按公钥收集服务的时钟划分: 纪元 N 覆盖 [N·length, (N+1)·length) 毫秒。
+// 节点轮换密钥时先生成新密钥并登记到之后的某个纪元，到达该纪元前继续使用原密钥，
+// 签名请求携带纪元，节点用该纪元的密钥签名
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use bn254::PublicKey;
+use serde::{Deserialize, Serialize};
+use crate::keystore;
+use crate::request::now_millis;
+use crate::signer::Signer;
+
+// 公钥收集服务默认的纪元长度: 一天
+pub const DEFAULT_EPOCH_LENGTH_MS: u64 = 24 * 60 * 60 * 1000;
+
+pub fn epoch_at(now: u64, epoch_length_ms: u64) -> u64 {
+    now / epoch_length_ms.max(1)
+}
+
+// 公钥收集服务报告的当前纪元
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct EpochInfo {
+    pub epoch: u64,
+    pub epoch_length_ms: u64,
+    // 下一纪元开始的时间 (毫秒)
+    pub next_epoch_at: u64,
+}
+
+impl EpochInfo {
+    pub fn at(now: u64, epoch_length_ms: u64) -> Self {
+        let epoch = epoch_at(now, epoch_length_ms);
+        EpochInfo { epoch, epoch_length_ms, next_epoch_at: (epoch + 1) * epoch_length_ms.max(1) }
+    }
+}
+
+// 节点的密钥安排: 每个密钥从其纪元起使用，直到下一个密钥的纪元
+pub struct KeySchedule {
+    keys: RwLock<BTreeMap<u64, Arc<Signer>>>,
+    epoch_length_ms: Option<u64>,
+}
+
+impl KeySchedule {
+    // 只有一个密钥，对所有纪元有效
+    pub fn new(signer: Arc<Signer>) -> Self {
+        KeySchedule { keys: RwLock::new(BTreeMap::from([(0, signer)])), epoch_length_ms: None }
+    }
+
+    // 按纪元长度从本地时钟确定当前纪元，签名请求未携带纪元时使用当前纪元的密钥
+    pub fn with_epoch_length(mut self, epoch_length_ms: u64) -> Self {
+        self.epoch_length_ms = Some(epoch_length_ms);
+        self
+    }
+
+    pub fn insert(&self, epoch: u64, signer: Arc<Signer>) {
+        self.keys.write().unwrap().insert(epoch, signer);
+    }
+
+    // 把公钥为 old 的密钥替换为 signer (重分享后换用新份额)，返回是否替换
+    pub fn replace(&self, old: &PublicKey, signer: Arc<Signer>) -> bool {
+        let mut keys = self.keys.write().unwrap();
+        let mut replaced = false;
+        for key in keys.values_mut().filter(|key| key.public_key().0 == old.0) {
+            *key = signer.clone();
+            replaced = true;
+        }
+        replaced
+    }
+
+    pub fn current_epoch(&self) -> Option<u64> {
+        self.epoch_length_ms.map(|length| epoch_at(now_millis(), length))
+    }
+
+    // epoch 纪元使用的密钥; 早于所有安排时使用最早的密钥。
+    // epoch 晚于当前纪元时返回 None，尚未生效的密钥不能提前签名;
+    // 不知道纪元长度时无法判断当前纪元，只使用最早的密钥
+    pub fn signer_at(&self, epoch: u64) -> Option<Arc<Signer>> {
+        let epoch = match self.current_epoch() {
+            Some(current) if epoch > current => return None,
+            Some(_) => epoch,
+            None => 0,
+        };
+        Some(self.started_at(epoch))
+    }
+
+    pub fn current(&self) -> Arc<Signer> {
+        self.started_at(self.current_epoch().unwrap_or(0))
+    }
+
+    fn started_at(&self, epoch: u64) -> Arc<Signer> {
+        let keys = self.keys.read().unwrap();
+        keys.range(..=epoch)
+            .next_back()
+            .or_else(|| keys.iter().next())
+            .map(|(_, signer)| signer.clone())
+            .expect("key schedule is never empty")
+    }
+
+    // 已安排的纪元，升序
+    pub fn epochs(&self) -> Vec<u64> {
+        self.keys.read().unwrap().keys().copied().collect()
+    }
+}
+
+// 安排在 epoch 纪元生效的密钥文件: 与节点密钥文件同目录，文件名后加 .epoch-<N>
+pub fn epoch_keystore_path(keystore: &Path, epoch: u64) -> PathBuf {
+    let mut name = keystore.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".epoch-{}", epoch));
+    keystore.with_file_name(name)
+}
+
+// 读取节点密钥文件旁所有已安排的密钥，按纪元升序
+pub fn load_epoch_keys(keystore: &Path, password: &str) -> io::Result<Vec<(u64, Signer)>> {
+    let prefix = format!("{}.epoch-", keystore.file_name().unwrap_or_default().to_string_lossy());
+    let dir = match keystore.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let epoch = match name.strip_prefix(&prefix).map(str::parse::<u64>) {
+            Some(Ok(epoch)) => epoch,
+            _ => continue,
+        };
+        keys.push((epoch, keystore::load(&path, password)?));
+    }
+    keys.sort_by_key(|(epoch, _)| *epoch);
+    Ok(keys)
+}
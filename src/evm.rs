@@ -0,0 +1,174 @@
+// 链上验证: 生成使用 ecPairing 预编译合约 (0x08) 的 Solidity 验证合约，
+// 以及调用该合约 verify 函数的 ABI calldata
+//
+// 预编译合约要求坐标为大端 32 字节; G2 的每个坐标按 (虚部, 实部) 排列，
+// 即 (x.imaginary, x.real, y.imaginary, y.real)。无穷远点编码为全零
+use std::io;
+use bn254::{PublicKey, Signature};
+use num_bigint::BigUint;
+use substrate_bn::{AffineG1, AffineG2, Fq, Group, G1, G2};
+use crate::hash::HashScheme;
+use crate::keys::invalid_data;
+
+pub type Word = [u8; 32];
+
+// bytes4(keccak256("verify(uint256[2],uint256[4],uint256[2])"))
+pub const VERIFY_SELECTOR: [u8; 4] = [0x75, 0x5f, 0x89, 0xcf];
+
+const CONTRACT_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+// Generated by the dss client. Do not edit.
+pragma solidity ^0.8.0;
+
+contract __CONTRACT_NAME__ {
+    // Negated G2 generator (x.imaginary, x.real, y.imaginary, y.real)
+    uint256 internal constant NEG_G2_X_IMAG = __NEG_G2_X_IMAG__;
+    uint256 internal constant NEG_G2_X_REAL = __NEG_G2_X_REAL__;
+    uint256 internal constant NEG_G2_Y_IMAG = __NEG_G2_Y_IMAG__;
+    uint256 internal constant NEG_G2_Y_REAL = __NEG_G2_Y_REAL__;
+
+    // Checks e(signature, -g2) * e(messagePoint, publicKey) == 1.
+    // messagePoint must be the hash of the message to G1 under the scheme recorded with the request.
+    function verify(
+        uint256[2] calldata signature,
+        uint256[4] calldata publicKey,
+        uint256[2] calldata messagePoint
+    ) external view returns (bool) {
+        uint256[12] memory input = [
+            signature[0], signature[1],
+            NEG_G2_X_IMAG, NEG_G2_X_REAL, NEG_G2_Y_IMAG, NEG_G2_Y_REAL,
+            messagePoint[0], messagePoint[1],
+            publicKey[0], publicKey[1], publicKey[2], publicKey[3]
+        ];
+        uint256[1] memory out;
+        bool success;
+        assembly {
+            success := staticcall(gas(), 0x08, input, 384, out, 0x20)
+        }
+        return success && out[0] == 1;
+    }
+}
+"#;
+
+fn fq_word(value: Fq) -> io::Result<Word> {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word).map_err(|e| invalid_data(format!("{:?}", e)))?;
+    Ok(word)
+}
+
+pub fn g1_words(point: &G1) -> io::Result<[Word; 2]> {
+    match AffineG1::from_jacobian(*point) {
+        Some(affine) => Ok([fq_word(affine.x())?, fq_word(affine.y())?]),
+        None => Ok([[0u8; 32]; 2]),
+    }
+}
+
+pub fn g2_words(point: &G2) -> io::Result<[Word; 4]> {
+    match AffineG2::from_jacobian(*point) {
+        Some(affine) => Ok([
+            fq_word(affine.x().imaginary())?,
+            fq_word(affine.x().real())?,
+            fq_word(affine.y().imaginary())?,
+            fq_word(affine.y().real())?,
+        ]),
+        None => Ok([[0u8; 32]; 4]),
+    }
+}
+
+pub fn word_to_decimal(word: &Word) -> String {
+    BigUint::from_bytes_be(word).to_string()
+}
+
+// verify(signature, publicKey, messagePoint) 的参数
+#[derive(Clone, Copy, Debug)]
+pub struct VerifyCall {
+    pub signature: Signature,
+    pub public_key: PublicKey,
+    pub message_point: G1,
+}
+
+impl VerifyCall {
+    pub fn new(scheme: &HashScheme, message: &[u8], signature: Signature, public_key: PublicKey) -> io::Result<Self> {
+        Ok(VerifyCall {
+            signature,
+            public_key,
+            message_point: scheme.hash_to_g1(message)?,
+        })
+    }
+
+    // 按参数顺序排列的 8 个 uint256
+    pub fn words(&self) -> io::Result<Vec<Word>> {
+        let mut words = Vec::with_capacity(8);
+        words.extend(g1_words(&self.signature.0)?);
+        words.extend(g2_words(&self.public_key.0)?);
+        words.extend(g1_words(&self.message_point)?);
+        Ok(words)
+    }
+
+    // 定长数组按值内联编码，calldata 为函数选择器加 8 个字
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut calldata = VERIFY_SELECTOR.to_vec();
+        for word in self.words()? {
+            calldata.extend_from_slice(&word);
+        }
+        Ok(calldata)
+    }
+}
+
+// 与 Solidity 合约相同 ABI 的手写运行时字节码，不依赖 solc 即可部署，用于本地测试
+//
+//   校验函数选择器和 calldata 长度，按 [signature, -g2, messagePoint, publicKey]
+//   拼接配对输入，staticcall 0x08，返回 uint256(success && out == 1)
+pub fn verifier_runtime_code() -> io::Result<Vec<u8>> {
+    let mut code = vec![
+        0x60, 0x00, 0x35, 0x60, 0xe0, 0x1c, // PUSH1 0 CALLDATALOAD PUSH1 224 SHR
+        0x63, VERIFY_SELECTOR[0], VERIFY_SELECTOR[1], VERIFY_SELECTOR[2], VERIFY_SELECTOR[3], // PUSH4 selector
+        0x14, 0x60, 0x14, 0x57, // EQ PUSH1 ok JUMPI
+        0x5b, 0x60, 0x00, 0x80, 0xfd, // 0x0f: JUMPDEST PUSH1 0 DUP1 REVERT
+        0x5b, // 0x14 ok: JUMPDEST
+        0x61, 0x01, 0x04, 0x36, 0x10, 0x60, 0x0f, 0x57, // PUSH2 260 CALLDATASIZE LT PUSH1 0x0f JUMPI
+        0x60, 0x40, 0x60, 0x04, 0x60, 0x00, 0x37, // signature -> mem[0x00..0x40]
+    ];
+    // -g2 -> mem[0x40..0xc0]
+    for (i, word) in g2_words(&-G2::one())?.iter().enumerate() {
+        code.push(0x7f); // PUSH32
+        code.extend_from_slice(word);
+        code.extend_from_slice(&[0x60, 0x40 + 0x20 * i as u8, 0x52]); // PUSH1 offset MSTORE
+    }
+    code.extend_from_slice(&[
+        0x60, 0x40, 0x60, 0xc4, 0x60, 0xc0, 0x37, // messagePoint -> mem[0xc0..0x100]
+        0x60, 0x80, 0x60, 0x44, 0x61, 0x01, 0x00, 0x37, // publicKey -> mem[0x100..0x180]
+        0x60, 0x20, 0x60, 0x00, 0x61, 0x01, 0x80, 0x60, 0x00, 0x60, 0x08, 0x5a, 0xfa, // STATICCALL(gas, 8, 0, 384, 0, 32)
+        0x60, 0x00, 0x51, 0x60, 0x01, 0x14, 0x16, // MLOAD(0) == 1, AND success
+        0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3, // MSTORE(0) RETURN(0, 32)
+    ]);
+    Ok(code)
+}
+
+// 部署交易的 init code: 把运行时字节码拷贝到内存并返回
+pub fn verifier_init_code() -> io::Result<Vec<u8>> {
+    let runtime = verifier_runtime_code()?;
+    let len = (runtime.len() as u16).to_be_bytes();
+    // PUSH2 len DUP1 PUSH2 13 PUSH1 0 CODECOPY PUSH1 0 RETURN
+    let mut code = vec![0x61, len[0], len[1], 0x80, 0x61, 0x00, 0x0d, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3];
+    code.extend_from_slice(&runtime);
+    Ok(code)
+}
+
+pub fn solidity_verifier(contract_name: &str) -> io::Result<String> {
+    let valid_name = contract_name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && contract_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid contract name {:?}", contract_name),
+        ));
+    }
+
+    let neg_g2 = g2_words(&-G2::one())?;
+    Ok(CONTRACT_TEMPLATE
+        .replace("__CONTRACT_NAME__", contract_name)
+        .replace("__NEG_G2_X_IMAG__", &word_to_decimal(&neg_g2[0]))
+        .replace("__NEG_G2_X_REAL__", &word_to_decimal(&neg_g2[1]))
+        .replace("__NEG_G2_Y_IMAG__", &word_to_decimal(&neg_g2[2]))
+        .replace("__NEG_G2_Y_REAL__", &word_to_decimal(&neg_g2[3])))
+}
@@ -4,7 +4,8 @@
 use core::slice;
 use std::ops::Neg;
 use num_bigint::BigUint;
-use bn254::{PrivateKey, PublicKey, ECDSA, hash_to_try_and_increment};
+use bn254::{PrivateKey, PublicKey, ECDSA};
+use dss_core::hash::hash_to_try_and_increment;
 use k256::{elliptic_curve::bigint::Encoding, U256};
 use substrate_bn::{Group, G2};
 use rand_core::OsRng;
@@ -176,11 +177,11 @@ fn check_aggregate() {
     hash_point.z().to_big_endian(&mut slice).unwrap();
     println!("message.z(): {:?}", BigUint::from_bytes_be(&slice));
 
-    let mut signature1 = ECDSA::sign(&message, &private_key1).unwrap();
+    let mut signature1 = ECDSA::sign(message, &private_key1).unwrap();
     signature1.0.normalize();
-    let mut signature2 = ECDSA::sign(&message, &private_key2).unwrap();
+    let mut signature2 = ECDSA::sign(message, &private_key2).unwrap();
     signature2.0.normalize();
-    let mut signature3 = ECDSA::sign(&message, &private_key3).unwrap();
+    let mut signature3 = ECDSA::sign(message, &private_key3).unwrap();
     signature3.0.normalize();
 
     let mut aggregated_signature = signature1 + signature2 + signature3;
@@ -0,0 +1,88 @@
+// 进程内的节点委员会: 各节点通过节点服务运行 DKG，然后用得到的份额重启节点并完成一次门限签名。
+// DKG 只经双向 TLS 进行，节点只接受允许的协调者，这里所有节点和协调者共用本地 CA 签发的一张证书
+//
+// cargo run --example dkg_committee
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use zeroize::Zeroizing;
+use dss_core::Signer;
+use dss_core::auth::{AggregatorIdentity, RequestAuthenticator};
+use dss_core::dkg::{run_dkg, DkgCoordinator, DkgNode};
+use dss_core::hash::HashScheme;
+use dss_core::keys::public_key_to_hex;
+use dss_core::keystore;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::threshold::ThresholdAggregator;
+use dss_core::tls::{create_ca, issue_certificate, TlsClient, TlsIdentity, TlsServer, Transport};
+use dss_core::verifier::verify_signature_with;
+
+const NODES: usize = 5;
+const THRESHOLD: usize = 3;
+const PASSWORD: &str = "dkg-committee-example";
+
+// 先绑定监听套接字再交给节点服务，返回时节点已可连接
+async fn spawn_service(service: NodeService) -> std::io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?.to_string();
+    tokio::spawn(async move {
+        service.serve(listener).await.unwrap();
+    });
+    Ok(addr)
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let session = format!("example-{}", uuid::Uuid::new_v4());
+    let dir = std::env::temp_dir().join(&session);
+    std::fs::create_dir_all(&dir)?;
+
+    let ca_dir = dir.join("ca");
+    create_ca(&ca_dir)?;
+    let issued = issue_certificate(&ca_dir, "committee", &["127.0.0.1".to_string()], &ca_dir)?;
+    let tls_identity = || TlsIdentity::load(&ca_dir.join("ca.pem"), &issued.cert, &issued.key);
+    let transport = || -> std::io::Result<Transport> {
+        Ok(Transport::Tls(Arc::new(TlsClient::new(tls_identity()?, Default::default())?)))
+    };
+    let identity = AggregatorIdentity::random();
+    let auth = Arc::new(RequestAuthenticator::new(vec![identity.public_key()]));
+    let coordinator = DkgCoordinator::new(transport()?, identity)?;
+
+    // 启用 DKG 的节点，份额都保存在同一临时目录下的不同子目录
+    let mut dkg_nodes = Vec::new();
+    let mut addrs = Vec::new();
+    for i in 0..NODES {
+        let node_dir = dir.join(format!("node-{}", i + 1));
+        std::fs::create_dir_all(&node_dir)?;
+        let dkg = Arc::new(DkgNode::new(node_dir, Zeroizing::new(PASSWORD.to_string())).with_transport(transport()?));
+        let tls = Arc::new(TlsServer::new(tls_identity()?, Vec::new())?);
+        let service =
+            NodeService::from_signer(Arc::new(Signer::random())).with_dkg(dkg.clone()).with_tls(tls).with_authenticator(auth.clone());
+        addrs.push(spawn_service(service).await?);
+        dkg_nodes.push(dkg);
+    }
+
+    let outcome = run_dkg(&coordinator, &session, THRESHOLD, &addrs, "example-group").await?;
+    println!("qualified dealers: {:?}", outcome.qualified);
+    println!("group public key: {}", public_key_to_hex(&outcome.group.group_public_key)?);
+    println!("group registration verifies: {}", outcome.registration.verify());
+
+    // 用 DKG 得到的份额启动新的节点服务，任取 THRESHOLD 个节点签名
+    let scheme = HashScheme::TryAndIncrement;
+    let message = b"signed by a committee without a dealer";
+    let mut aggregator = ThresholdAggregator::new(THRESHOLD);
+    for dkg in dkg_nodes.iter().skip(NODES - THRESHOLD) {
+        let share = keystore::load(&dkg.share_path(&session), PASSWORD)?;
+        let addr = spawn_service(NodeService::from_signer(Arc::new(share))).await?;
+        let node_signature = request_signature(&addr, message, &SignOptions::new(scheme.clone())).await?;
+        let index = outcome.group.share_index(&node_signature.public_key).expect("share of the group");
+        aggregator.add(index, node_signature.signature);
+        println!("partial signature from share {}", index);
+    }
+
+    let signature = aggregator.combine()?;
+    let valid = verify_signature_with(&scheme, message, &signature, &outcome.group.group_public_key);
+    println!("threshold signature from shares {:?} verifies: {}", aggregator.indices(), valid);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,191 @@
+// 消息到 G1 的哈希方式
+//
+// try_and_increment: 与 bn254 库 ECDSA::sign 相同的哈希，与早期版本的签名兼容
+// svdw: RFC 9380 的 BN254G1_XMD:SHA-256_SVDW_RO_ 映射，带域分隔标签 (DST)，
+//       与 gnark 以及链上验证合约使用的标准映射一致 (Z = 1，余因子为 1)
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use ark_bn254::Fq as ArkFq;
+use ark_ff::{BigInteger, Field, LegendreSymbol, One, PrimeField, Zero};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use substrate_bn::{arith::U256, AffineG1, Fq, G1};
+use crate::keys::invalid_data;
+
+pub const DEFAULT_DST: &str = "DSS-V01-CS01-with-BN254G1_XMD:SHA-256_SVDW_RO_";
+// RFC 9380 要求 DST 不超过 255 字节
+pub const MAX_DST_LEN: usize = 255;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum HashScheme {
+    #[default]
+    TryAndIncrement,
+    Svdw { dst: String },
+}
+
+impl HashScheme {
+    pub fn svdw(dst: &str) -> Result<Self, String> {
+        if dst.is_empty() || dst.len() > MAX_DST_LEN {
+            return Err(format!("domain separation tag must be 1 to {} bytes", MAX_DST_LEN));
+        }
+        Ok(HashScheme::Svdw { dst: dst.to_string() })
+    }
+
+    pub fn hash_to_g1(&self, message: &[u8]) -> io::Result<G1> {
+        match self {
+            HashScheme::TryAndIncrement => hash_to_try_and_increment(message),
+            HashScheme::Svdw { dst } => svdw_hash_to_g1(message, dst.as_bytes()),
+        }
+    }
+}
+
+// 命令行格式: "try_and_increment"、"svdw" (使用默认 DST) 或 "svdw:<DST>"
+impl FromStr for HashScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "try_and_increment" | "try-and-increment" => Ok(HashScheme::TryAndIncrement),
+            "svdw" => HashScheme::svdw(DEFAULT_DST),
+            _ => match s.strip_prefix("svdw:") {
+                Some(dst) => HashScheme::svdw(dst),
+                None => Err(format!("unknown hash scheme {:?}, expected try_and_increment, svdw or svdw:<dst>", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for HashScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashScheme::TryAndIncrement => write!(f, "try_and_increment"),
+            HashScheme::Svdw { dst } => write!(f, "svdw:{}", dst),
+        }
+    }
+}
+
+// 2^256 以下 Fq 模数的最大倍数 (5p)，超过它的哈希值会使取模结果有偏，直接丢弃
+const LAST_MULTIPLE_OF_FQ_MODULUS: U256 = U256([
+    0xf587_14d7_0a38_f4c2_2ca2_bc72_3a70_f263,
+    0xf1f5_883e_65f8_20d0_9991_5c90_8786_b9d3,
+]);
+
+// Hash(message || ctr) mod p 作为 x 坐标，取 y 为偶数的点，ctr 从 0 递增直到落在曲线上
+// 与 bn254 0.0.1 的 hash_to_try_and_increment 逐字节一致 (该函数在库中不公开)
+pub fn hash_to_try_and_increment(message: &[u8]) -> io::Result<G1> {
+    let mut data = [message, &[0u8]].concat();
+    let position = data.len() - 1;
+    (0..255u8)
+        .find_map(|ctr| {
+            data[position] = ctr;
+            let hash = U256::from_slice(&Sha256::digest(&data)).ok()?;
+            if hash >= LAST_MULTIPLE_OF_FQ_MODULUS {
+                return None;
+            }
+            let mut x = hash;
+            let modulus = Fq::modulus();
+            while x > modulus {
+                x.add(&U256::zero(), &modulus);
+            }
+            let mut compressed = [0u8; 33];
+            compressed[0] = 0x02;
+            x.to_big_endian(&mut compressed[1..]).ok()?;
+            G1::from_compressed(&compressed).ok()
+        })
+        .ok_or_else(|| invalid_data("no curve point found for message"))
+}
+
+// RFC 9380 5.3.1 expand_message_xmd (SHA-256)
+pub fn expand_message_xmd(message: &[u8], dst: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let blocks = len.div_ceil(32);
+    if blocks > 255 || len > 65535 || dst.len() > MAX_DST_LEN {
+        return Err(invalid_data("expand_message_xmd parameters out of range"));
+    }
+    let mut dst_prime = dst.to_vec();
+    dst_prime.push(dst.len() as u8);
+
+    let b0 = Sha256::new()
+        .chain_update([0u8; 64])
+        .chain_update(message)
+        .chain_update((len as u16).to_be_bytes())
+        .chain_update([0u8])
+        .chain_update(&dst_prime)
+        .finalize();
+    let mut b = Sha256::new().chain_update(b0).chain_update([1u8]).chain_update(&dst_prime).finalize();
+    let mut uniform = b.to_vec();
+    for i in 2..=blocks {
+        let mixed: Vec<u8> = b0.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect();
+        b = Sha256::new().chain_update(mixed).chain_update([i as u8]).chain_update(&dst_prime).finalize();
+        uniform.extend_from_slice(&b);
+    }
+    uniform.truncate(len);
+    Ok(uniform)
+}
+
+// RFC 9380 5.2 hash_to_field，count = 2，L = 48
+fn hash_to_field(message: &[u8], dst: &[u8]) -> io::Result<[ArkFq; 2]> {
+    let uniform = expand_message_xmd(message, dst, 96)?;
+    Ok([ArkFq::from_be_bytes_mod_order(&uniform[..48]), ArkFq::from_be_bytes_mod_order(&uniform[48..])])
+}
+
+fn sgn0(x: &ArkFq) -> bool {
+    x.into_bigint().is_odd()
+}
+
+fn is_square(x: &ArkFq) -> bool {
+    x.legendre() != LegendreSymbol::QuadraticNonResidue
+}
+
+// y^2 = x^3 + 3
+fn curve(x: &ArkFq) -> ArkFq {
+    x.square() * x + ArkFq::from(3u64)
+}
+
+// RFC 9380 6.6.1 Shallue-van de Woestijne 映射，Z = 1
+fn map_to_curve_svdw(u: &ArkFq) -> (ArkFq, ArkFq) {
+    let z = ArkFq::one();
+    // c1 = g(Z)，c2 = -Z / 2，c3 = sqrt(-g(Z) · 3Z²) 且 sgn0(c3) = 0，c4 = -4g(Z) / 3Z²
+    let c1 = curve(&z);
+    let three_z2 = ArkFq::from(3u64) * z.square();
+    let c2 = -z * ArkFq::from(2u64).inverse().unwrap();
+    let mut c3 = (-c1 * three_z2).sqrt().expect("-g(Z)·3Z² is a square for Z = 1");
+    if sgn0(&c3) {
+        c3 = -c3;
+    }
+    let c4 = -ArkFq::from(4u64) * c1 * three_z2.inverse().unwrap();
+
+    let tv1 = u.square() * c1;
+    let tv2 = ArkFq::one() + tv1;
+    let tv1 = ArkFq::one() - tv1;
+    // inv0: 0 的逆取 0
+    let tv3 = (tv1 * tv2).inverse().unwrap_or_else(ArkFq::zero);
+    let tv4 = *u * tv1 * tv3 * c3;
+    let x1 = c2 - tv4;
+    let x2 = c2 + tv4;
+    let x3 = (tv2.square() * tv3).square() * c4 + z;
+    let x = if is_square(&curve(&x1)) {
+        x1
+    } else if is_square(&curve(&x2)) {
+        x2
+    } else {
+        x3
+    };
+    let y = curve(&x).sqrt().expect("one of x1, x2, x3 is on the curve");
+    let y = if sgn0(u) == sgn0(&y) { y } else { -y };
+    (x, y)
+}
+
+fn to_g1((x, y): (ArkFq, ArkFq)) -> io::Result<G1> {
+    let x = Fq::from_slice(&x.into_bigint().to_bytes_be()).map_err(|e| invalid_data(format!("{:?}", e)))?;
+    let y = Fq::from_slice(&y.into_bigint().to_bytes_be()).map_err(|e| invalid_data(format!("{:?}", e)))?;
+    let point = AffineG1::new(x, y).map_err(|e| invalid_data(format!("{:?}", e)))?;
+    Ok(point.into())
+}
+
+// RFC 9380 3 hash_to_curve: 两个域元素分别映射后相加，BN254 G1 的余因子为 1
+fn svdw_hash_to_g1(message: &[u8], dst: &[u8]) -> io::Result<G1> {
+    let [u0, u1] = hash_to_field(message, dst)?;
+    Ok(to_g1(map_to_curve_svdw(&u0))? + to_g1(map_to_curve_svdw(&u1))?)
+}
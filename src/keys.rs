@@ -0,0 +1,146 @@
+use std::io;
+use bn254::{PublicKey, Signature};
+use substrate_bn::{Group, G2};
+use crate::bitmap::SignerBitmap;
+
+pub(crate) fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+pub fn public_key_to_hex(public_key: &PublicKey) -> io::Result<String> {
+    Ok(hex::encode(public_key.to_compressed().map_err(invalid_data)?))
+}
+
+pub fn public_key_from_hex(public_key_hex: &str) -> io::Result<PublicKey> {
+    let bytes = hex::decode(public_key_hex).map_err(invalid_data)?;
+    PublicKey::from_compressed(&bytes).map_err(invalid_data)
+}
+
+pub fn signature_to_hex(signature: &Signature) -> io::Result<String> {
+    Ok(hex::encode(signature.to_compressed().map_err(invalid_data)?))
+}
+
+pub fn signature_from_hex(signature_hex: &str) -> io::Result<Signature> {
+    let bytes = hex::decode(signature_hex).map_err(invalid_data)?;
+    Signature::from_compressed(&bytes).map_err(invalid_data)
+}
+
+// 一组参与签名的公钥
+#[derive(Clone, Debug, Default)]
+pub struct KeySet {
+    keys: Vec<PublicKey>,
+}
+
+impl KeySet {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        KeySet { keys }
+    }
+
+    // 从公钥收集服务返回的十六进制公钥列表解析
+    pub fn from_hex<S: AsRef<str>>(keys_hex: &[S]) -> io::Result<Self> {
+        let keys = keys_hex
+            .iter()
+            .map(|key| public_key_from_hex(key.as_ref()))
+            .collect::<io::Result<Vec<PublicKey>>>()?;
+        Ok(KeySet { keys })
+    }
+
+    pub fn push(&mut self, public_key: PublicKey) {
+        self.keys.push(public_key);
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.position(public_key).is_some()
+    }
+
+    pub fn position(&self, public_key: &PublicKey) -> Option<usize> {
+        self.keys.iter().position(|key| key.0 == public_key.0)
+    }
+
+    // 按位图选出参与签名的公钥
+    pub fn select(&self, signers: &SignerBitmap) -> io::Result<KeySet> {
+        let mut selected = KeySet::default();
+        for index in signers.indices() {
+            let key = self.keys.get(index).ok_or_else(|| {
+                invalid_data(format!(
+                    "signer index {} out of range for {} registered keys",
+                    index,
+                    self.keys.len()
+                ))
+            })?;
+            selected.push(*key);
+        }
+        Ok(selected)
+    }
+
+    // 聚合公钥: 所有公钥在 G2 上求和
+    pub fn aggregate(&self) -> PublicKey {
+        self.keys.iter().fold(PublicKey(G2::zero()), |acc, key| acc + *key)
+    }
+}
+
+// 用于 #[serde(with = "...")] 的十六进制编解码
+pub mod signature_hex {
+    use bn254::Signature;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(signature: &Signature, serializer: S) -> Result<S::Ok, S::Error> {
+        let signature_hex = super::signature_to_hex(signature).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&signature_hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Signature, D::Error> {
+        let signature_hex = String::deserialize(deserializer)?;
+        super::signature_from_hex(&signature_hex).map_err(serde::de::Error::custom)
+    }
+}
+
+// Option<Signature>，配合 #[serde(default)] 使用
+pub mod optional_signature_hex {
+    use bn254::Signature;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(signature: &Option<Signature>, serializer: S) -> Result<S::Ok, S::Error> {
+        match signature {
+            Some(signature) => super::signature_hex::serialize(signature, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Signature>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(signature_hex) => super::signature_from_hex(&signature_hex).map(Some).map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+pub mod public_key_hex {
+    use bn254::PublicKey;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(public_key: &PublicKey, serializer: S) -> Result<S::Ok, S::Error> {
+        let public_key_hex = super::public_key_to_hex(public_key).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&public_key_hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PublicKey, D::Error> {
+        let public_key_hex = String::deserialize(deserializer)?;
+        super::public_key_from_hex(&public_key_hex).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,319 @@
+// 口令加密的节点密钥文件
+//
+// 私钥用 scrypt 从口令派生的密钥经 AES-256-GCM 加密，以 JSON 信封保存:
+// {
+//   "version": 1,
+//   "public_key": "<压缩公钥>",
+//   "kdf": { "name": "scrypt", "log_n": 15, "r": 8, "p": 1, "salt": "<hex>" },
+//   "cipher": { "name": "aes-256-gcm", "nonce": "<hex>" },
+//   "ciphertext": "<hex>"
+// }
+// 公钥同时作为 AEAD 的附加数据，篡改信封中的公钥会导致解密失败
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+use bn254::PublicKey;
+use crate::keys::{invalid_data, public_key_from_hex, public_key_to_hex};
+use crate::signer::Signer;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+pub const PASSWORD_ENV: &str = "DSS_KEYSTORE_PASSWORD";
+
+const KDF_NAME: &str = "scrypt";
+const CIPHER_NAME: &str = "aes-256-gcm";
+const DEFAULT_LOG_N: u8 = 15;
+const DEFAULT_R: u32 = 8;
+const DEFAULT_P: u32 = 1;
+// 防止恶意密钥文件用超大参数耗尽内存或 CPU: scrypt 占用 128·r·2^log_n 字节，计算量再乘以 p
+const MAX_LOG_N: u8 = 20;
+const MAX_R: u32 = 32;
+const MAX_P: u32 = 16;
+const MAX_KDF_MEMORY: u64 = 1 << 30;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub name: String,
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub name: String,
+    pub nonce: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedKeystore {
+    pub version: u32,
+    pub public_key: String,
+    pub kdf: KdfParams,
+    pub cipher: CipherParams,
+    pub ciphertext: String,
+}
+
+impl EncryptedKeystore {
+    pub fn encrypt(signer: &Signer, password: &str) -> io::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let kdf = KdfParams {
+            name: KDF_NAME.to_string(),
+            log_n: DEFAULT_LOG_N,
+            r: DEFAULT_R,
+            p: DEFAULT_P,
+            salt: hex::encode(salt),
+        };
+        let public_key = public_key_to_hex(&signer.public_key())?;
+        let key = derive_key(&kdf, password)?;
+        let plaintext = Zeroizing::new(signer.to_bytes()?);
+        let cipher = Aes256Gcm::new_from_slice(key.as_slice()).map_err(invalid_data)?;
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload { msg: plaintext.as_slice(), aad: public_key.as_bytes() },
+            )
+            .map_err(|_| invalid_data("failed to encrypt private key"))?;
+
+        Ok(EncryptedKeystore {
+            version: KEYSTORE_VERSION,
+            public_key,
+            kdf,
+            cipher: CipherParams {
+                name: CIPHER_NAME.to_string(),
+                nonce: hex::encode(nonce),
+            },
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> io::Result<Signer> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(invalid_data(format!("unsupported keystore version {}", self.version)));
+        }
+        if self.cipher.name != CIPHER_NAME {
+            return Err(invalid_data(format!("unsupported keystore cipher {}", self.cipher.name)));
+        }
+        let nonce = hex::decode(&self.cipher.nonce).map_err(invalid_data)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(invalid_data("invalid keystore nonce length"));
+        }
+        let ciphertext = hex::decode(&self.ciphertext).map_err(invalid_data)?;
+
+        let key = derive_key(&self.kdf, password)?;
+        let cipher = Aes256Gcm::new_from_slice(key.as_slice()).map_err(invalid_data)?;
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload { msg: ciphertext.as_slice(), aad: self.public_key.as_bytes() },
+                )
+                .map_err(|_| invalid_data("wrong passphrase or corrupted keystore"))?,
+        );
+
+        let signer = Signer::from_bytes(&plaintext)?;
+        if public_key_to_hex(&signer.public_key())? != self.public_key {
+            return Err(invalid_data("keystore public key does not match the decrypted private key"));
+        }
+        Ok(signer)
+    }
+
+    // 不需要口令即可读取的公钥
+    pub fn public_key(&self) -> io::Result<PublicKey> {
+        public_key_from_hex(&self.public_key)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| {
+            invalid_data(format!("{} is not an encrypted keystore: {}", path.display(), e))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(invalid_data)?;
+        write_private(path, contents.as_bytes())
+    }
+}
+
+fn derive_key(kdf: &KdfParams, password: &str) -> io::Result<Zeroizing<[u8; 32]>> {
+    if kdf.name != KDF_NAME {
+        return Err(invalid_data(format!("unsupported keystore kdf {}", kdf.name)));
+    }
+    if kdf.log_n > MAX_LOG_N {
+        return Err(invalid_data(format!("scrypt log_n {} exceeds limit of {}", kdf.log_n, MAX_LOG_N)));
+    }
+    if kdf.r == 0 || kdf.r > MAX_R || kdf.p == 0 || kdf.p > MAX_P {
+        return Err(invalid_data(format!(
+            "scrypt r {} and p {} must be between 1 and {} / {}",
+            kdf.r, kdf.p, MAX_R, MAX_P
+        )));
+    }
+    if (128 * u64::from(kdf.r)) << kdf.log_n > MAX_KDF_MEMORY {
+        return Err(invalid_data(format!(
+            "scrypt parameters log_n {} r {} need more than {} bytes of memory",
+            kdf.log_n, kdf.r, MAX_KDF_MEMORY
+        )));
+    }
+    let salt = hex::decode(&kdf.salt).map_err(invalid_data)?;
+    let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32).map_err(invalid_data)?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(password.as_bytes(), &salt, &params, key.as_mut_slice()).map_err(invalid_data)?;
+    Ok(key)
+}
+
+// 读取并解密密钥文件
+pub fn load(path: &Path, password: &str) -> io::Result<Signer> {
+    EncryptedKeystore::read(path)?.decrypt(password)
+}
+
+// 加密私钥并写入文件，仅所有者可读写
+pub fn save(path: &Path, signer: &Signer, password: &str) -> io::Result<()> {
+    EncryptedKeystore::encrypt(signer, password)?.write(path)
+}
+
+// 用零覆盖密钥文件的内容并落盘
+fn zero(path: &Path) -> io::Result<()> {
+    use std::io::Write;
+
+    let len = fs::metadata(path)?.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    file.write_all(&vec![0u8; len as usize])?;
+    file.sync_all()
+}
+
+// 作废密钥文件: 先用零覆盖内容并落盘，再删除
+pub fn destroy(path: &Path) -> io::Result<()> {
+    zero(path)?;
+    fs::remove_file(path)
+}
+
+// 用新密钥替换密钥文件，原内容先用零覆盖; 中途崩溃时留下的全零文件无法加载，不会被当作不存在而重新生成
+pub fn replace(path: &Path, signer: &Signer, password: &str) -> io::Result<()> {
+    zero(path)?;
+    save(path, signer, password)
+}
+
+// 节点密钥文件作废后留下的标记: 文件名后加 .retired
+pub fn retired_marker(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".retired");
+    path.with_file_name(name)
+}
+
+// 作废节点密钥文件并留下标记，之后在该路径加载或生成密钥都会失败，需要操作员处理
+pub fn retire(path: &Path, reason: &str) -> io::Result<()> {
+    write_private(&retired_marker(path), reason.as_bytes())?;
+    destroy(path)
+}
+
+// 密钥文件存在则加载，否则生成新密钥并保存; 返回值的第二项表示是否新生成。
+// 密钥文件已被作废 (见 retire) 时报错，不会静默生成新密钥
+pub fn load_or_generate(path: &Path, password: &str) -> io::Result<(Signer, bool)> {
+    let marker = retired_marker(path);
+    if marker.exists() {
+        let reason = fs::read_to_string(&marker).unwrap_or_default();
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} was retired ({}); remove {} to start with a new key",
+                path.display(),
+                reason.trim(),
+                marker.display()
+            ),
+        ));
+    }
+    if path.exists() {
+        return Ok((load(path, password)?, false));
+    }
+    let signer = Signer::random();
+    save(path, &signer, password)?;
+    Ok((signer, true))
+}
+
+// 从口令文件或环境变量读取口令，口令文件优先; 去掉末尾换行
+pub fn read_password(password_file: Option<&Path>, env_var: &str) -> io::Result<Zeroizing<String>> {
+    let password = match password_file {
+        Some(path) => Zeroizing::new(fs::read_to_string(path)?),
+        None => Zeroizing::new(env::var(env_var).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no passphrase: pass a password file or set {}", env_var),
+            )
+        })?),
+    };
+    let trimmed = password.trim_end_matches(['\r', '\n']);
+    if trimmed.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "keystore passphrase is empty"));
+    }
+    Ok(Zeroizing::new(trimmed.to_string()))
+}
+
+// 写入同目录的临时文件 (创建时即为 0600) 并落盘后原子替换原文件，写入中途崩溃不会损坏原文件
+#[cfg(unix)]
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let tmp_path = temp_path(path);
+    {
+        let mut tmp = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+        // 上次写入中断留下的临时文件可能权限更宽
+        tmp.set_permissions(fs::Permissions::from_mode(0o600))?;
+        tmp.write_all(contents)?;
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })?;
+    // 目录项落盘后替换才在断电后保留
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::File::open(parent)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+
+    let tmp_path = temp_path(path);
+    {
+        let mut tmp = fs::File::create(&tmp_path)?;
+        tmp.write_all(contents)?;
+        tmp.sync_all()?;
+    }
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+// 与目标文件同目录的临时文件: 文件名后加 .tmp
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
@@ -0,0 +1,34 @@
+// 分布式签名系统的核心库，节点服务、客户端和公钥收集服务共用
+pub mod keys;
+pub mod hash;
+pub mod bitmap;
+pub mod signer;
+pub mod keystore;
+pub mod aggregator;
+pub mod quorum;
+pub mod request;
+pub mod verifier;
+pub mod pop;
+pub mod scheme;
+pub mod protocol;
+pub mod tls;
+pub mod auth;
+pub mod policy;
+pub mod node;
+pub mod collector;
+pub mod registry;
+pub mod epoch;
+pub mod store;
+pub mod evm;
+pub mod batch;
+pub mod threshold;
+pub mod dkg;
+pub mod reshare;
+
+pub use bn254::{PrivateKey, PublicKey, Signature};
+pub use keys::KeySet;
+pub use bitmap::SignerBitmap;
+pub use signer::Signer;
+pub use aggregator::{AggregatedSignature, Aggregator};
+pub use verifier::Verifier;
+pub use quorum::Quorum;
@@ -1,25 +1,33 @@
-#![allow(unused_imports)]
-#![allow(unused_variables)]
-#![allow(dead_code)]
-use actix_web::web::BytesMut;
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::{timeout, timeout_at, Instant};
 use clap::Parser;
-use rand_core::OsRng;
-use hex;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 use serde_json::json;
-use reqwest::Client; // 导入 reqwest 用于发送 HTTP 请求
-use core::slice;
-use std::ops::Neg;
-use num_bigint::BigUint;
-use bn254::{PrivateKey, PublicKey, ECDSA, Signature};
-use k256::{elliptic_curve::bigint::Encoding, U256};
-use substrate_bn::{Group, G1};
+use dss_core::{AggregatedSignature, Aggregator, Quorum, Signer, SignerBitmap};
+use dss_core::auth::{parse_aggregator_key, AggregatorIdentity, AggregatorKey, RequestAuthenticator, DEFAULT_MAX_SKEW_MS};
+use dss_core::collector::KeyCollectorClient;
+use dss_core::dkg::DkgNode;
+use dss_core::epoch::{epoch_at, epoch_keystore_path, load_epoch_keys, KeySchedule};
+use dss_core::hash::HashScheme;
+use dss_core::keystore;
+use dss_core::registry::Registry;
+use dss_core::scheme::SignatureScheme;
+use dss_core::node::{request_signature, NodeService, NodeSignature, SignOptions};
+use dss_core::policy::PolicyEngine;
+use dss_core::protocol::{ErrorCode, ProtocolError};
+use dss_core::request::{now_millis, NodeOutcome, RequestRecord, RequestState, RequestTable, SigningMode};
+use dss_core::store::{FileStore, MemoryStore, PendingSync, Store};
+use dss_core::threshold::{ThresholdAggregator, ThresholdGroup};
+use dss_core::tls::{parse_fingerprint, parse_peer_pin, Fingerprint, TlsClient, TlsIdentity, TlsServer, Transport};
+use dss_core::verifier::verify_signature_with;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -27,6 +35,10 @@ struct Args {
     #[arg(short, long)]
     node_addr: String,
 
+    // 在公钥收集服务中登记的节点 ID，默认使用 node_addr
+    #[arg(long)]
+    node_id: Option<String>,
+
     #[arg(short, long)]
     web_addr: String,
 
@@ -35,32 +47,167 @@ struct Args {
 
     #[arg(short, long)]
     key_collector: String, // 新增公钥收集服务的地址
+
+    // 完成签名请求所需的最少签名数: "all"、绝对数量 (如 3) 或比例 (如 2/3、0.67)
+    #[arg(short, long, default_value = "all")]
+    quorum: Quorum,
+
+    // 单个节点的签名超时 (毫秒)
+    #[arg(long, default_value_t = 5000)]
+    node_timeout_ms: u64,
+
+    // 整个签名请求的截止时间 (毫秒)
+    #[arg(long, default_value_t = 15000)]
+    request_timeout_ms: u64,
+
+    // 消息到 G1 的哈希方式: "try_and_increment"、"svdw" (默认 DST) 或 "svdw:<DST>"
+    #[arg(long, default_value = "try_and_increment")]
+    hash_scheme: HashScheme,
+
+    // 防御流氓公钥的签名方案: "proof_of_possession" (公钥登记时校验持有证明) 或
+    // "message_augmentation" (节点对 域标签 || 公钥 || 消息 签名，只支持多签; 节点登记时不附带持有证明，
+    // 公钥收集服务须以 --signature-scheme message_augmentation 运行)
+    #[arg(long, default_value = "proof_of_possession")]
+    signature_scheme: SignatureScheme,
+
+    // 节点加密密钥文件; 存在则加载，不存在则生成并保存。未指定时每次启动生成新密钥，
+    // 仅在公钥收集服务不使用纪元时允许。
+    // 轮换 (POST /rotate_key，见 --admin-addr) 生成的密钥保存在同目录的 <文件名>.epoch-<N>，启动时一并加载
+    #[arg(long)]
+    keystore: Option<PathBuf>,
+
+    // 管理接口 (POST /rotate_key) 的监听地址，必须是回环地址; 未指定时不提供密钥轮换。
+    // 管理接口不在 --web-addr 上提供，能访问 Web 端口的客户端不能轮换节点密钥
+    #[arg(long)]
+    admin_addr: Option<SocketAddr>,
+
+    // 密钥文件口令所在文件; 未指定时读取环境变量 DSS_KEYSTORE_PASSWORD
+    #[arg(long)]
+    keystore_password_file: Option<PathBuf>,
+
+    // 门限模式: keytool deal 生成的群信息文件。指定后按门限合成签名，--quorum 不再生效
+    #[arg(long)]
+    threshold_group: Option<PathBuf>,
+
+    // 签名请求及结果的持久化文件; 重启后恢复记录并继续未完成的请求。未指定时只保存在内存中
+    #[arg(long)]
+    request_store: Option<PathBuf>,
+
+    // 参与分布式密钥生成 (keytool dkg)，份额用节点密钥文件的口令加密保存到该目录下的 <session>.json;
+    // 需要双向 TLS，并只接受 --authorized-aggregator 中的协调者
+    #[arg(long)]
+    dkg_dir: Option<PathBuf>,
+
+    // 节点通道的双向 TLS: 本地 CA 证书、本节点的证书和私钥 (keytool ca-issue 签发)，须同时指定。
+    // 启用后节点服务只接受 CA 签发证书的连接，主节点也只通过 TLS 请求签名
+    #[arg(long, requires_all = ["tls_cert", "tls_key"])]
+    tls_ca: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_ca")]
+    tls_cert: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_ca")]
+    tls_key: Option<PathBuf>,
+
+    // 按节点地址固定证书指纹: <节点地址>=<SHA-256 指纹>，可重复; 未固定的节点只校验证书链
+    #[arg(long = "tls-pin", requires = "tls_ca", value_parser = parse_peer_pin)]
+    tls_pins: Vec<(String, Fingerprint)>,
+
+    // 节点服务只接受这些指纹的主节点证书，可重复; 未指定时接受 CA 签发的任意证书
+    #[arg(long = "tls-allow-client", requires = "tls_ca", value_parser = parse_fingerprint)]
+    tls_allowed_clients: Vec<Fingerprint>,
+
+    // 主节点身份密钥文件 (secp256k1)，用于对发出的签名请求签名; 不存在则生成并保存。
+    // 未指定时发出的签名请求不带认证
+    #[arg(long)]
+    identity_key: Option<PathBuf>,
+
+    // 允许请求本节点签名的主节点公钥 (keytool identity-generate 打印的十六进制压缩公钥)，可重复;
+    // 指定后节点服务拒绝未认证或来自其他主节点的签名请求
+    #[arg(long = "authorized-aggregator", value_parser = parse_aggregator_key)]
+    authorized_aggregators: Vec<AggregatorKey>,
+
+    // 签名请求时间戳允许的时钟偏差 (毫秒)，同时是重放检查的时间窗口
+    #[arg(long, default_value_t = DEFAULT_MAX_SKEW_MS)]
+    auth_max_skew_ms: u64,
+
+    // 节点签名策略文件 (JSON): 消息大小、内容类型、匹配规则、JSON schema 和各主节点的频率限制。
+    // 文件修改后自动重新加载; 未指定时节点为任何请求签名
+    #[arg(long)]
+    policy: Option<PathBuf>,
 }
 
 // 共享状态，用于存储其他节点的地址
 struct AppState {
     other_nodes: Vec<String>,
-    pending_requests: Mutex<HashMap<String, Vec<u8>>>,
+    key_collector: KeyCollectorClient,
+    quorum: Quorum,
+    node_timeout: Duration,
+    request_timeout: Duration,
+    hash_scheme: HashScheme,
+    signature_scheme: SignatureScheme,
+    threshold_group: Option<Arc<ThresholdGroup>>,
+    pending_requests: Mutex<RequestTable>,
+    // 公钥收集服务的纪元长度; 获取失败时请求不记录纪元
+    epoch_length_ms: Option<u64>,
+    // 本节点的密钥安排，以及轮换密钥所需的身份和密钥文件
+    keys: Arc<KeySchedule>,
+    node_id: String,
+    node_addr: String,
+    keystore: Option<(PathBuf, Zeroizing<String>)>,
+    // 请求节点签名使用的传输方式
+    transport: Transport,
+    // 对发出的签名请求签名的主节点身份
+    identity: Option<Arc<AggregatorIdentity>>,
+}
+
+impl AppState {
+    fn current_epoch(&self) -> Option<u64> {
+        self.epoch_length_ms.map(|length| epoch_at(now_millis(), length))
+    }
 }
 
 async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello, world!")
 }
 
-async fn send_public_key(key_collector: &str, public_key: &str) {
-    let client = Client::new();
-    let response = client
-        .post(format!("{}/receive_key", key_collector))
-        .json(&public_key)
-        .send()
-        .await;
-
-    match response {
-        Ok(res) if res.status().is_success() => {
-            println!("Public key sent successfully. pk: {}", public_key);
-        },
-        Ok(res) => {
-            eprintln!("Failed to send public key. Server responded with status: {}", res.status());
+// 仅在密钥为新生成或公钥收集服务中的记录与本节点不一致时登记;
+// 消息增强部署不附带持有证明，公钥收集服务须以同一方案运行
+async fn send_public_key(
+    key_collector: &str,
+    signer: &Signer,
+    node_id: &str,
+    node_addr: &str,
+    is_new: bool,
+    epoch: Option<u64>,
+    signature_scheme: SignatureScheme,
+) {
+    let collector = KeyCollectorClient::new(key_collector);
+    if !is_new {
+        match collector.node(node_id).await {
+            Ok(Some(record))
+                if record.is_active()
+                    && record.address == node_addr
+                    && epoch
+                        .and_then(|epoch| record.key_at(epoch))
+                        .unwrap_or(&record.public_key)
+                        .0
+                        == signer.public_key().0 =>
+            {
+                println!("Public key already registered with key collector as node {} (index {}).", node_id, record.index);
+                return;
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("Failed to query key collector, registering anyway: {}", e),
+        }
+    }
+    let registered = match signature_scheme {
+        SignatureScheme::ProofOfPossession => collector.register(signer, node_id, node_addr).await,
+        SignatureScheme::MessageAugmentation => collector.register_unproven(signer.public_key(), node_id, node_addr).await,
+    };
+    match registered {
+        Ok(()) => {
+            println!("Public key sent successfully. node: {}, pk: {}", node_id, hex::encode(signer.public_key().to_compressed().unwrap()));
         },
         Err(e) => {
             eprintln!("Error sending public key: {}", e);
@@ -70,113 +217,461 @@ async fn send_public_key(key_collector: &str, public_key: &str) {
 
 async fn receive_message(
     msg: web::Json<serde_json::Value>,
-    state: web::Data<Arc<Mutex<AppState>>>,
+    state: web::Data<Arc<AppState>>,
 ) -> impl Responder {
+    let message_str = match msg.get("message").and_then(|message| message.as_str()) {
+        Some(message) => message.to_string(),
+        None => return HttpResponse::BadRequest().json(json!({ "error": "missing string field `message`" })),
+    };
+    println!("message: {}", message_str);
+
     let request_id = Uuid::new_v4().to_string();
+    let required = match &state.threshold_group {
+        Some(group) => group.threshold,
+        None => state.quorum.required(state.other_nodes.len()),
+    };
+    let mut record = RequestRecord::new(&request_id, &message_str, &state.hash_scheme, &state.other_nodes, required);
+    record.signature_scheme = state.signature_scheme;
+    match &state.threshold_group {
+        Some(group) => {
+            record.signing_mode = SigningMode::Threshold {
+                threshold: group.threshold,
+                group_public_key: group.group_public_key,
+            };
+        }
+        // 门限模式使用固定的份额密钥，不按纪元轮换
+        None => record.epoch = state.current_epoch(),
+    }
+    if let Err(e) = insert_request(&state, record).await {
+        eprintln!("Failed to persist request {}: {}", request_id, e);
+        return HttpResponse::InternalServerError().json(json!({ "error": "failed to persist request" }));
+    }
+
+    let state = state.get_ref().clone();
     let request_id_clone = request_id.clone();
-    
-    let state_clone = state.clone();
     tokio::spawn(async move {
-        let mut signatures = Vec::new();        
-        let state = state_clone.lock().await;
-        let message = msg.get("message").unwrap();
-        let message_str = message.as_str().unwrap();    
-        println!("message: {}", message_str);
-        
-        for node in &state.other_nodes {
-            match send_to_node(node, message_str).await {
-                Ok(signature) => {
-                    println!("Received signature from node: {}", node);
-                    signatures.push(signature);
-                },
-                Err(e) => eprintln!("Failed to send message to {} or receive signature: {}", node, e),
-            }
-        }
+        collect_signatures(&state, &request_id_clone).await;
+    });
 
-        
-        // 聚合所有签名
-        if !signatures.is_empty() {
-            let aggregated_signature = aggregate_signatures(&signatures).await;
-            println!("Aggregated Signature: {:?}", aggregated_signature);
-            let mut pending_requests = state.pending_requests.lock().await;
-            pending_requests.insert(request_id_clone, aggregated_signature.to_compressed().unwrap());
-        }
-        
+    HttpResponse::Ok().json(json!({ "request_id": request_id }))
+}
+
+// 多消息请求: {"messages": {"<节点地址>": "<消息>", ...}}，每个节点签自己的消息 (如各自的观测结果)，
+// 聚合为一个签名，按 (公钥, 消息) 列表验证。门限 (--quorum) 按列出的节点数计算
+async fn receive_messages(
+    body: web::Json<serde_json::Value>,
+    state: web::Data<Arc<AppState>>,
+) -> impl Responder {
+    if state.threshold_group.is_some() {
+        return HttpResponse::BadRequest().json(json!({ "error": "multi-message requests are not supported in threshold mode" }));
+    }
+    let messages: BTreeMap<String, String> = match body.get("messages").cloned().map(serde_json::from_value) {
+        Some(Ok(messages)) => messages,
+        _ => return HttpResponse::BadRequest().json(json!({ "error": "missing object field `messages` of node address to string" })),
+    };
+    if messages.is_empty() {
+        return HttpResponse::BadRequest().json(json!({ "error": "`messages` must name at least one node" }));
+    }
+    if let Some(node) = messages.keys().find(|node| !state.other_nodes.contains(node)) {
+        return HttpResponse::BadRequest().json(json!({ "error": format!("{} is not a configured node", node) }));
+    }
+    println!("messages for {} nodes", messages.len());
+
+    let request_id = Uuid::new_v4().to_string();
+    let mut record = match RequestRecord::with_messages(&request_id, messages, &state.hash_scheme, &state.quorum) {
+        Ok(record) => record,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e })),
+    };
+    record.signature_scheme = state.signature_scheme;
+    record.epoch = state.current_epoch();
+    if let Err(e) = insert_request(&state, record).await {
+        eprintln!("Failed to persist request {}: {}", request_id, e);
+        return HttpResponse::InternalServerError().json(json!({ "error": "failed to persist request" }));
+    }
+
+    let state = state.get_ref().clone();
+    let request_id_clone = request_id.clone();
+    tokio::spawn(async move {
+        collect_signatures(&state, &request_id_clone).await;
     });
 
     HttpResponse::Ok().json(json!({ "request_id": request_id }))
 }
 
-async fn send_to_node(addr: &str, msg: &str) -> std::io::Result<Signature> {
-    let mut stream = TcpStream::connect(addr).await?;
-    stream.write_all(msg.as_bytes()).await?;
+// 在锁外的阻塞线程上等待刷盘，不占用请求表的锁和执行器线程
+async fn wait_sync(pending: PendingSync) -> std::io::Result<()> {
+    tokio::task::spawn_blocking(move || pending.wait())
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+async fn insert_request(state: &AppState, record: RequestRecord) -> std::io::Result<()> {
+    let pending = state.pending_requests.lock().await.insert(record)?;
+    wait_sync(pending).await
+}
 
-    // 读取签名结果
-    let mut buf = BytesMut::with_capacity(1024); // BLS签名大小
-    stream.read_buf(&mut buf).await?;
-    println!("Received signature: {:?}", buf);
-    let signature = Signature::from_compressed(&buf)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+async fn update_request<F>(state: &AppState, request_id: &str, update: F)
+where
+    F: FnOnce(&mut RequestRecord),
+{
+    // 单独的语句，锁在等待刷盘前释放
+    let pending = state.pending_requests.lock().await.update(request_id, update);
+    let persisted = match pending {
+        Ok(pending) => wait_sync(pending).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = persisted {
+        eprintln!("Failed to persist request {}: {}", request_id, e);
+    }
+}
 
-    Ok(signature)
+// 重启后继续未结束的请求，截止时间从恢复时重新计算
+fn resume_requests(state: &Arc<AppState>, request_ids: Vec<String>) {
+    for request_id in request_ids {
+        println!("Resuming request {}", request_id);
+        let state = state.clone();
+        tokio::spawn(async move {
+            collect_signatures(&state, &request_id).await;
+        });
+    }
 }
 
-async fn run_node_service(addr: &str, key_collector: &str) -> std::io::Result<()> {
-    let listener = TcpListener::bind(addr).await?;
-    println!("Node service listening on {}", addr);
+// 并发向所有节点请求签名
+// 每个节点有单独的超时，整个请求有总的截止时间; 达到门限后立即发布结果，
+// 截止时间前迟到的签名仍会并入聚合签名和签名者位图。
+// 已记录签名的节点不会被重复请求，因此重启后可从中断处继续
+async fn collect_signatures(state: &AppState, request_id: &str) {
+    let record = match state.pending_requests.lock().await.get(request_id) {
+        Some(record) => record.clone(),
+        None => return,
+    };
+    update_request(state, request_id, |record| record.set_state(RequestState::Collecting)).await;
+    let required = record.required;
 
-    // 生成BLS签名密钥
-    let private_key = PrivateKey::random(&mut OsRng);
-    let public_key = PublicKey::from_private_key(&private_key);
+    let mut combiner = match Combiner::for_record(state, &record).await {
+        Ok(combiner) => combiner,
+        Err(reason) => {
+            eprintln!("Request {} failed: {}", request_id, reason);
+            update_request(state, request_id, |record| record.fail(RequestState::Failed, reason)).await;
+            return;
+        }
+    };
 
-    // 将公钥发送到公钥收集服务
-    let public_key_hex = hex::encode(public_key.to_compressed().unwrap());
-    send_public_key(key_collector, &public_key_hex).await;
+    // 多消息请求中各节点签自己的消息
+    let message = record.message.as_bytes();
+    let mut requests = JoinSet::new();
+    for node in record.missing_nodes() {
+        update_request(state, request_id, |record| record.record_node(&node, NodeOutcome::Pending)).await;
+        let node_message = record.node_message(&node).as_bytes().to_vec();
+        let mut options = SignOptions::new(record.hash_scheme.clone())
+            .with_signature_scheme(record.signature_scheme)
+            .with_epoch(record.epoch)
+            .with_transport(state.transport.clone());
+        options.identity = state.identity.clone();
+        let node_timeout = state.node_timeout;
+        requests.spawn(async move {
+            let result = timeout(node_timeout, request_signature(&node, &node_message, &options)).await;
+            (node, node_message, result)
+        });
+    }
 
+    let deadline = Instant::now() + state.request_timeout;
+    let mut expired = false;
     loop {
-        let (mut socket, _) = listener.accept().await?;
-        let keypair_clone = private_key.to_bytes().unwrap().clone();
-        tokio::spawn(async move {
-            let mut buf = [0; 1024];
-            let private_key_clone = PrivateKey::try_from(keypair_clone.as_slice()).unwrap();
-            match socket.read(&mut buf).await {
-                Ok(n) if n > 0 => {
-                    let received = String::from_utf8_lossy(&buf[..n]);
-                    println!("Node service received: {}", received);
-
-                    let message = received.as_bytes();
-                    
-                    // let hash_point = hash_to_try_and_increment(message).unwrap();
-                    // TODO 使用合约中的hash_point来签名
-                    let signature = ECDSA::sign(&message, &private_key_clone).unwrap();
-
-                    // 发送签名结果回主节点
-                    if let Err(e) = socket.write_all(signature.to_compressed().unwrap().as_slice()).await {
-                        eprintln!("Failed to send signature: {}", e);
+        let (node, node_message, result) = match timeout_at(deadline, requests.join_next()).await {
+            Ok(Some(Ok(response))) => response,
+            Ok(Some(Err(e))) => {
+                eprintln!("Signature request task failed: {}", e);
+                continue;
+            }
+            // 所有节点都已响应
+            Ok(None) => break,
+            Err(_) => {
+                eprintln!("Request {} reached its deadline with {} requests outstanding", request_id, requests.len());
+                requests.abort_all();
+                expired = true;
+                break;
+            }
+        };
+
+        let outcome = match result {
+            Ok(Ok(node_signature)) => {
+                println!("Received signature from node: {}", node);
+                // 消息增强方案下节点签的是按其公钥增强后的内容
+                let added = record
+                    .signature_scheme
+                    .signing_message(&node_message, &node_signature.public_key)
+                    .map_err(|e| e.to_string())
+                    .and_then(|signed| combiner.add(&record.hash_scheme, &signed, &node_signature));
+                match added {
+                    Ok(signer_index) => NodeOutcome::Signed {
+                        signer_index,
+                        signature: node_signature.signature,
+                        at: now_millis(),
+                    },
+                    Err(reason) => {
+                        eprintln!("Rejected signature from {}: {}", node, reason);
+                        NodeOutcome::Rejected { reason, at: now_millis() }
                     }
                 }
-                _ => return,
+            },
+            Ok(Err(e)) => match ProtocolError::from_io(&e) {
+                Some(refusal) if refusal.code == ErrorCode::PolicyRefused => {
+                    eprintln!("Node {} refused to sign: {}", node, refusal.reason);
+                    NodeOutcome::Refused { reason: refusal.reason.clone(), at: now_millis() }
+                }
+                _ => {
+                    eprintln!("Failed to send message to {} or receive signature: {}", node, e);
+                    NodeOutcome::Failed { reason: e.to_string(), at: now_millis() }
+                }
+            },
+            Err(_) => {
+                eprintln!("Node {} did not respond within {:?}", node, state.node_timeout);
+                NodeOutcome::TimedOut { at: now_millis() }
             }
-        });
+        };
+
+        // 达到门限立即发布，之后的签名更新已发布的结果
+        let aggregated = if combiner.len() >= required {
+            match combiner.result(&record.hash_scheme, message) {
+                Ok(aggregated) => Some(aggregated),
+                Err(reason) => {
+                    eprintln!("Failed to combine signatures for request {}: {}", request_id, reason);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        update_request(state, request_id, |record| {
+            record.record_node(&node, outcome);
+            if let Some(aggregated) = aggregated {
+                if record.state != RequestState::Complete {
+                    println!("Quorum reached for request {}: signers {:?}", request_id, aggregated.signers.indices());
+                }
+                record.complete(aggregated);
+            }
+        })
+        .await;
     }
+
+    // 截止时间到达时仍未响应的节点记为超时
+    update_request(state, request_id, |record| {
+        let now = now_millis();
+        for outcome in record.nodes.values_mut() {
+            if outcome.is_pending() {
+                *outcome = NodeOutcome::TimedOut { at: now };
+            }
+        }
+
+        if let Some(result) = &record.result {
+            println!("Aggregated Signature: {:?}, signers: {:?}", result.signature, result.signers.indices());
+            return;
+        }
+        let mut reason = format!(
+            "quorum not reached: {} of {} required signatures, {} short",
+            combiner.len(),
+            required,
+            required.saturating_sub(combiner.len())
+        );
+        let refused = record.nodes.values().filter(|outcome| matches!(outcome, NodeOutcome::Refused { .. })).count();
+        if refused > 0 {
+            reason.push_str(&format!(", {} refused by signing policy", refused));
+        }
+        eprintln!("Request {} failed: {}", request_id, reason);
+        let terminal = if expired { RequestState::Expired } else { RequestState::Failed };
+        record.fail(terminal, reason);
+    })
+    .await;
+}
+
+// 签名的合成方式及其所需的公钥信息
+enum Combiner {
+    // 按公钥在登记表中的位置记录签名并求和，多消息请求也使用这种方式;
+    // 只接受请求纪元生效的公钥，没有纪元的旧请求按公钥收集服务的当前纪元，已安排到之后纪元的公钥不被接受;
+    // 持有证明方案的请求不接受没有持有证明登记的公钥
+    Multisig { registry: Registry, epoch: u64, signature_scheme: SignatureScheme, aggregator: Aggregator },
+    // 按份额序号记录部分签名并插值合成
+    Threshold { group: Arc<ThresholdGroup>, aggregator: ThresholdAggregator },
+}
+
+impl Combiner {
+    // 按请求记录的签名方式创建，并恢复已记录的签名
+    async fn for_record(state: &AppState, record: &RequestRecord) -> Result<Self, String> {
+        let mut combiner = match &record.signing_mode {
+            SigningMode::Multisig | SigningMode::MultiMessage => {
+                // 获取公钥登记表，用于确定签名者在位图中的位置
+                let registry = state
+                    .key_collector
+                    .registry()
+                    .await
+                    .map_err(|e| format!("failed to fetch public keys from key collector: {}", e))?;
+                let epoch = match record.epoch {
+                    Some(epoch) => epoch,
+                    None => state
+                        .key_collector
+                        .epoch()
+                        .await
+                        .map(|info| info.epoch)
+                        .map_err(|e| format!("failed to fetch the current key epoch from key collector: {}", e))?,
+                };
+                let aggregator = Aggregator::new(registry.len());
+                Combiner::Multisig { registry, epoch, signature_scheme: record.signature_scheme, aggregator }
+            }
+            SigningMode::Threshold { threshold, group_public_key } => {
+                let group = match &state.threshold_group {
+                    Some(group) if group.group_public_key.0 == group_public_key.0 => group.clone(),
+                    _ => return Err("request was created for a threshold group this aggregator is not configured with".to_string()),
+                };
+                Combiner::Threshold { group, aggregator: ThresholdAggregator::new(*threshold) }
+            }
+        };
+        for (signer_index, signature) in record.signatures() {
+            match &mut combiner {
+                Combiner::Multisig { aggregator, .. } => aggregator.add(signer_index, signature),
+                Combiner::Threshold { aggregator, .. } => aggregator.add(signer_index as u32, signature),
+            };
+        }
+        Ok(combiner)
+    }
+
+    // 校验节点签名并记录，返回签名者序号: 多签为登记表 index，门限为份额序号
+    fn add(&mut self, scheme: &HashScheme, message: &[u8], node_signature: &NodeSignature) -> Result<usize, String> {
+        match self {
+            Combiner::Multisig { registry, epoch, signature_scheme, aggregator } => {
+                add_node_signature(aggregator, registry, *epoch, *signature_scheme, scheme, message, node_signature)
+            }
+            Combiner::Threshold { group, aggregator } => {
+                let index = group
+                    .share_index(&node_signature.public_key)
+                    .ok_or_else(|| "public key is not a share of the threshold group".to_string())?;
+                if !verify_signature_with(scheme, message, &node_signature.signature, &node_signature.public_key) {
+                    return Err("partial signature does not verify under the node's share public key".to_string());
+                }
+                if !aggregator.add(index, node_signature.signature) {
+                    return Err(format!("duplicate partial signature for share {}", index));
+                }
+                Ok(index as usize)
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Combiner::Multisig { aggregator, .. } => aggregator.len(),
+            Combiner::Threshold { aggregator, .. } => aggregator.len(),
+        }
+    }
+
+    // 当前已收集签名的合成结果; 门限模式下位图的第 i 位对应份额序号 i，只标记合成时用到的份额
+    fn result(&self, scheme: &HashScheme, message: &[u8]) -> Result<AggregatedSignature, String> {
+        match self {
+            Combiner::Multisig { aggregator, .. } => Ok(aggregator.aggregate()),
+            Combiner::Threshold { group, aggregator } => {
+                let signature = aggregator.combine().map_err(|e| e.to_string())?;
+                if !verify_signature_with(scheme, message, &signature, &group.group_public_key) {
+                    return Err("combined signature does not verify under the group public key".to_string());
+                }
+                let mut signers = SignerBitmap::default();
+                for index in aggregator.used_indices() {
+                    signers.set(index as usize);
+                }
+                Ok(AggregatedSignature { signature, signers })
+            }
+        }
+    }
+}
+
+// 校验节点签名并按其公钥在登记表中的位置记录
+fn add_node_signature(
+    aggregator: &mut Aggregator,
+    registry: &Registry,
+    epoch: u64,
+    signature_scheme: SignatureScheme,
+    scheme: &HashScheme,
+    message: &[u8],
+    node_signature: &NodeSignature,
+) -> Result<usize, String> {
+    let index = registry.active_index_at(&node_signature.public_key, epoch).ok_or_else(|| {
+        "public key is not registered for the request's epoch with the key collector or the node is deregistered".to_string()
+    })?;
+    registry.check_scheme(&[index], Some(epoch), signature_scheme).map_err(|e| e.to_string())?;
+    if !verify_signature_with(scheme, message, &node_signature.signature, &node_signature.public_key) {
+        return Err("signature does not verify under the node's public key".to_string());
+    }
+    if !aggregator.add(index, node_signature.signature) {
+        return Err(format!("duplicate signature for signer {}", index));
+    }
+    Ok(index)
 }
 
-// 新增聚合签名的逻辑
-async fn aggregate_signatures(signatures: &[Signature]) -> Signature {
-    signatures.iter().fold(Signature(G1::zero()), |acc, sig| acc + *sig)
+// 轮换节点密钥: 生成新密钥并加密保存，登记到公钥收集服务，从指定纪元 (默认下一纪元) 起使用。
+// 请求体可为 {"epoch": N}; 当前纪元内节点继续用原密钥签名
+async fn rotate_key(
+    body: Option<web::Json<serde_json::Value>>,
+    state: web::Data<Arc<AppState>>,
+) -> impl Responder {
+    let (keystore_path, password) = match &state.keystore {
+        Some(keystore) => keystore,
+        None => return HttpResponse::BadRequest().json(json!({ "error": "key rotation requires --keystore" })),
+    };
+    let current = match state.current_epoch() {
+        Some(epoch) => epoch,
+        None => return HttpResponse::ServiceUnavailable().json(json!({ "error": "key epochs are unknown, the key collector was unreachable at startup" })),
+    };
+    let epoch = match body.as_ref().and_then(|body| body.get("epoch")) {
+        None => current + 1,
+        Some(epoch) => match epoch.as_u64() {
+            Some(epoch) => epoch,
+            None => return HttpResponse::BadRequest().json(json!({ "error": "`epoch` must be a non-negative integer" })),
+        },
+    };
+    if epoch <= current {
+        return HttpResponse::BadRequest().json(json!({ "error": format!("epoch {} has already started (current epoch {})", epoch, current) }));
+    }
+    let path = epoch_keystore_path(keystore_path, epoch);
+    if path.exists() {
+        return HttpResponse::Conflict().json(json!({ "error": format!("a key is already scheduled for epoch {}", epoch) }));
+    }
+
+    // 先保存再登记，避免登记成功后密钥丢失
+    let signer = Signer::random();
+    if let Err(e) = keystore::save(&path, &signer, password) {
+        eprintln!("Failed to save rotated key to {}: {}", path.display(), e);
+        return HttpResponse::InternalServerError().json(json!({ "error": "failed to save the new key" }));
+    }
+    // 新公钥由当前纪元的密钥授权
+    let authorizer = state.keys.current();
+    let record = match state.key_collector.register_at(&signer, &state.node_id, &state.node_addr, Some(epoch), &authorizer).await {
+        Ok(record) => record,
+        Err(e) => {
+            eprintln!("Key collector rejected the rotated key: {}", e);
+            let _ = std::fs::remove_file(&path);
+            return HttpResponse::BadGateway().json(json!({ "error": e.to_string() }));
+        }
+    };
+    let public_key = hex::encode(signer.public_key().to_compressed().unwrap());
+    state.keys.insert(epoch, Arc::new(signer));
+    println!("Rotated node key: {} from epoch {} (current epoch {}), saved to {}", public_key, epoch, current, path.display());
+    HttpResponse::Ok().json(json!({
+        "node_id": record.node_id,
+        "index": record.index,
+        "epoch": epoch,
+        "public_key": public_key,
+        "keystore": path.display().to_string(),
+    }))
 }
 
 async fn check_status(
     request_id: web::Path<String>,
-    state: web::Data<Arc<Mutex<AppState>>>,
+    state: web::Data<Arc<AppState>>,
 ) -> impl Responder {
     let request_id = request_id.into_inner();
-    let state = state.lock().await;
     let pending_requests = state.pending_requests.lock().await;
     
     match pending_requests.get(&request_id) {
-        Some(signature) => HttpResponse::Ok().json(hex::encode(signature.as_slice())),
-        None => HttpResponse::Ok().body("processing"),
+        Some(record) => HttpResponse::Ok().json(record),
+        None => HttpResponse::NotFound().json(json!({ "error": format!("unknown request id {}", request_id) })),
     }
 }
 
@@ -184,26 +679,240 @@ async fn check_status(
 async fn main() -> std::io::Result<()> {
     let args = Args::parse();
 
-    let state = web::Data::new(Arc::new(Mutex::new(AppState {
+    // 门限必须在当前委员会规模下可达
+    if let Err(e) = args.quorum.validate(args.other_nodes.len()) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+    }
+    if let Some(addr) = args.admin_addr.filter(|addr| !addr.ip().is_loopback()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("--admin-addr {} must be a loopback address", addr),
+        ));
+    }
+
+    // 门限模式的群信息
+    let threshold_group = match &args.threshold_group {
+        Some(path) => {
+            let group = ThresholdGroup::read(path)?;
+            if group.threshold > args.other_nodes.len() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("threshold {} exceeds the {} configured nodes", group.threshold, args.other_nodes.len()),
+                ));
+            }
+            // 部分签名必须针对同一内容才能插值合成
+            if args.signature_scheme == SignatureScheme::MessageAugmentation {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "message augmentation is not supported in threshold mode",
+                ));
+            }
+            println!("Threshold mode: {} of {} shares", group.threshold, group.shares.len());
+            Some(Arc::new(group))
+        }
+        None => None,
+    };
+    println!("Signature scheme: {}", args.signature_scheme);
+
+    // 私密份额只经双向 TLS 传输，协调者的消息必须来自允许的协调者
+    if args.dkg_dir.is_some() && (args.tls_ca.is_none() || args.authorized_aggregators.is_empty()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--dkg-dir needs mutual TLS (--tls-ca) and at least one --authorized-aggregator as coordinator",
+        ));
+    }
+
+    // 恢复持久化的签名请求
+    let request_store: Box<dyn Store<RequestRecord>> = match &args.request_store {
+        Some(path) => {
+            println!("Using request store {}", path.display());
+            Box::new(FileStore::open(path)?)
+        }
+        None => Box::new(MemoryStore::new()),
+    };
+    let pending_requests = RequestTable::open(request_store)?;
+    let unfinished = pending_requests.unfinished();
+    println!("Loaded {} requests, {} unfinished", pending_requests.len(), unfinished.len());
+
+    // 节点通道的双向 TLS
+    let (transport, tls_server) = match (&args.tls_ca, &args.tls_cert, &args.tls_key) {
+        (Some(ca), Some(cert), Some(key)) => {
+            let identity = TlsIdentity::load(ca, cert, key)?;
+            println!("Mutual TLS enabled, certificate fingerprint {}", hex::encode(identity.fingerprint()));
+            let pins = args.tls_pins.iter().cloned().collect();
+            let client = TlsClient::new(identity, pins)?;
+            let server = TlsServer::new(TlsIdentity::load(ca, cert, key)?, args.tls_allowed_clients.clone())?;
+            (Transport::Tls(Arc::new(client)), Some(Arc::new(server)))
+        }
+        _ => (Transport::Plain, None),
+    };
+
+    // 签名请求的主节点认证
+    let identity = match &args.identity_key {
+        Some(path) => {
+            let (identity, is_new) = AggregatorIdentity::load_or_generate(path)?;
+            let action = if is_new { "Generated" } else { "Loaded" };
+            println!("{} aggregator identity {}, public key {}", action, path.display(), hex::encode(identity.public_key()));
+            Some(Arc::new(identity))
+        }
+        None => None,
+    };
+    let authenticator = if args.authorized_aggregators.is_empty() {
+        None
+    } else {
+        println!("Node service only signs for {} authorized aggregators", args.authorized_aggregators.len());
+        let authenticator = RequestAuthenticator::new(args.authorized_aggregators.clone());
+        Some(Arc::new(authenticator.with_max_skew(args.auth_max_skew_ms)))
+    };
+
+    let policy = match &args.policy {
+        Some(path) => {
+            println!("Using signing policy {}", path.display());
+            Some(Arc::new(PolicyEngine::load(path)?))
+        }
+        None => None,
+    };
+
+    let key_collector = KeyCollectorClient::new(&args.key_collector);
+    let epoch_length_ms = match key_collector.epoch().await {
+        Ok(info) => {
+            println!("Key epoch {} ({} ms per epoch)", info.epoch, info.epoch_length_ms);
+            Some(info.epoch_length_ms)
+        }
+        Err(e) => {
+            eprintln!("Failed to query key epochs, requests will not record an epoch: {}", e);
+            None
+        }
+    };
+
+    // 公钥收集服务按纪元记录公钥时，节点重启后必须继续使用已登记的密钥，不能每次生成新密钥
+    if epoch_length_ms.is_some() && args.keystore.is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the key collector uses key epochs, --keystore is required so the node keeps its registered key across restarts",
+        ));
+    }
+
+    // 加载或生成BLS签名密钥，以及轮换后安排在之后纪元使用的密钥
+    let (signer, is_new, keystore) = match &args.keystore {
+        Some(path) => {
+            let password = keystore::read_password(args.keystore_password_file.as_deref(), keystore::PASSWORD_ENV)?;
+            let (signer, is_new) = keystore::load_or_generate(path, &password)?;
+            if is_new {
+                println!("Generated new node key at {}", path.display());
+            } else {
+                println!("Loaded node key from {}", path.display());
+            }
+            (signer, is_new, Some((path.clone(), password)))
+        },
+        None => (Signer::random(), true, None),
+    };
+    let mut keys = KeySchedule::new(Arc::new(signer));
+    if let Some(length) = epoch_length_ms {
+        keys = keys.with_epoch_length(length);
+    }
+    if let Some((path, password)) = &keystore {
+        for (epoch, signer) in load_epoch_keys(path, password)? {
+            println!("Loaded node key for epoch {}", epoch);
+            keys.insert(epoch, Arc::new(signer));
+        }
+    }
+    let keys = Arc::new(keys);
+
+    // 将当前纪元的公钥发送到公钥收集服务
+    let node_id = args.node_id.clone().unwrap_or_else(|| args.node_addr.clone());
+    send_public_key(
+        &args.key_collector,
+        &keys.current(),
+        &node_id,
+        &args.node_addr,
+        is_new,
+        keys.current_epoch(),
+        args.signature_scheme,
+    )
+    .await;
+
+    let state = web::Data::new(Arc::new(AppState {
         other_nodes: args.other_nodes,
-        pending_requests: Mutex::new(HashMap::new()),
-    })));
+        key_collector,
+        quorum: args.quorum,
+        node_timeout: Duration::from_millis(args.node_timeout_ms),
+        request_timeout: Duration::from_millis(args.request_timeout_ms),
+        hash_scheme: args.hash_scheme,
+        signature_scheme: args.signature_scheme,
+        threshold_group,
+        pending_requests: Mutex::new(pending_requests),
+        epoch_length_ms,
+        keys: keys.clone(),
+        node_id,
+        node_addr: args.node_addr.clone(),
+        keystore,
+        transport: transport.clone(),
+        identity,
+    }));
+
+    let dkg = match &args.dkg_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            let password = keystore::read_password(args.keystore_password_file.as_deref(), keystore::PASSWORD_ENV)?;
+            println!("DKG enabled, key shares are saved to {}", dir.display());
+            let mut dkg = DkgNode::new(dir.clone(), password).with_transport(transport);
+            // 重分享结束后作废节点密钥文件中的旧份额
+            if let Some(path) = &args.keystore {
+                dkg = dkg.with_keystore(path.clone());
+            }
+            Some(Arc::new(dkg))
+        }
+        None => None,
+    };
 
     // 启动节点服务
+    let mut service = NodeService::new(keys);
+    if let Some(dkg) = dkg {
+        service = service.with_dkg(dkg);
+    }
+    if let Some(tls) = tls_server {
+        service = service.with_tls(tls);
+    }
+    if let Some(authenticator) = authenticator {
+        service = service.with_authenticator(authenticator);
+    }
+    if let Some(policy) = policy {
+        service = service.with_policy(policy);
+    }
     let node_addr = args.node_addr.clone();
     tokio::spawn(async move {
-        run_node_service(&node_addr, &args.key_collector).await.unwrap();
+        service.run(&node_addr).await.unwrap();
     });
 
+    // 继续重启前未完成的签名请求
+    resume_requests(state.get_ref(), unfinished);
+
     // 启动 Web 服务
-    HttpServer::new(move || {
+    let web_state = state.clone();
+    let web_server = HttpServer::new(move || {
         App::new()
-            .app_data(state.clone())
+            .app_data(web_state.clone())
             .route("/", web::get().to(hello))
             .route("/recv_msg", web::post().to(receive_message))
+            .route("/recv_messages", web::post().to(receive_messages))
             .route("/check_status/{request_id}", web::get().to(check_status))
     })
     .bind(args.web_addr)?
-    .run()
-    .await
+    .run();
+
+    // 管理接口只监听回环地址
+    match args.admin_addr {
+        Some(admin_addr) => {
+            let admin_server = HttpServer::new(move || {
+                App::new().app_data(state.clone()).route("/rotate_key", web::post().to(rotate_key))
+            })
+            .workers(1)
+            .bind(admin_addr)?
+            .run();
+            println!("Admin interface listening on {}", admin_addr);
+            tokio::try_join!(web_server, admin_server).map(|_| ())
+        }
+        None => web_server.await,
+    }
 }
\ No newline at end of file
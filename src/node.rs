@@ -0,0 +1,370 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use bn254::{PublicKey, Signature};
+use crate::auth::{AggregatorIdentity, RequestAuthenticator};
+use crate::dkg::{DkgMessage, DkgNode};
+use crate::epoch::KeySchedule;
+use crate::hash::HashScheme;
+use crate::protocol::{
+    dkg_body, message_digest, read_frame, read_message, sign_request_body, write_message, write_message_version, ErrorCode,
+    Message, ProtocolError, RequestAuth, PROTOCOL_VERSION,
+};
+use crate::policy::PolicyEngine;
+use crate::pop::is_reserved_message;
+use crate::request::now_millis;
+use crate::scheme::SignatureScheme;
+use crate::signer::Signer;
+use crate::tls::{TlsServer, Transport};
+
+// 节点返回的签名及其公钥
+#[derive(Clone, Copy, Debug)]
+pub struct NodeSignature {
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
+// 签名请求的选项: 默认按持有证明方案、以当前纪元的密钥签名，经明文 TCP 发送且不认证
+#[derive(Clone, Default)]
+pub struct SignOptions {
+    pub scheme: HashScheme,
+    pub signature_scheme: SignatureScheme,
+    pub epoch: Option<u64>,
+    pub transport: Transport,
+    pub identity: Option<Arc<AggregatorIdentity>>,
+}
+
+impl SignOptions {
+    pub fn new(scheme: HashScheme) -> Self {
+        SignOptions { scheme, ..Default::default() }
+    }
+
+    // 消息增强方案下节点对 "域标签 || 节点公钥 || 消息" 签名，返回的签名按增强后的内容验证
+    pub fn with_signature_scheme(mut self, signature_scheme: SignatureScheme) -> Self {
+        self.signature_scheme = signature_scheme;
+        self
+    }
+
+    // 要求节点用 epoch 纪元的密钥签名; 未指定时节点使用当前纪元的密钥
+    pub fn with_epoch(mut self, epoch: Option<u64>) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    // 经指定的传输方式 (明文 TCP 或 mTLS) 请求签名
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // 用主节点身份密钥对请求签名，要求认证的节点只接受允许列表中的主节点
+    pub fn with_identity(mut self, identity: Arc<AggregatorIdentity>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+}
+
+// 向签名节点发送签名请求并读取签名结果
+pub async fn request_signature(addr: &str, message: &[u8], options: &SignOptions) -> io::Result<NodeSignature> {
+    let SignOptions { scheme, signature_scheme, epoch, transport, identity } = options;
+    let (signature_scheme, epoch) = (*signature_scheme, *epoch);
+    let auth = identity.as_ref().map(|identity| {
+        identity.authorize(&sign_request_body(message, scheme, signature_scheme, epoch), now_millis())
+    });
+    let request =
+        Message::SignRequest { message: message.to_vec(), scheme: scheme.clone(), signature_scheme, epoch, auth };
+
+    // 从当前版本开始协商: 旧版本节点拒绝帧版本时，用能表达该请求的更低版本重试
+    let mut version = PROTOCOL_VERSION;
+    let reply = loop {
+        let mut stream = transport.connect(addr).await?;
+        write_message_version(&mut stream, &request, version).await?;
+        match read_message(&mut stream).await? {
+            Some(Message::Error(e)) if e.code == ErrorCode::UnsupportedVersion && version > request.min_version() => {
+                version -= 1;
+            }
+            reply => break reply,
+        }
+    };
+
+    match reply {
+        Some(Message::SignResponse { digest, public_key, signature }) => {
+            // 确认节点签名的正是发送的消息 (按节点公钥增强后的内容)
+            if digest != message_digest(&signature_scheme.signing_message(message, &public_key)?) {
+                return Err(ProtocolError::new(
+                    ErrorCode::Malformed,
+                    "node signed a different message than was sent",
+                )
+                .into());
+            }
+            Ok(NodeSignature { public_key, signature })
+        }
+        Some(Message::Error(e)) => Err(e.into()),
+        Some(other) => Err(ProtocolError::new(
+            ErrorCode::UnexpectedMessage,
+            format!("expected SignResponse, got {:?}", other.message_type()),
+        )
+        .into()),
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "node closed the connection without replying",
+        )),
+    }
+}
+
+// 节点签名服务: 对收到的每个签名请求签名并返回。按纪元轮换密钥，运行中加入密钥安排的新密钥在其纪元到达后使用
+pub struct NodeService {
+    keys: Arc<KeySchedule>,
+    dkg: Option<Arc<DkgNode>>,
+    tls: Option<Arc<TlsServer>>,
+    auth: Option<Arc<RequestAuthenticator>>,
+    policy: Option<Arc<PolicyEngine>>,
+}
+
+impl NodeService {
+    pub fn new(keys: Arc<KeySchedule>) -> Self {
+        NodeService { keys, dkg: None, tls: None, auth: None, policy: None }
+    }
+
+    // 只有一把密钥的节点
+    pub fn from_signer(signer: Arc<Signer>) -> Self {
+        Self::new(Arc::new(KeySchedule::new(signer)))
+    }
+
+    // 同时参与分布式密钥生成
+    pub fn with_dkg(mut self, dkg: Arc<DkgNode>) -> Self {
+        self.dkg = Some(dkg);
+        self
+    }
+
+    // 只接受完成双向 TLS 握手的连接
+    pub fn with_tls(mut self, tls: Arc<TlsServer>) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    // 只为允许的主节点签名
+    pub fn with_authenticator(mut self, auth: Arc<RequestAuthenticator>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    // 签名前按签名策略检查请求
+    pub fn with_policy(mut self, policy: Arc<PolicyEngine>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub async fn run(self, addr: &str) -> io::Result<()> {
+        self.serve(TcpListener::bind(addr).await?).await
+    }
+
+    // 在已绑定的监听套接字上提供服务
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        let NodeService { keys, dkg, tls, auth, policy } = self;
+        let addr = listener.local_addr()?;
+        match tls {
+            Some(_) => println!("Node service listening on {} (mutual TLS)", addr),
+            None => println!("Node service listening on {}", addr),
+        }
+
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let keys = keys.clone();
+            let dkg = dkg.clone();
+            let tls = tls.clone();
+            let auth = auth.clone();
+            let policy = policy.clone();
+            tokio::spawn(async move {
+                let context = NodeContext {
+                    keys: &keys,
+                    dkg: dkg.as_deref(),
+                    auth: auth.as_deref(),
+                    policy: policy.as_deref(),
+                    tls: tls.is_some(),
+                    peer,
+                };
+                let result = match tls {
+                    Some(tls) => match tls.accept(socket).await {
+                        Ok(stream) => handle_connection(stream, &context).await,
+                        Err(e) => {
+                            eprintln!("TLS handshake with {} failed: {}", peer, e);
+                            return;
+                        }
+                    },
+                    None => handle_connection(socket, &context).await,
+                };
+                if let Err(e) = result {
+                    eprintln!("Connection from {} closed with error: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+// 处理一个连接所需的节点服务状态
+struct NodeContext<'a> {
+    keys: &'a KeySchedule,
+    dkg: Option<&'a DkgNode>,
+    auth: Option<&'a RequestAuthenticator>,
+    policy: Option<&'a PolicyEngine>,
+    // 连接是否经过双向 TLS 握手
+    tls: bool,
+    peer: SocketAddr,
+}
+
+async fn handle_connection<S>(mut socket: S, context: &NodeContext<'_>) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        // 按请求的协议版本回复
+        let (version, request) = match read_frame(&mut socket).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                // 协议错误时回复错误帧后关闭连接
+                if let Some(protocol_error) = ProtocolError::from_io(&e) {
+                    write_message(&mut socket, &Message::Error(protocol_error.clone())).await?;
+                }
+                return Err(e);
+            }
+        };
+
+        let reply = match request {
+            Message::SignRequest { message, scheme, signature_scheme, epoch, auth: request_auth } => {
+                // 先认证主节点，再处理请求内容
+                let authorized = match context.auth {
+                    Some(auth) => {
+                        let body = sign_request_body(&message, &scheme, signature_scheme, epoch);
+                        auth.check(request_auth.as_ref(), &body, now_millis()).map(Some)
+                    }
+                    None => Ok(None),
+                };
+                match authorized {
+                    Ok(aggregator) => {
+                        // 签名策略按主节点公钥区分请求方，未认证时按来源 IP
+                        let requester = aggregator.map_or_else(|| context.peer.ip().to_string(), hex::encode);
+                        let refusal = context
+                            .policy
+                            .and_then(|policy| policy.evaluate(&requester, &message, now_millis()).err());
+                        match refusal {
+                            Some(reason) => {
+                                eprintln!("Refused to sign for {}: {}", requester, reason);
+                                Message::Error(ProtocolError::new(ErrorCode::PolicyRefused, reason))
+                            }
+                            None => {
+                                let reply = sign_request(context.keys, &message, &scheme, signature_scheme, epoch);
+                                // 只有产生了签名的请求才计入频率限制
+                                let recorded = match (&reply, context.policy) {
+                                    (Message::SignResponse { .. }, Some(policy)) => {
+                                        policy.record(&requester, now_millis())
+                                    }
+                                    _ => Ok(()),
+                                };
+                                match recorded {
+                                    Ok(()) => reply,
+                                    Err(reason) => {
+                                        eprintln!("Refused to sign for {}: {}", requester, reason);
+                                        Message::Error(ProtocolError::new(ErrorCode::PolicyRefused, reason))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(reason) => {
+                        eprintln!("Rejected sign request: {}", reason);
+                        Message::Error(ProtocolError::new(ErrorCode::Unauthorized, reason))
+                    }
+                }
+            }
+            Message::Dkg { message, auth } => match context.dkg {
+                Some(dkg) => match authorize_dkg(context, &message, auth.as_ref()) {
+                    Ok(()) => match dkg.handle(message, context.keys).await {
+                        Ok(reply) => Message::Dkg { message: reply, auth: None },
+                        Err(e) => {
+                            eprintln!("DKG request failed: {}", e);
+                            Message::Error(e)
+                        }
+                    },
+                    Err(reason) => {
+                        eprintln!("Rejected DKG message from {}: {}", context.peer, reason);
+                        Message::Error(ProtocolError::new(ErrorCode::Unauthorized, reason))
+                    }
+                },
+                None => Message::Error(ProtocolError::new(ErrorCode::DkgFailed, "DKG is not enabled on this node")),
+            },
+            other => Message::Error(ProtocolError::new(
+                ErrorCode::UnexpectedMessage,
+                format!("expected SignRequest or Dkg, got {:?}", other.message_type()),
+            )),
+        };
+
+        // 发送签名结果回主节点
+        write_message_version(&mut socket, &reply, version).await?;
+    }
+}
+
+// DKG 消息只经双向 TLS 接收; 协调者的消息还须由允许列表中的协调者签名，节点之间的 Deal 不需要
+fn authorize_dkg(context: &NodeContext<'_>, message: &DkgMessage, auth: Option<&RequestAuth>) -> Result<(), String> {
+    if !context.tls {
+        return Err("DKG messages are only accepted over mutual TLS".to_string());
+    }
+    if !message.from_coordinator() {
+        return Ok(());
+    }
+    let authenticator = context
+        .auth
+        .ok_or_else(|| "DKG coordinator messages need --authorized-aggregator".to_string())?;
+    let body = dkg_body(message).map_err(|e| e.to_string())?;
+    authenticator.check_dkg(auth, &body, now_millis()).map(|_| ())
+}
+
+fn sign_request(
+    keys: &KeySchedule,
+    message: &[u8],
+    scheme: &HashScheme,
+    signature_scheme: SignatureScheme,
+    epoch: Option<u64>,
+) -> Message {
+    if is_reserved_message(message) {
+        eprintln!("Refusing to sign a message in a reserved key-registration domain");
+        return Message::Error(ProtocolError::new(
+            ErrorCode::SigningFailed,
+            "message is in a reserved key-registration domain",
+        ));
+    }
+    println!(
+        "Node service received {} bytes to sign with {} ({}, epoch {:?})",
+        message.len(),
+        scheme,
+        signature_scheme,
+        epoch
+    );
+    let signer = match epoch {
+        Some(epoch) => match keys.signer_at(epoch) {
+            Some(signer) => signer,
+            None => {
+                eprintln!("Refusing to sign for epoch {}, which has not started", epoch);
+                return Message::Error(ProtocolError::new(
+                    ErrorCode::SigningFailed,
+                    format!("key epoch {} has not started", epoch),
+                ));
+            }
+        },
+        None => keys.current(),
+    };
+    let signed = signature_scheme
+        .signing_message(message, &signer.public_key())
+        .and_then(|signed| Ok((signer.sign_with(scheme, &signed)?, signed)));
+    match signed {
+        Ok((signature, signed)) => Message::SignResponse {
+            digest: message_digest(&signed),
+            public_key: signer.public_key(),
+            signature,
+        },
+        Err(e) => Message::Error(ProtocolError::new(ErrorCode::SigningFailed, e.to_string())),
+    }
+}
@@ -0,0 +1,276 @@
+// 节点签名策略
+//
+// 节点在签名前按策略文件检查请求，拒绝时把原因以 PolicyRefused 错误帧返回给主节点，
+// 主节点记录为该节点的 refused 结果。策略文件为 JSON，修改后在下一个请求时重新加载，
+// 新文件无效时继续使用原策略:
+// {
+//   "max_payload_bytes": 1024,
+//   "content_types": ["application/json", "text/plain"],
+//   "allow": [{ "prefix": "price=" }, { "regex": "^\\{" }],
+//   "deny": [{ "regex": "(?i)withdraw" }],
+//   "json_schema": { "type": "object", "required": ["price"] },
+//   "rate_limit": { "requests": 10, "window_ms": 1000 },
+//   "aggregator_rate_limits": { "<主节点公钥或 IP>": { "requests": 100, "window_ms": 1000 } }
+// }
+// 各项均可省略。allow 非空时消息须匹配其中之一，deny 中任意一条匹配即拒绝;
+// json_schema 只检查 application/json 的消息; 频率限制按主节点分别计数，
+// 认证的请求 (见 auth.rs) 按主节点公钥区分，否则按来源 IP
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use jsonschema::Validator;
+use regex::bytes::Regex;
+use serde::Deserialize;
+use crate::keys::invalid_data;
+
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const TEXT_CONTENT_TYPE: &str = "text/plain";
+pub const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+// 消息的内容类型: JSON 对象或数组为 application/json，其他 UTF-8 文本为 text/plain
+pub fn content_type(payload: &[u8]) -> &'static str {
+    match serde_json::from_slice::<serde_json::Value>(payload) {
+        Ok(value) if value.is_object() || value.is_array() => JSON_CONTENT_TYPE,
+        _ if std::str::from_utf8(payload).is_ok() => TEXT_CONTENT_TYPE,
+        _ => BINARY_CONTENT_TYPE,
+    }
+}
+
+// 时间窗口内最多签名的请求数
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests: usize,
+    pub window_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum PatternSpec {
+    Prefix(String),
+    Regex(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    max_payload_bytes: Option<usize>,
+    #[serde(default)]
+    content_types: Vec<String>,
+    #[serde(default)]
+    allow: Vec<PatternSpec>,
+    #[serde(default)]
+    deny: Vec<PatternSpec>,
+    json_schema: Option<serde_json::Value>,
+    rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    aggregator_rate_limits: HashMap<String, RateLimit>,
+}
+
+enum Pattern {
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn compile(spec: PatternSpec) -> io::Result<Self> {
+        match spec {
+            PatternSpec::Prefix(prefix) => Ok(Pattern::Prefix(prefix)),
+            PatternSpec::Regex(regex) => Regex::new(&regex)
+                .map(Pattern::Regex)
+                .map_err(|e| invalid_data(format!("invalid regex {:?}: {}", regex, e))),
+        }
+    }
+
+    fn matches(&self, payload: &[u8]) -> bool {
+        match self {
+            Pattern::Prefix(prefix) => payload.starts_with(prefix.as_bytes()),
+            Pattern::Regex(regex) => regex.is_match(payload),
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Prefix(prefix) => write!(f, "prefix {:?}", prefix),
+            Pattern::Regex(regex) => write!(f, "regex {:?}", regex.as_str()),
+        }
+    }
+}
+
+// 解析后的签名策略; 默认策略不做任何限制
+#[derive(Default)]
+pub struct Policy {
+    max_payload_bytes: Option<usize>,
+    content_types: Vec<String>,
+    allow: Vec<Pattern>,
+    deny: Vec<Pattern>,
+    json_schema: Option<Validator>,
+    rate_limit: Option<RateLimit>,
+    aggregator_rate_limits: HashMap<String, RateLimit>,
+}
+
+impl Policy {
+    pub fn parse(json: &str) -> io::Result<Self> {
+        let file: PolicyFile = serde_json::from_str(json).map_err(invalid_data)?;
+        let json_schema = match &file.json_schema {
+            Some(schema) => Some(
+                jsonschema::validator_for(schema).map_err(|e| invalid_data(format!("invalid JSON schema: {}", e)))?,
+            ),
+            None => None,
+        };
+        let compile = |specs: Vec<PatternSpec>| specs.into_iter().map(Pattern::compile).collect::<io::Result<Vec<_>>>();
+        Ok(Policy {
+            max_payload_bytes: file.max_payload_bytes,
+            content_types: file.content_types,
+            allow: compile(file.allow)?,
+            deny: compile(file.deny)?,
+            json_schema,
+            rate_limit: file.rate_limit,
+            aggregator_rate_limits: file.aggregator_rate_limits,
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // 按消息内容检查，不含频率限制; 拒绝时返回原因
+    pub fn check_payload(&self, payload: &[u8]) -> Result<(), String> {
+        if let Some(max) = self.max_payload_bytes {
+            if payload.len() > max {
+                return Err(format!("payload of {} bytes exceeds the limit of {}", payload.len(), max));
+            }
+        }
+        let content_type = content_type(payload);
+        if !self.content_types.is_empty() && !self.content_types.iter().any(|allowed| allowed == content_type) {
+            return Err(format!("content type {} is not allowed", content_type));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| pattern.matches(payload)) {
+            return Err("payload does not match any allow rule".to_string());
+        }
+        if let Some(pattern) = self.deny.iter().find(|pattern| pattern.matches(payload)) {
+            return Err(format!("payload matches deny rule {}", pattern));
+        }
+        if let (Some(schema), JSON_CONTENT_TYPE) = (&self.json_schema, content_type) {
+            let value: serde_json::Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+            schema.validate(&value).map_err(|e| format!("payload does not match the JSON schema: {}", e))?;
+        }
+        Ok(())
+    }
+
+    // requester 的频率限制: 单独配置的优先，否则使用默认限制
+    pub fn rate_limit_for(&self, requester: &str) -> Option<RateLimit> {
+        self.aggregator_rate_limits.get(requester).copied().or(self.rate_limit)
+    }
+}
+
+// 策略文件的修改时间和长度，用于判断是否需要重新加载
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// 节点服务使用的策略: 从文件加载并在文件变化时重新加载，同时记录各主节点的签名次数
+pub struct PolicyEngine {
+    path: PathBuf,
+    current: RwLock<(Arc<Policy>, FileStamp)>,
+    // 每个主节点在时间窗口内已签名的请求时间，窗口内没有请求的主节点不保留
+    history: Mutex<HashMap<String, VecDeque<u64>>>,
+}
+
+impl PolicyEngine {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let stamp = file_stamp(path);
+        let policy = Policy::load(path)?;
+        Ok(PolicyEngine {
+            path: path.to_path_buf(),
+            current: RwLock::new((Arc::new(policy), stamp)),
+            history: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // 当前策略，文件变化时先重新加载
+    pub fn policy(&self) -> Arc<Policy> {
+        let stamp = file_stamp(&self.path);
+        {
+            let current = self.current.read().unwrap();
+            if stamp.is_none() || current.1 == stamp {
+                return current.0.clone();
+            }
+        }
+        let mut current = self.current.write().unwrap();
+        if current.1 != stamp {
+            match Policy::load(&self.path) {
+                Ok(policy) => {
+                    println!("Reloaded signing policy from {}", self.path.display());
+                    current.0 = Arc::new(policy);
+                }
+                Err(e) => eprintln!(
+                    "Failed to reload signing policy from {}, keeping the previous policy: {}",
+                    self.path.display(),
+                    e
+                ),
+            }
+            current.1 = stamp;
+        }
+        current.0.clone()
+    }
+
+    // 检查 requester 的签名请求，拒绝时返回原因; 只检查不计数，签名成功后由 record 计入频率限制
+    pub fn evaluate(&self, requester: &str, payload: &[u8], now: u64) -> Result<(), String> {
+        let policy = self.policy();
+        policy.check_payload(payload)?;
+        self.admit(&policy, requester, now, false)
+    }
+
+    // 签名已产生后计入 requester 的频率限制; 并发请求在检查后占满窗口时仍拒绝，签名不发出
+    pub fn record(&self, requester: &str, now: u64) -> Result<(), String> {
+        self.admit(&self.policy(), requester, now, true)
+    }
+
+    // 仍在跟踪的请求方数量
+    pub fn tracked_requesters(&self) -> usize {
+        self.history.lock().unwrap().len()
+    }
+
+    fn admit(&self, policy: &Policy, requester: &str, now: u64, record: bool) -> Result<(), String> {
+        let mut history = self.history.lock().unwrap();
+        if record {
+            // 清除窗口内已没有请求或不再受限的请求方，避免按来源 IP 计数时无限增长
+            history.retain(|other, recent| {
+                policy
+                    .rate_limit_for(other)
+                    .is_some_and(|limit| recent.back().is_some_and(|at| at.saturating_add(limit.window_ms) > now))
+            });
+        }
+        let limit = match policy.rate_limit_for(requester) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let in_window = |at: &u64| at.saturating_add(limit.window_ms) > now;
+        if !record {
+            let count = history.get(requester).map_or(0, |recent| recent.iter().filter(|at| in_window(at)).count());
+            return if count >= limit.requests { Err(rate_limit_exceeded(limit)) } else { Ok(()) };
+        }
+        let recent = history.entry(requester.to_string()).or_default();
+        while recent.front().is_some_and(|at| !in_window(at)) {
+            recent.pop_front();
+        }
+        if recent.len() >= limit.requests {
+            return Err(rate_limit_exceeded(limit));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+}
+
+fn rate_limit_exceeded(limit: RateLimit) -> String {
+    format!("rate limit of {} requests per {} ms exceeded", limit.requests, limit.window_ms)
+}
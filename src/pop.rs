@@ -0,0 +1,127 @@
+// 公钥持有证明 (proof-of-possession)、轮换授权以及注销证明
+//
+// 登记公钥时必须附带用对应私钥对登记声明 "域标签 || 压缩公钥 || 节点 ID || 地址 || 生效纪元 || 序号" 的签名，
+// 防止恶意参与者登记抵消诚实公钥的流氓公钥 (rogue key)，
+// 同时把公钥绑定到节点 ID 和地址，截获的登记请求无法用于其他节点 ID 或改写地址。
+// 序号由节点单调递增 (默认取当前毫秒时间)，登记表拒绝不大于已记录序号的请求，截获的请求无法重放;
+// 已登记的节点更换公钥时，还需要当前公钥对同一声明的授权签名
+use std::io;
+use bn254::{PublicKey, Signature};
+use crate::keys::invalid_data;
+use crate::signer::Signer;
+use crate::threshold::ThresholdGroup;
+use crate::verifier::verify_signature;
+
+pub const POP_DOMAIN: &[u8] = b"DSS_BN254_POP_V2:";
+pub const AUTHORIZE_DOMAIN: &[u8] = b"DSS_BN254_AUTHORIZE_V1:";
+pub const DEREGISTER_DOMAIN: &[u8] = b"DSS_BN254_DEREGISTER_V2:";
+pub const GROUP_POP_DOMAIN: &[u8] = b"DSS_BN254_GROUP_POP_V2:";
+
+// 节点签名服务拒绝签名的域，包括已不再接受的旧版本域
+const RESERVED_DOMAINS: &[&[u8]] = &[
+    POP_DOMAIN,
+    AUTHORIZE_DOMAIN,
+    DEREGISTER_DOMAIN,
+    GROUP_POP_DOMAIN,
+    b"DSS_BN254_POP_V1:",
+    b"DSS_BN254_DEREGISTER_V1:",
+    b"DSS_BN254_GROUP_POP_V1:",
+];
+
+// 变长字段带长度前缀，避免相邻字段拼接产生歧义
+fn put_str(message: &mut Vec<u8>, value: &str) {
+    message.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    message.extend_from_slice(value.as_bytes());
+}
+
+fn registration_message(
+    domain: &[u8],
+    public_key: &PublicKey,
+    node_id: &str,
+    address: &str,
+    epoch: Option<u64>,
+    nonce: u64,
+) -> io::Result<Vec<u8>> {
+    let mut message = domain.to_vec();
+    message.extend_from_slice(&public_key.to_compressed().map_err(invalid_data)?);
+    put_str(&mut message, node_id);
+    put_str(&mut message, address);
+    match epoch {
+        Some(epoch) => {
+            message.push(1);
+            message.extend_from_slice(&epoch.to_be_bytes());
+        }
+        None => message.push(0),
+    }
+    message.extend_from_slice(&nonce.to_be_bytes());
+    Ok(message)
+}
+
+pub fn pop_message(public_key: &PublicKey, node_id: &str, address: &str, epoch: Option<u64>, nonce: u64) -> io::Result<Vec<u8>> {
+    registration_message(POP_DOMAIN, public_key, node_id, address, epoch, nonce)
+}
+
+// 节点当前公钥授权登记新公钥的消息，声明内容与新公钥的持有证明相同
+pub fn authorization_message(
+    public_key: &PublicKey,
+    node_id: &str,
+    address: &str,
+    epoch: Option<u64>,
+    nonce: u64,
+) -> io::Result<Vec<u8>> {
+    registration_message(AUTHORIZE_DOMAIN, public_key, node_id, address, epoch, nonce)
+}
+
+pub fn deregister_message(node_id: &str, nonce: u64) -> Vec<u8> {
+    let mut message = DEREGISTER_DOMAIN.to_vec();
+    put_str(&mut message, node_id);
+    message.extend_from_slice(&nonce.to_be_bytes());
+    message
+}
+
+// 门限群的持有证明消息，由门限个份额的部分签名合成: "域标签 || 群公钥 || 群 ID || 代数 || 门限 || 份额列表"。
+// 声明绑定代数和份额列表，截获的登记请求不能在重分享后重放，也不能换上其他份额公钥
+pub fn group_pop_message(group: &ThresholdGroup, group_id: &str, generation: u64) -> io::Result<Vec<u8>> {
+    let mut message = GROUP_POP_DOMAIN.to_vec();
+    message.extend_from_slice(&group.group_public_key.to_compressed().map_err(invalid_data)?);
+    put_str(&mut message, group_id);
+    message.extend_from_slice(&generation.to_be_bytes());
+    message.extend_from_slice(&(group.threshold as u32).to_be_bytes());
+    message.extend_from_slice(&(group.shares.len() as u32).to_be_bytes());
+    for share in &group.shares {
+        message.extend_from_slice(&share.index.to_be_bytes());
+        message.extend_from_slice(&share.public_key.to_compressed().map_err(invalid_data)?);
+    }
+    Ok(message)
+}
+
+// 节点签名服务不得为保留域下的消息签名，否则持有证明、授权和注销证明可被伪造
+pub fn is_reserved_message(message: &[u8]) -> bool {
+    RESERVED_DOMAINS.iter().any(|domain| message.starts_with(domain))
+}
+
+pub fn prove_possession(signer: &Signer, node_id: &str, address: &str, epoch: Option<u64>, nonce: u64) -> io::Result<Signature> {
+    signer.sign(&pop_message(&signer.public_key(), node_id, address, epoch, nonce)?)
+}
+
+pub fn verify_possession(
+    public_key: &PublicKey,
+    node_id: &str,
+    address: &str,
+    epoch: Option<u64>,
+    nonce: u64,
+    proof: &Signature,
+) -> bool {
+    match pop_message(public_key, node_id, address, epoch, nonce) {
+        Ok(message) => verify_signature(&message, proof, public_key),
+        Err(_) => false,
+    }
+}
+
+pub fn prove_deregistration(signer: &Signer, node_id: &str, nonce: u64) -> io::Result<Signature> {
+    signer.sign(&deregister_message(node_id, nonce))
+}
+
+pub fn verify_deregistration(public_key: &PublicKey, node_id: &str, nonce: u64, proof: &Signature) -> bool {
+    verify_signature(&deregister_message(node_id, nonce), proof, public_key)
+}
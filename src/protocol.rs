@@ -0,0 +1,523 @@
+// 节点间签名协议
+//
+// 帧格式: | 长度 u32 (大端) | 版本 u8 | 消息类型 u8 | 负载 |
+// 长度字段不包含自身，只覆盖版本、消息类型和负载
+// 版本 2: 签名请求携带哈希方式
+// 版本 3: 签名请求携带签名方案 (是否对消息做公钥增强)
+// 版本 4: 签名请求可携带密钥纪元，节点用该纪元的密钥签名
+// 版本 5: 签名请求可携带主节点身份密钥的签名、时间戳和随机数 (见 auth.rs)
+// 版本 6: Dkg 消息可携带协调者的认证，格式与签名请求相同
+// 各版本只在签名请求末尾追加字段，读取时接受 MIN_PROTOCOL_VERSION 到 PROTOCOL_VERSION 的帧，
+// 旧版本缺少的字段取默认值; 节点按请求的版本回复，便于滚动升级
+// Dkg 消息的负载为 JSON 编码的 DkgMessage，只在启用 DKG 的节点之间使用
+use std::fmt;
+use std::io;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use bn254::{PublicKey, Signature};
+use crate::dkg::DkgMessage;
+use crate::hash::HashScheme;
+use crate::scheme::SignatureScheme;
+
+pub const PROTOCOL_VERSION: u8 = 6;
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+const HEADER_LEN: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    SignRequest = 1,
+    SignResponse = 2,
+    Error = 3,
+    Dkg = 4,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = ProtocolError;
+
+    fn try_from(value: u8) -> Result<Self, ProtocolError> {
+        match value {
+            1 => Ok(MessageType::SignRequest),
+            2 => Ok(MessageType::SignResponse),
+            3 => Ok(MessageType::Error),
+            4 => Ok(MessageType::Dkg),
+            _ => Err(ProtocolError::new(
+                ErrorCode::UnknownMessageType,
+                format!("unknown message type {}", value),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnsupportedVersion = 1,
+    UnknownMessageType = 2,
+    FrameTooLarge = 3,
+    Malformed = 4,
+    UnexpectedMessage = 5,
+    SigningFailed = 6,
+    DkgFailed = 7,
+    Unauthorized = 8,
+    // 节点按签名策略拒绝签名，reason 为拒绝原因
+    PolicyRefused = 9,
+}
+
+impl ErrorCode {
+    fn from_u8(value: u8) -> Result<Self, ProtocolError> {
+        match value {
+            1 => Ok(ErrorCode::UnsupportedVersion),
+            2 => Ok(ErrorCode::UnknownMessageType),
+            3 => Ok(ErrorCode::FrameTooLarge),
+            4 => Ok(ErrorCode::Malformed),
+            5 => Ok(ErrorCode::UnexpectedMessage),
+            6 => Ok(ErrorCode::SigningFailed),
+            7 => Ok(ErrorCode::DkgFailed),
+            8 => Ok(ErrorCode::Unauthorized),
+            9 => Ok(ErrorCode::PolicyRefused),
+            _ => Err(ProtocolError::malformed(format!("unknown error code {}", value))),
+        }
+    }
+}
+
+// 协议错误，通过 io::Error 传递，可用 ProtocolError::from_io 取回
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub reason: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, reason: impl Into<String>) -> Self {
+        ProtocolError { code, reason: reason.into() }
+    }
+
+    pub fn malformed(reason: impl Into<String>) -> Self {
+        ProtocolError::new(ErrorCode::Malformed, reason)
+    }
+
+    pub fn from_io(e: &io::Error) -> Option<&ProtocolError> {
+        e.get_ref().and_then(|inner| inner.downcast_ref::<ProtocolError>())
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.reason)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for io::Error {
+    fn from(e: ProtocolError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+pub fn message_digest(message: &[u8]) -> [u8; 32] {
+    Sha256::digest(message).into()
+}
+
+// 主节点对签名请求的认证: public_key 为压缩的 secp256k1 公钥，
+// signature 为对 (时间戳, 随机数, 请求内容) 的 ECDSA 签名 (r || s)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestAuth {
+    pub public_key: [u8; 33],
+    // 毫秒
+    pub timestamp: u64,
+    pub nonce: [u8; 16],
+    pub signature: [u8; 64],
+}
+
+// 签名请求中除认证外的内容，按帧中的编码; 主节点的认证签名覆盖这部分
+pub fn sign_request_body(
+    message: &[u8],
+    scheme: &HashScheme,
+    signature_scheme: SignatureScheme,
+    epoch: Option<u64>,
+) -> Vec<u8> {
+    let mut writer = PayloadWriter::default();
+    writer.put_sign_request(message, scheme, signature_scheme, epoch, PROTOCOL_VERSION);
+    writer.into_inner()
+}
+
+// DKG 消息中协调者认证覆盖的内容: JSON 编码的 DkgMessage
+pub fn dkg_body(message: &DkgMessage) -> io::Result<Vec<u8>> {
+    serde_json::to_vec(message).map_err(|e| ProtocolError::malformed(e.to_string()).into())
+}
+
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    SignRequest {
+        message: Vec<u8>,
+        scheme: HashScheme,
+        signature_scheme: SignatureScheme,
+        // 未指定时节点使用自己当前纪元的密钥
+        epoch: Option<u64>,
+        auth: Option<RequestAuth>,
+    },
+    // digest 为节点实际签名内容的 SHA-256，主节点据此确认节点签的正是发送的消息
+    // public_key 为签名节点的公钥，主节点据此在登记表中定位签名者
+    SignResponse {
+        digest: [u8; 32],
+        public_key: PublicKey,
+        signature: Signature,
+    },
+    Error(ProtocolError),
+    // 协调者发给节点的 DKG 消息必须带认证，节点之间的 Deal 和节点的回复不带
+    Dkg {
+        message: DkgMessage,
+        auth: Option<RequestAuth>,
+    },
+}
+
+impl Message {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Message::SignRequest { .. } => MessageType::SignRequest,
+            Message::SignResponse { .. } => MessageType::SignResponse,
+            Message::Error(_) => MessageType::Error,
+            Message::Dkg { .. } => MessageType::Dkg,
+        }
+    }
+
+    // 能表达该消息的最低协议版本
+    pub fn min_version(&self) -> u8 {
+        match self {
+            Message::Dkg { auth: Some(_), .. } => 6,
+            Message::SignRequest { auth: Some(_), .. } => 5,
+            Message::SignRequest { epoch: Some(_), .. } => 4,
+            Message::SignRequest { signature_scheme: SignatureScheme::MessageAugmentation, .. } => 3,
+            Message::SignRequest { scheme: HashScheme::Svdw { .. }, .. } | Message::Dkg { .. } => 2,
+            _ => MIN_PROTOCOL_VERSION,
+        }
+    }
+
+    fn encode_payload(&self, version: u8) -> io::Result<Vec<u8>> {
+        if version < self.min_version() {
+            return Err(ProtocolError::new(
+                ErrorCode::UnsupportedVersion,
+                format!("{:?} needs protocol version {}, peer uses {}", self.message_type(), self.min_version(), version),
+            )
+            .into());
+        }
+        let mut writer = PayloadWriter::default();
+        match self {
+            Message::SignRequest { message, scheme, signature_scheme, epoch, auth } => {
+                writer.put_sign_request(message, scheme, *signature_scheme, *epoch, version);
+                if version >= 5 {
+                    writer.put_auth(auth.as_ref());
+                }
+            }
+            Message::SignResponse { digest, public_key, signature } => {
+                writer.put_raw(digest);
+                let public_key = public_key
+                    .to_compressed()
+                    .map_err(|e| ProtocolError::malformed(e.to_string()))?;
+                writer.put_bytes(&public_key);
+                let signature = signature
+                    .to_compressed()
+                    .map_err(|e| ProtocolError::malformed(e.to_string()))?;
+                writer.put_bytes(&signature);
+            }
+            Message::Error(error) => {
+                writer.put_u8(error.code as u8);
+                writer.put_str(&error.reason);
+            }
+            Message::Dkg { message, auth } => {
+                writer.put_bytes(&dkg_body(message)?);
+                if version >= 6 {
+                    writer.put_auth(auth.as_ref());
+                }
+            }
+        }
+        Ok(writer.into_inner())
+    }
+
+    fn decode_payload(version: u8, message_type: MessageType, payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut reader = PayloadReader::new(payload);
+        let message = match message_type {
+            MessageType::SignRequest => {
+                let message = reader.get_bytes()?.to_vec();
+                let scheme = match if version >= 2 { reader.get_u8()? } else { 0 } {
+                    0 => HashScheme::TryAndIncrement,
+                    1 => HashScheme::svdw(&reader.get_str()?).map_err(ProtocolError::malformed)?,
+                    other => return Err(ProtocolError::malformed(format!("unknown hash scheme {}", other))),
+                };
+                let signature_scheme = match if version >= 3 { reader.get_u8()? } else { 0 } {
+                    0 => SignatureScheme::ProofOfPossession,
+                    1 => SignatureScheme::MessageAugmentation,
+                    other => return Err(ProtocolError::malformed(format!("unknown signature scheme {}", other))),
+                };
+                let epoch = match if version >= 4 { reader.get_u8()? } else { 0 } {
+                    0 => None,
+                    1 => Some(reader.get_u64()?),
+                    other => return Err(ProtocolError::malformed(format!("unknown epoch flag {}", other))),
+                };
+                let auth = if version >= 5 { reader.get_auth()? } else { None };
+                Message::SignRequest { message, scheme, signature_scheme, epoch, auth }
+            }
+            MessageType::SignResponse => {
+                let digest = reader.get_array::<32>()?;
+                let public_key = PublicKey::from_compressed(reader.get_bytes()?)
+                    .map_err(|e| ProtocolError::malformed(format!("invalid public key: {}", e)))?;
+                let signature = Signature::from_compressed(reader.get_bytes()?)
+                    .map_err(|e| ProtocolError::malformed(format!("invalid signature: {}", e)))?;
+                Message::SignResponse { digest, public_key, signature }
+            }
+            MessageType::Error => {
+                let code = ErrorCode::from_u8(reader.get_u8()?)?;
+                let reason = reader.get_str()?;
+                Message::Error(ProtocolError::new(code, reason))
+            }
+            MessageType::Dkg if version < 2 => {
+                return Err(ProtocolError::malformed(format!("Dkg messages need protocol version 2, got {}", version)))
+            }
+            MessageType::Dkg => {
+                let message = serde_json::from_slice(reader.get_bytes()?)
+                    .map_err(|e| ProtocolError::malformed(format!("invalid DKG message: {}", e)))?;
+                let auth = if version >= 6 { reader.get_auth()? } else { None };
+                Message::Dkg { message, auth }
+            }
+        };
+        reader.finish()?;
+        Ok(message)
+    }
+}
+
+pub async fn write_message<W>(writer: &mut W, message: &Message) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_message_version(writer, message, PROTOCOL_VERSION).await
+}
+
+// 按指定版本编码; 消息用到该版本没有的字段时返回 UnsupportedVersion
+pub async fn write_message_version<W>(writer: &mut W, message: &Message, version: u8) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let payload = message.encode_payload(version)?;
+    let frame_len = HEADER_LEN + payload.len();
+    if frame_len > MAX_FRAME_LEN {
+        return Err(ProtocolError::new(
+            ErrorCode::FrameTooLarge,
+            format!("frame of {} bytes exceeds limit of {}", frame_len, MAX_FRAME_LEN),
+        )
+        .into());
+    }
+
+    let mut frame = Vec::with_capacity(4 + frame_len);
+    frame.extend_from_slice(&(frame_len as u32).to_be_bytes());
+    frame.push(version);
+    frame.push(message.message_type() as u8);
+    frame.extend_from_slice(&payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+// 读取一帧; 连接在帧边界处关闭时返回 Ok(None)
+pub async fn read_message<R>(reader: &mut R) -> io::Result<Option<Message>>
+where
+    R: AsyncRead + Unpin,
+{
+    Ok(read_frame(reader).await?.map(|(_, message)| message))
+}
+
+// 同 read_message，同时返回帧的协议版本
+pub async fn read_frame<R>(reader: &mut R) -> io::Result<Option<(u8, Message)>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let frame_len = u32::from_be_bytes(len_buf) as usize;
+    if frame_len > MAX_FRAME_LEN {
+        return Err(ProtocolError::new(
+            ErrorCode::FrameTooLarge,
+            format!("frame of {} bytes exceeds limit of {}", frame_len, MAX_FRAME_LEN),
+        )
+        .into());
+    }
+    if frame_len < HEADER_LEN {
+        return Err(ProtocolError::malformed("frame shorter than header").into());
+    }
+
+    let mut frame = vec![0u8; frame_len];
+    reader.read_exact(&mut frame).await?;
+
+    let version = frame[0];
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        return Err(ProtocolError::new(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "unsupported protocol version {}, expected {} to {}",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        )
+        .into());
+    }
+    let message_type = MessageType::try_from(frame[1])?;
+    Ok(Some((version, Message::decode_payload(version, message_type, &frame[HEADER_LEN..])?)))
+}
+
+#[derive(Default)]
+struct PayloadWriter {
+    buf: Vec<u8>,
+}
+
+impl PayloadWriter {
+    fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_raw(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn put_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+
+    // 旧版本的帧在该版本最后一个字段处结束
+    fn put_sign_request(
+        &mut self,
+        message: &[u8],
+        scheme: &HashScheme,
+        signature_scheme: SignatureScheme,
+        epoch: Option<u64>,
+        version: u8,
+    ) {
+        self.put_bytes(message);
+        if version < 2 {
+            return;
+        }
+        match scheme {
+            HashScheme::TryAndIncrement => self.put_u8(0),
+            HashScheme::Svdw { dst } => {
+                self.put_u8(1);
+                self.put_str(dst);
+            }
+        }
+        if version < 3 {
+            return;
+        }
+        match signature_scheme {
+            SignatureScheme::ProofOfPossession => self.put_u8(0),
+            SignatureScheme::MessageAugmentation => self.put_u8(1),
+        }
+        if version < 4 {
+            return;
+        }
+        match epoch {
+            None => self.put_u8(0),
+            Some(epoch) => {
+                self.put_u8(1);
+                self.put_u64(epoch);
+            }
+        }
+    }
+
+    // 认证标志，带认证时依次为公钥、时间戳、随机数和签名
+    fn put_auth(&mut self, auth: Option<&RequestAuth>) {
+        match auth {
+            None => self.put_u8(0),
+            Some(auth) => {
+                self.put_u8(1);
+                self.put_raw(&auth.public_key);
+                self.put_u64(auth.timestamp);
+                self.put_raw(&auth.nonce);
+                self.put_raw(&auth.signature);
+            }
+        }
+    }
+
+    fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+struct PayloadReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> PayloadReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        PayloadReader { buf }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.buf.len() < n {
+            return Err(ProtocolError::malformed("payload truncated"));
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_u32(&mut self) -> Result<u32, ProtocolError> {
+        let bytes = self.get_array::<4>()?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn get_u64(&mut self) -> Result<u64, ProtocolError> {
+        let bytes = self.get_array::<8>()?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn get_array<const N: usize>(&mut self) -> Result<[u8; N], ProtocolError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn get_bytes(&mut self) -> Result<&'a [u8], ProtocolError> {
+        let len = self.get_u32()? as usize;
+        self.take(len)
+    }
+
+    fn get_str(&mut self) -> Result<String, ProtocolError> {
+        String::from_utf8(self.get_bytes()?.to_vec())
+            .map_err(|_| ProtocolError::malformed("string is not valid UTF-8"))
+    }
+
+    fn get_auth(&mut self) -> Result<Option<RequestAuth>, ProtocolError> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(RequestAuth {
+                public_key: self.get_array()?,
+                timestamp: self.get_u64()?,
+                nonce: self.get_array()?,
+                signature: self.get_array()?,
+            })),
+            other => Err(ProtocolError::malformed(format!("unknown auth flag {}", other))),
+        }
+    }
+
+    fn finish(&self) -> Result<(), ProtocolError> {
+        if !self.buf.is_empty() {
+            return Err(ProtocolError::malformed(format!("{} trailing bytes in payload", self.buf.len())));
+        }
+        Ok(())
+    }
+}
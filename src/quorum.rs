@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+// 签名请求完成所需的签名者数量: 绝对数量或委员会的比例
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quorum {
+    Count(usize),
+    Fraction(f64),
+}
+
+impl Quorum {
+    pub fn all() -> Self {
+        Quorum::Fraction(1.0)
+    }
+
+    // 在 committee_size 个节点的委员会中至少需要多少个签名
+    pub fn required(&self, committee_size: usize) -> usize {
+        match *self {
+            Quorum::Count(count) => count,
+            Quorum::Fraction(fraction) => {
+                // 减去一个极小值，避免 2/3 * 3 这类浮点误差向上取整
+                let required = (fraction * committee_size as f64 - 1e-9).ceil() as usize;
+                required.clamp(1, committee_size.max(1))
+            }
+        }
+    }
+
+    pub fn is_met(&self, signers: usize, committee_size: usize) -> bool {
+        signers >= self.required(committee_size)
+    }
+
+    // 检查门限对给定委员会是否可达
+    pub fn validate(&self, committee_size: usize) -> Result<(), String> {
+        let required = self.required(committee_size);
+        if required == 0 {
+            return Err("quorum must require at least one signer".to_string());
+        }
+        if required > committee_size {
+            return Err(format!(
+                "quorum requires {} signers but the committee only has {} nodes",
+                required, committee_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Quorum::all()
+    }
+}
+
+impl FromStr for Quorum {
+    type Err = String;
+
+    // 支持 "all"、"3"、"2/3" 和 "0.67" 四种写法
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Quorum::all());
+        }
+
+        let fraction = if let Some((numerator, denominator)) = s.split_once('/') {
+            let numerator: f64 = numerator.trim().parse().map_err(|_| format!("invalid quorum: {}", s))?;
+            let denominator: f64 = denominator.trim().parse().map_err(|_| format!("invalid quorum: {}", s))?;
+            if denominator == 0.0 {
+                return Err(format!("invalid quorum: {}", s));
+            }
+            numerator / denominator
+        } else if s.contains('.') {
+            s.parse::<f64>().map_err(|_| format!("invalid quorum: {}", s))?
+        } else {
+            let count = s.parse::<usize>().map_err(|_| format!("invalid quorum: {}", s))?;
+            if count == 0 {
+                return Err("quorum must require at least one signer".to_string());
+            }
+            return Ok(Quorum::Count(count));
+        };
+
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(format!("quorum fraction must be in (0, 1], got {}", s));
+        }
+        Ok(Quorum::Fraction(fraction))
+    }
+}
+
+impl fmt::Display for Quorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quorum::Count(count) => write!(f, "{}", count),
+            Quorum::Fraction(fraction) => write!(f, "{}", fraction),
+        }
+    }
+}
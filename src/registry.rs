@@ -0,0 +1,456 @@
+// 公钥收集服务的节点登记表
+//
+// 每个节点 ID 对应一条记录; 首次登记时分配的 index 永不改变也不复用，
+// 签名者位图中的第 i 位即对应 index 为 i 的节点
+//
+// 公钥按纪元 (epoch) 轮换: 记录保存每个公钥的生效纪元，已开始的纪元的公钥不再改变，
+// 因此纪元 N 中的签名始终按纪元 N 的公钥集合验证
+use std::io;
+use serde::{Deserialize, Serialize};
+use bn254::{PublicKey, Signature};
+use crate::collector::{GroupRegistration, KeyRegistration};
+use crate::keys::{invalid_data, KeySet};
+use crate::pop::verify_deregistration;
+use crate::scheme::SignatureScheme;
+use crate::threshold::{SharePublicKey, ThresholdGroup};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    Active,
+    Deregistered,
+}
+
+// 从 epoch 纪元起生效的公钥
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EpochKey {
+    pub epoch: u64,
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub public_key: PublicKey,
+    // 登记该公钥时的防御方案: 附带持有证明为 proof_of_possession，没有持有证明为 message_augmentation
+    #[serde(default)]
+    pub signature_scheme: SignatureScheme,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeRecord {
+    pub node_id: String,
+    pub index: usize,
+    pub address: String,
+    // 最近登记的公钥，可能要到之后的纪元才生效
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub public_key: PublicKey,
+    pub registered_at: u64,
+    pub updated_at: u64,
+    pub status: NodeStatus,
+    // 公钥历史，按生效纪元升序; 旧记录没有历史，public_key 对所有纪元有效
+    #[serde(default)]
+    pub keys: Vec<EpochKey>,
+    // 最近一次被接受的登记或注销请求的序号，更小或相等序号的请求视为重放
+    #[serde(default)]
+    pub nonce: u64,
+}
+
+impl NodeRecord {
+    pub fn is_active(&self) -> bool {
+        self.status == NodeStatus::Active
+    }
+
+    // epoch 纪元生效的公钥; 节点在该纪元之后才登记时返回 None
+    pub fn key_at(&self, epoch: u64) -> Option<&PublicKey> {
+        if self.keys.is_empty() {
+            return Some(&self.public_key);
+        }
+        self.keys.iter().rev().find(|key| key.epoch <= epoch).map(|key| &key.public_key)
+    }
+
+    // epoch 纪元生效的公钥的登记方式; epoch 为 None 时为最近登记的公钥的登记方式。
+    // 没有历史的旧记录都经过持有证明登记
+    pub fn scheme_at(&self, epoch: Option<u64>) -> SignatureScheme {
+        let key = match epoch {
+            Some(epoch) => self.keys.iter().rev().find(|key| key.epoch <= epoch),
+            None => self.keys.last(),
+        };
+        key.map(|key| key.signature_scheme).unwrap_or_default()
+    }
+
+    // 当前纪元生效或已安排在之后纪元生效的公钥，节点可以用它们管理自己的记录
+    fn live_keys(&self, epoch: u64) -> Vec<&PublicKey> {
+        let mut keys: Vec<&PublicKey> = self.key_at(epoch).into_iter().collect();
+        keys.extend(self.keys.iter().filter(|key| key.epoch > epoch).map(|key| &key.public_key));
+        keys
+    }
+
+    fn has_key(&self, public_key: &PublicKey) -> bool {
+        self.public_key.0 == public_key.0 || self.keys.iter().any(|key| key.public_key.0 == public_key.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterOutcome {
+    Created,
+    // 与已有记录完全相同的重复登记
+    Unchanged,
+    // 地址或公钥变化，或重新激活已注销的节点
+    Updated,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    InvalidProof,
+    // 公钥已被其他节点 ID 登记
+    KeyInUse(String),
+    // 公钥曾被该节点使用并已轮换掉，不能重新启用
+    KeyRetired(String),
+    // 公钥的生效纪元已经开始，不能再更改该纪元的公钥
+    EpochStarted { epoch: u64, current: u64 },
+    UnknownNode(String),
+    // 请求序号不大于节点上次记录的序号
+    StaleNonce { nonce: u64, last: u64 },
+    // 更换公钥缺少节点当前公钥的授权签名
+    Unauthorized(String),
+    // 群 ID 已登记为其他群公钥
+    GroupKeyChanged(String),
+    // 登记请求的代数不是群记录的当前代数 (重复提交) 或下一代 (重分享)
+    StaleGeneration { generation: u64, expected: u64 },
+    // 份额公钥属于该群当前或已作废的份额
+    ShareRetired(String),
+    UnknownGroup(String),
+    // 公钥没有持有证明，只能用于消息增强签名
+    ProofRequired(String),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::InvalidProof => write!(f, "invalid proof"),
+            RegistryError::KeyInUse(node_id) => write!(f, "public key is already registered by node {}", node_id),
+            RegistryError::KeyRetired(node_id) => write!(f, "public key was already rotated out by node {}", node_id),
+            RegistryError::EpochStarted { epoch, current } => {
+                write!(f, "key epoch {} has already started (current epoch {})", epoch, current)
+            }
+            RegistryError::UnknownNode(node_id) => write!(f, "unknown node {}", node_id),
+            RegistryError::StaleNonce { nonce, last } => {
+                write!(f, "request nonce {} is not greater than the last accepted nonce {}", nonce, last)
+            }
+            RegistryError::Unauthorized(node_id) => {
+                write!(f, "changing the key of node {} requires an authorization signed by its registered key", node_id)
+            }
+            RegistryError::GroupKeyChanged(group_id) => {
+                write!(f, "group {} is already registered with a different group public key", group_id)
+            }
+            RegistryError::StaleGeneration { generation, expected } => {
+                write!(f, "group generation {} does not match the expected generation {}", generation, expected)
+            }
+            RegistryError::ShareRetired(group_id) => {
+                write!(f, "a share public key was already used by an earlier generation of group {}", group_id)
+            }
+            RegistryError::UnknownGroup(group_id) => write!(f, "unknown group {}", group_id),
+            RegistryError::ProofRequired(node_id) => write!(
+                f,
+                "node {} registered its key without proof of possession, only message_augmentation signatures may use it",
+                node_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+// 按 index 排序的节点记录
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    nodes: Vec<NodeRecord>,
+    // 登记时要求的防御方案: message_augmentation 时也接受没有持有证明的公钥
+    signature_scheme: SignatureScheme,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    // 从公钥收集服务返回的记录重建登记表，index 必须从 0 开始连续
+    pub fn from_records(mut nodes: Vec<NodeRecord>) -> io::Result<Self> {
+        nodes.sort_by_key(|node| node.index);
+        for (position, node) in nodes.iter().enumerate() {
+            if node.index != position {
+                return Err(invalid_data(format!(
+                    "registry index {} of node {} is not contiguous",
+                    node.index, node.node_id
+                )));
+            }
+        }
+        Ok(Registry { nodes, signature_scheme: SignatureScheme::default() })
+    }
+
+    pub fn with_signature_scheme(mut self, signature_scheme: SignatureScheme) -> Self {
+        self.signature_scheme = signature_scheme;
+        self
+    }
+
+    pub fn records(&self) -> &[NodeRecord] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, node_id: &str) -> Option<&NodeRecord> {
+        self.nodes.iter().find(|node| node.node_id == node_id)
+    }
+
+    // 各节点最近登记的公钥 (含已注销节点)，按 index 排列，用于验证没有记录纪元的旧签名
+    pub fn key_set(&self) -> KeySet {
+        KeySet::new(self.nodes.iter().map(|node| node.public_key).collect())
+    }
+
+    // epoch 纪元的公钥集合 (含已注销节点)，按 index 排列，用于按位图验证该纪元的签名。
+    // 在该纪元之后才登记的节点不会出现在位图中，用其最早的公钥占位以保持 index 对齐
+    pub fn key_set_at(&self, epoch: u64) -> KeySet {
+        KeySet::new(
+            self.nodes
+                .iter()
+                .map(|node| match node.key_at(epoch) {
+                    Some(public_key) => *public_key,
+                    None => node.keys.first().map(|key| key.public_key).unwrap_or(node.public_key),
+                })
+                .collect(),
+        )
+    }
+
+    // 活跃节点中在 epoch 纪元持有该公钥的节点 index; 安排在 epoch 之后生效的公钥不匹配
+    pub fn active_index_at(&self, public_key: &PublicKey, epoch: u64) -> Option<usize> {
+        self.nodes
+            .iter()
+            .find(|node| node.is_active() && node.key_at(epoch).is_some_and(|key| key.0 == public_key.0))
+            .map(|node| node.index)
+    }
+
+    // 检查 signers 中各节点在 epoch 纪元 (None 为最近登记) 的公钥可以用于 signature_scheme 的签名
+    pub fn check_scheme(
+        &self,
+        signers: &[usize],
+        epoch: Option<u64>,
+        signature_scheme: SignatureScheme,
+    ) -> Result<(), RegistryError> {
+        for node in signers.iter().filter_map(|index| self.nodes.get(*index)) {
+            if !signature_scheme.accepts(node.scheme_at(epoch)) {
+                return Err(RegistryError::ProofRequired(node.node_id.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    // 登记或轮换公钥，epoch 为当前纪元。
+    // 新节点的公钥从当前纪元 (或登记请求指定的之后纪元) 起生效;
+    // 已有节点更换公钥时新公钥从指定纪元 (默认下一纪元) 起生效，当前纪元仍使用原公钥。
+    // 已有节点的任何变更都要求序号大于上次记录的序号; 更换公钥还要求当前或已安排的公钥的授权签名，
+    // 地址变更和重新激活由原公钥自己的持有证明授权。
+    // 以消息增强方案运行时也接受没有持有证明的登记，此时已有节点的变更都要求授权签名
+    pub fn register(
+        &mut self,
+        registration: &KeyRegistration,
+        now: u64,
+        epoch: u64,
+    ) -> Result<RegisterOutcome, RegistryError> {
+        let signature_scheme = match registration.proof_of_possession {
+            Some(_) if registration.verify() => SignatureScheme::ProofOfPossession,
+            None if self.signature_scheme == SignatureScheme::MessageAugmentation => SignatureScheme::MessageAugmentation,
+            _ => return Err(RegistryError::InvalidProof),
+        };
+        if let Some(other) = self.nodes.iter().find(|node| {
+            node.node_id != registration.node_id && node.has_key(&registration.public_key)
+        }) {
+            return Err(RegistryError::KeyInUse(other.node_id.clone()));
+        }
+
+        if let Some(node) = self.nodes.iter_mut().find(|node| node.node_id == registration.node_id) {
+            let live_keys = node.live_keys(epoch);
+            let current = live_keys.iter().any(|key| key.0 == registration.public_key.0);
+            if current && node.is_active() && node.address == registration.address {
+                return Ok(RegisterOutcome::Unchanged);
+            }
+            if registration.nonce <= node.nonce {
+                return Err(RegistryError::StaleNonce { nonce: registration.nonce, last: node.nonce });
+            }
+            let authorized = live_keys.iter().any(|key| registration.is_authorized_by(key));
+            if current && !authorized && signature_scheme == SignatureScheme::MessageAugmentation {
+                return Err(RegistryError::Unauthorized(node.node_id.clone()));
+            }
+            if !current {
+                // 轮换掉的公钥可能已经泄露，不允许重新启用
+                if node.has_key(&registration.public_key) {
+                    return Err(RegistryError::KeyRetired(node.node_id.clone()));
+                }
+                if !authorized {
+                    return Err(RegistryError::Unauthorized(node.node_id.clone()));
+                }
+                let start = registration.epoch.unwrap_or(epoch + 1);
+                if start <= epoch {
+                    return Err(RegistryError::EpochStarted { epoch: start, current: epoch });
+                }
+                if node.keys.is_empty() {
+                    node.keys.push(EpochKey {
+                        epoch: 0,
+                        public_key: node.public_key,
+                        signature_scheme: SignatureScheme::ProofOfPossession,
+                    });
+                }
+                // 替换尚未生效的同期或更晚的安排
+                node.keys.retain(|key| key.epoch < start);
+                node.keys.push(EpochKey { epoch: start, public_key: registration.public_key, signature_scheme });
+                node.public_key = registration.public_key;
+            }
+            node.address = registration.address.clone();
+            node.status = NodeStatus::Active;
+            node.nonce = registration.nonce;
+            node.updated_at = now;
+            return Ok(RegisterOutcome::Updated);
+        }
+
+        let start = registration.epoch.unwrap_or(epoch);
+        if start < epoch {
+            return Err(RegistryError::EpochStarted { epoch: start, current: epoch });
+        }
+        self.nodes.push(NodeRecord {
+            node_id: registration.node_id.clone(),
+            index: self.nodes.len(),
+            address: registration.address.clone(),
+            public_key: registration.public_key,
+            registered_at: now,
+            updated_at: now,
+            status: NodeStatus::Active,
+            keys: vec![EpochKey { epoch: start, public_key: registration.public_key, signature_scheme }],
+            nonce: registration.nonce,
+        });
+        Ok(RegisterOutcome::Created)
+    }
+
+    // 注销节点，需要节点当前纪元或已安排的公钥对注销消息的签名，序号规则与登记相同;
+    // 记录和 index 保留，用于验证该节点参与过的历史签名
+    pub fn deregister(
+        &mut self,
+        node_id: &str,
+        proof: &Signature,
+        nonce: u64,
+        now: u64,
+        epoch: u64,
+    ) -> Result<&NodeRecord, RegistryError> {
+        let node = self
+            .nodes
+            .iter_mut()
+            .find(|node| node.node_id == node_id)
+            .ok_or_else(|| RegistryError::UnknownNode(node_id.to_string()))?;
+        if !node.live_keys(epoch).iter().any(|key| verify_deregistration(key, node_id, nonce, proof)) {
+            return Err(RegistryError::InvalidProof);
+        }
+        if nonce <= node.nonce {
+            return Err(RegistryError::StaleNonce { nonce, last: node.nonce });
+        }
+        node.status = NodeStatus::Deregistered;
+        node.nonce = nonce;
+        node.updated_at = now;
+        Ok(node)
+    }
+}
+
+// 门限群记录: 群公钥由 DKG 产生，登记时必须附带门限个份额合成的持有证明
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupRecord {
+    pub group_id: String,
+    pub group: ThresholdGroup,
+    pub registered_at: u64,
+    pub updated_at: u64,
+    // 重分享或刷新份额的次数，每次更新份额信息加一
+    #[serde(default)]
+    pub generation: u64,
+    // 被重分享替换的份额公钥，不能在之后的代中再次登记
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired_shares: Vec<SharePublicKey>,
+}
+
+impl GroupRecord {
+    // 根据登记请求创建或更新记录; 同一群 ID 的群公钥不可更改，重分享后只更新份额信息。
+    // 持有证明绑定代数: 重复提交当前代是幂等的，更新必须登记下一代，且不能沿用当前或已作废的份额
+    pub fn apply(
+        existing: Option<&GroupRecord>,
+        registration: &GroupRegistration,
+        now: u64,
+    ) -> Result<(GroupRecord, RegisterOutcome), RegistryError> {
+        if !registration.verify() {
+            return Err(RegistryError::InvalidProof);
+        }
+        let generation = registration.generation;
+        match existing {
+            Some(record) if record.group.group_public_key.0 != registration.group.group_public_key.0 => {
+                Err(RegistryError::GroupKeyChanged(registration.group_id.clone()))
+            }
+            Some(record) if same_group(&record.group, &registration.group) => {
+                if generation != record.generation {
+                    return Err(RegistryError::StaleGeneration { generation, expected: record.generation });
+                }
+                Ok((record.clone(), RegisterOutcome::Unchanged))
+            }
+            Some(record) => {
+                if generation != record.generation + 1 {
+                    return Err(RegistryError::StaleGeneration { generation, expected: record.generation + 1 });
+                }
+                let reused = registration.group.shares.iter().any(|share| {
+                    record
+                        .group
+                        .shares
+                        .iter()
+                        .chain(&record.retired_shares)
+                        .any(|used| used.public_key.0 == share.public_key.0)
+                });
+                if reused {
+                    return Err(RegistryError::ShareRetired(registration.group_id.clone()));
+                }
+                let mut retired_shares = record.retired_shares.clone();
+                retired_shares.extend(record.group.shares.iter().cloned());
+                Ok((
+                    GroupRecord {
+                        group: registration.group.clone(),
+                        updated_at: now,
+                        generation,
+                        retired_shares,
+                        ..record.clone()
+                    },
+                    RegisterOutcome::Updated,
+                ))
+            }
+            None => {
+                if generation != 0 {
+                    return Err(RegistryError::StaleGeneration { generation, expected: 0 });
+                }
+                Ok((
+                    GroupRecord {
+                        group_id: registration.group_id.clone(),
+                        group: registration.group.clone(),
+                        registered_at: now,
+                        updated_at: now,
+                        generation: 0,
+                        retired_shares: Vec::new(),
+                    },
+                    RegisterOutcome::Created,
+                ))
+            }
+        }
+    }
+}
+
+fn same_group(a: &ThresholdGroup, b: &ThresholdGroup) -> bool {
+    a.threshold == b.threshold
+        && a.group_public_key.0 == b.group_public_key.0
+        && a.shares.len() == b.shares.len()
+        && a.shares
+            .iter()
+            .zip(&b.shares)
+            .all(|(a, b)| a.index == b.index && a.public_key.0 == b.public_key.0)
+}
@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bn254::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use crate::aggregator::AggregatedSignature;
+use crate::hash::HashScheme;
+use crate::quorum::Quorum;
+use crate::scheme::SignatureScheme;
+use crate::store::{PendingSync, Store};
+
+// 当前时间 (Unix 毫秒)
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// 签名请求的生命周期
+// accepted -> collecting -> complete / failed / expired
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestState {
+    Accepted,
+    Collecting,
+    Complete,
+    // 所有节点都已响应但未达到门限，或请求无法开始
+    Failed,
+    // 截止时间已到仍未达到门限
+    Expired,
+}
+
+impl RequestState {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, RequestState::Complete | RequestState::Failed | RequestState::Expired)
+    }
+}
+
+// 签名的合成方式
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SigningMode {
+    // 多签: 签名求和，按签名者位图聚合公钥验证
+    #[default]
+    Multisig,
+    // 门限签名: 任意 threshold 个份额签名插值合成，在固定的群公钥下验证
+    Threshold {
+        threshold: usize,
+        #[serde(with = "crate::keys::public_key_hex")]
+        group_public_key: PublicKey,
+    },
+    // 多消息聚合: 每个节点签自己的消息，签名求和，按 (公钥, 消息) 列表用多配对验证
+    MultiMessage,
+}
+
+// 单个节点对签名请求的处理结果; 带曲线点，不派生相等比较
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum NodeOutcome {
+    Pending,
+    // 保存节点签名，主节点重启后据此恢复聚合进度
+    Signed {
+        signer_index: usize,
+        #[serde(with = "crate::keys::signature_hex")]
+        signature: Signature,
+        at: u64,
+    },
+    // 节点返回了签名，但主节点校验未通过
+    Rejected { reason: String, at: u64 },
+    // 节点按签名策略拒绝签名
+    Refused { reason: String, at: u64 },
+    // 连接或协议错误
+    Failed { reason: String, at: u64 },
+    TimedOut { at: u64 },
+}
+
+impl NodeOutcome {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, NodeOutcome::Pending)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestRecord {
+    pub request_id: String,
+    pub state: RequestState,
+    pub message: String,
+    // 多消息请求中每个节点要签的消息，未列出的节点签 message
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub messages: BTreeMap<String, String>,
+    // 节点签名使用的哈希方式，验证时必须使用同一方式
+    #[serde(default)]
+    pub hash_scheme: HashScheme,
+    // 防御流氓公钥的签名方案，消息增强方案下按 (公钥, 增强后的消息) 验证
+    #[serde(default)]
+    pub signature_scheme: SignatureScheme,
+    #[serde(default)]
+    pub signing_mode: SigningMode,
+    // 创建请求时的密钥纪元，节点用该纪元的密钥签名，验证时使用该纪元的公钥集合;
+    // 旧记录没有纪元，按各节点最新登记的公钥验证
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<u64>,
+    pub required: usize,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub completed_at: Option<u64>,
+    pub nodes: BTreeMap<String, NodeOutcome>,
+    pub result: Option<AggregatedSignature>,
+    pub error: Option<String>,
+}
+
+impl RequestRecord {
+    pub fn new(request_id: &str, message: &str, hash_scheme: &HashScheme, nodes: &[String], required: usize) -> Self {
+        let now = now_millis();
+        RequestRecord {
+            request_id: request_id.to_string(),
+            state: RequestState::Accepted,
+            message: message.to_string(),
+            messages: BTreeMap::new(),
+            hash_scheme: hash_scheme.clone(),
+            signature_scheme: SignatureScheme::ProofOfPossession,
+            signing_mode: SigningMode::Multisig,
+            epoch: None,
+            required,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            nodes: nodes.iter().map(|node| (node.clone(), NodeOutcome::Pending)).collect(),
+            result: None,
+            error: None,
+        }
+    }
+
+    // 多消息请求: 只向列出的节点请求签名，各节点签自己的消息。门限按列出的节点数计算，
+    // 列出的节点不足门限时请求永远无法完成，直接拒绝
+    pub fn with_messages(
+        request_id: &str,
+        messages: BTreeMap<String, String>,
+        hash_scheme: &HashScheme,
+        quorum: &Quorum,
+    ) -> Result<Self, String> {
+        quorum.validate(messages.len())?;
+        let nodes: Vec<String> = messages.keys().cloned().collect();
+        let mut record = RequestRecord::new(request_id, "", hash_scheme, &nodes, quorum.required(nodes.len()));
+        record.messages = messages;
+        record.signing_mode = SigningMode::MultiMessage;
+        Ok(record)
+    }
+
+    // 节点要签的消息
+    pub fn node_message(&self, node: &str) -> &str {
+        self.messages.get(node).unwrap_or(&self.message)
+    }
+
+    // 已签名节点的 (signer_index, 消息)，按 signer_index 排序，与签名者位图选出的公钥顺序一致
+    pub fn signed_messages(&self) -> Vec<(usize, &str)> {
+        let mut signed: Vec<(usize, &str)> = self
+            .nodes
+            .iter()
+            .filter_map(|(node, outcome)| match outcome {
+                NodeOutcome::Signed { signer_index, .. } => Some((*signer_index, self.node_message(node))),
+                _ => None,
+            })
+            .collect();
+        signed.sort_by_key(|(signer_index, _)| *signer_index);
+        signed
+    }
+
+    pub fn set_state(&mut self, state: RequestState) {
+        self.state = state;
+        self.updated_at = now_millis();
+    }
+
+    pub fn record_node(&mut self, node: &str, outcome: NodeOutcome) {
+        self.nodes.insert(node.to_string(), outcome);
+        self.updated_at = now_millis();
+    }
+
+    // 记录 (或用迟到的签名更新) 聚合结果
+    pub fn complete(&mut self, aggregated: AggregatedSignature) {
+        if self.completed_at.is_none() {
+            self.completed_at = Some(now_millis());
+        }
+        self.result = Some(aggregated);
+        self.error = None;
+        self.set_state(RequestState::Complete);
+    }
+
+    pub fn fail(&mut self, state: RequestState, reason: String) {
+        self.error = Some(reason);
+        self.set_state(state);
+    }
+
+    pub fn signer_count(&self) -> usize {
+        self.nodes
+            .values()
+            .filter(|outcome| matches!(outcome, NodeOutcome::Signed { .. }))
+            .count()
+    }
+
+    // 已记录的节点签名 (signer_index, signature)
+    pub fn signatures(&self) -> Vec<(usize, Signature)> {
+        self.nodes
+            .values()
+            .filter_map(|outcome| match outcome {
+                NodeOutcome::Signed { signer_index, signature, .. } => Some((*signer_index, *signature)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // 尚未返回有效签名的节点
+    pub fn missing_nodes(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, NodeOutcome::Signed { .. }))
+            .map(|(node, _)| node.clone())
+            .collect()
+    }
+
+    pub fn shortfall(&self) -> usize {
+        self.required.saturating_sub(self.signer_count())
+    }
+
+    // 验证前检查结果位图中的签名者数量达到记录的门限; quorum 为验证者自己按委员会规模要求的门限
+    pub fn check_signers(&self, aggregated: &AggregatedSignature, quorum: Option<&Quorum>) -> Result<(), String> {
+        let mut required = self.required.max(1);
+        if let Some(quorum) = quorum {
+            required = required.max(quorum.required(self.nodes.len()));
+        }
+        let signers = aggregated.signers.count();
+        if signers < required {
+            return Err(format!("result has {} signers, {} required", signers, required));
+        }
+        Ok(())
+    }
+}
+
+// 主节点的签名请求表，每次修改后写入持久化存储; 重启后从存储恢复，继续尚未结束的请求
+pub struct RequestTable {
+    records: HashMap<String, RequestRecord>,
+    store: Box<dyn Store<RequestRecord>>,
+}
+
+impl RequestTable {
+    pub fn open(mut store: Box<dyn Store<RequestRecord>>) -> io::Result<Self> {
+        let records = store.load()?.into_iter().collect();
+        Ok(RequestTable { records, store })
+    }
+
+    pub fn get(&self, request_id: &str) -> Option<&RequestRecord> {
+        self.records.get(request_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // 写入存储后更新内存; 返回的 PendingSync 须在释放锁后等待
+    pub fn insert(&mut self, record: RequestRecord) -> io::Result<PendingSync> {
+        let pending = self.store.put_deferred(&record.request_id, &record)?;
+        self.records.insert(record.request_id.clone(), record);
+        Ok(pending)
+    }
+
+    pub fn update<F>(&mut self, request_id: &str, update: F) -> io::Result<PendingSync>
+    where
+        F: FnOnce(&mut RequestRecord),
+    {
+        match self.records.get_mut(request_id) {
+            Some(record) => {
+                update(record);
+                self.store.put_deferred(request_id, record)
+            }
+            None => Ok(PendingSync::done()),
+        }
+    }
+
+    // 重启前尚未结束的请求
+    pub fn unfinished(&self) -> Vec<String> {
+        let mut unfinished: Vec<String> = self
+            .records
+            .values()
+            .filter(|record| !record.state.is_terminal())
+            .map(|record| record.request_id.clone())
+            .collect();
+        unfinished.sort();
+        unfinished
+    }
+}
@@ -0,0 +1,358 @@
+// 份额重分享: 在不改变群公钥的前提下更换委员会、修改门限或定期刷新份额
+//
+// 旧委员会中持有份额 s_i 的节点 i 作为分发者，选取常数项为 s_i 的 t'-1 次多项式 g_i，
+// 公开承诺 C_ik = g_ik·g2 (C_i0 必须等于旧群信息中的份额公钥)，把 g_i(j) 发给新委员会的参与者 j。
+// 合格分发者集合 D 至少包含旧门限个分发者，参与者 j 的新份额为
+//   s'_j = Σ_{i∈D} λ_i · g_i(j)
+// 其中 λ_i 为 D 上在 0 点插值的拉格朗日系数，因此新份额仍在常数项为主私钥的多项式上，
+// 群公钥 Σ λ_i·C_i0 = Σ λ_i·s_i·g2 保持不变。新旧份额不能混合使用，刷新后旧份额作废
+//
+// 分发会把旧份额交给新委员会，旧委员会节点只按操作员写入 --dkg-dir 的重分享计划分发，
+// 计划须与协调者的请求完全一致，且只用于一次分发。会话结束时分发者用零覆盖并删除旧份额的密钥文件，
+// 否则任意门限个旧份额仍可恢复主私钥
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use bn254::PublicKey;
+use serde::{Deserialize, Serialize};
+use substrate_bn::Fr;
+use crate::dkg::{
+    combine_received, complaints_for, finalize_round, random_polynomial, resolve_complaints,
+    validate_session, weighted_group, DealerCommitments, DkgCoordinator, DkgMessage, DkgOutcome, DkgShare, Participant,
+    ReceivedDeal, RevealedShare, Weights,
+};
+use crate::keys::invalid_data;
+use crate::signer::Signer;
+use crate::threshold::{evaluate, fr_from_u32, lagrange_coefficient, ThresholdGroup};
+
+// 合格分发者 (旧份额序号) 的拉格朗日系数
+pub fn lagrange_weights(qualified: &BTreeSet<u32>) -> io::Result<Weights> {
+    let indices: Vec<u32> = qualified.iter().copied().collect();
+    indices
+        .iter()
+        .map(|&index| Ok((index, lagrange_coefficient(index, &indices)?)))
+        .collect()
+}
+
+// 操作员批准的重分享计划: 会话、旧群公钥、新门限以及新委员会
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResharePlan {
+    pub session: String,
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub group_public_key: PublicKey,
+    pub threshold: usize,
+    pub recipients: Vec<Participant>,
+}
+
+impl ResharePlan {
+    // 第 i 个新节点的份额序号为 i + 1
+    pub fn new(session: &str, group: &ThresholdGroup, threshold: usize, recipients: &[String]) -> Self {
+        ResharePlan {
+            session: session.to_string(),
+            group_public_key: group.group_public_key,
+            threshold,
+            recipients: recipients
+                .iter()
+                .enumerate()
+                .map(|(position, address)| Participant { index: position as u32 + 1, address: address.clone() })
+                .collect(),
+        }
+    }
+
+    // 计划保存在 DKG 目录下的 <session>.reshare.json
+    pub fn path(dir: &Path, session: &str) -> PathBuf {
+        dir.join(format!("{}.reshare.json", session))
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| invalid_data(format!("{} is not a reshare plan: {}", path.display(), e)))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        validate_session(&self.session)?;
+        validate_recipients(self.threshold, &self.recipients)?;
+        let contents = serde_json::to_string_pretty(self).map_err(invalid_data)?;
+        fs::write(path, contents)
+    }
+
+    // 协调者的请求与计划不一致时拒绝分发
+    pub fn check(&self, session: &str, group: &ThresholdGroup, threshold: usize, recipients: &[Participant]) -> io::Result<()> {
+        if self.session != session
+            || self.group_public_key.0 != group.group_public_key.0
+            || self.threshold != threshold
+            || self.recipients != recipients
+        {
+            return Err(invalid_data(format!("reshare request for session {} does not match the approved plan", session)));
+        }
+        Ok(())
+    }
+}
+
+// 旧委员会中的分发者，用自己的份额作为多项式常数项
+pub struct ReshareDealer {
+    pub index: u32,
+    // 旧份额的公钥，会话结束后据此找到并作废旧份额的密钥文件
+    pub share_public_key: PublicKey,
+    // 旧群的份额序号，即本会话中可能的分发者
+    dealers: Vec<u32>,
+    recipients: Vec<u32>,
+    polynomial: Vec<Fr>,
+    commitments: Vec<PublicKey>,
+}
+
+impl ReshareDealer {
+    // share 必须是旧群信息中的某个份额
+    pub fn new(share: &Signer, old_group: &ThresholdGroup, threshold: usize, recipients: &[Participant]) -> io::Result<Self> {
+        let index = old_group
+            .share_index(&share.public_key())
+            .ok_or_else(|| invalid_data("this node does not hold a share of the group"))?;
+        validate_recipients(threshold, recipients)?;
+        let (polynomial, commitments) = random_polynomial(share.private_key().0, threshold);
+        Ok(ReshareDealer {
+            index,
+            share_public_key: share.public_key(),
+            dealers: old_group.shares.iter().map(|share| share.index).collect(),
+            recipients: recipients.iter().map(|recipient| recipient.index).collect(),
+            polynomial,
+            commitments,
+        })
+    }
+
+    pub fn commitments(&self) -> &[PublicKey] {
+        &self.commitments
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.polynomial.len()
+    }
+
+    // index 是否为旧群中的份额序号
+    pub fn is_dealer(&self, index: u32) -> bool {
+        self.dealers.contains(&index)
+    }
+
+    pub fn share_for(&self, recipient: u32) -> io::Result<Fr> {
+        if !self.recipients.contains(&recipient) {
+            return Err(invalid_data(format!("{} is not a recipient", recipient)));
+        }
+        Ok(evaluate(&self.polynomial, fr_from_u32(recipient)?))
+    }
+}
+
+// 新门限至少为 2: 门限为 1 时每个新份额都等于主私钥
+fn validate_recipients(threshold: usize, recipients: &[Participant]) -> io::Result<()> {
+    if threshold < 2 || threshold > recipients.len() {
+        return Err(invalid_data(format!(
+            "threshold {} must be between 2 and the number of recipients ({})",
+            threshold,
+            recipients.len()
+        )));
+    }
+    let mut indices: Vec<u32> = recipients.iter().map(|recipient| recipient.index).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.len() != recipients.len() || indices.contains(&0) {
+        return Err(invalid_data("recipient indices must be distinct and non-zero"));
+    }
+    Ok(())
+}
+
+// 分发者的承诺是否与旧群信息一致: 次数符合新门限，常数项为该分发者的份额公钥
+pub fn valid_dealer(old_group: &ThresholdGroup, threshold: usize, dealer: &DealerCommitments) -> bool {
+    dealer.commitments.len() == threshold
+        && old_group
+            .share_public_key(dealer.dealer)
+            .is_some_and(|public_key| public_key.0 == dealer.commitments[0].0)
+}
+
+// 新委员会中的参与者
+pub struct ReshareRecipient {
+    pub index: u32,
+    pub threshold: usize,
+    pub recipients: Vec<u32>,
+    pub old_group: ThresholdGroup,
+    pub commitments: Vec<DealerCommitments>,
+}
+
+impl ReshareRecipient {
+    pub fn new(
+        index: u32,
+        threshold: usize,
+        recipients: &[Participant],
+        old_group: ThresholdGroup,
+        commitments: Vec<DealerCommitments>,
+    ) -> io::Result<Self> {
+        validate_recipients(threshold, recipients)?;
+        if !recipients.iter().any(|recipient| recipient.index == index) {
+            return Err(invalid_data(format!("{} is not a recipient", index)));
+        }
+        if let Some(dealer) = commitments.iter().find(|dealer| !valid_dealer(&old_group, threshold, dealer)) {
+            return Err(invalid_data(format!("commitments of dealer {} do not match the old group", dealer.dealer)));
+        }
+        let recipients = recipients.iter().map(|recipient| recipient.index).collect();
+        Ok(ReshareRecipient { index, threshold, recipients, old_group, commitments })
+    }
+
+    pub fn complaints(&self, received: &BTreeMap<u32, ReceivedDeal>) -> Vec<u32> {
+        complaints_for(self.index, &self.commitments, received)
+    }
+
+    // 按拉格朗日系数合并合格分发者的份额，并确认群公钥没有改变; generation 为新群记录的代数
+    pub fn finalize(
+        &self,
+        qualified: &BTreeSet<u32>,
+        received: &BTreeMap<u32, ReceivedDeal>,
+        revealed: &[RevealedShare],
+        group_id: &str,
+        generation: u64,
+    ) -> io::Result<DkgShare> {
+        if qualified.len() < self.old_group.threshold {
+            return Err(invalid_data(format!(
+                "{} qualified dealers, the old group needs {}",
+                qualified.len(),
+                self.old_group.threshold
+            )));
+        }
+        let weights = lagrange_weights(qualified)?;
+        let group = weighted_group(&self.commitments, &weights, self.threshold, &self.recipients)?;
+        if group.group_public_key.0 != self.old_group.group_public_key.0 {
+            return Err(invalid_data("resharing would change the group public key"));
+        }
+        let secret = combine_received(self.index, &self.commitments, &weights, received, revealed)?;
+        DkgShare::new(secret, &group, group_id, generation)
+    }
+}
+
+// 按计划协调一次重分享: dealers 为旧委员会节点 (不在线的节点跳过)，计划中的新委员会节点都必须在线;
+// 旧委员会节点须已写入同一份计划。generation 为新群记录的代数
+pub async fn run_reshare(
+    coordinator: &DkgCoordinator,
+    plan: &ResharePlan,
+    old_group: &ThresholdGroup,
+    dealers: &[String],
+    group_id: &str,
+    generation: u64,
+) -> io::Result<DkgOutcome> {
+    let session = plan.session.as_str();
+    let threshold = plan.threshold;
+    let participants = plan.recipients.clone();
+    validate_session(session)?;
+    validate_recipients(threshold, &participants)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    if plan.group_public_key.0 != old_group.group_public_key.0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the reshare plan is for another group"));
+    }
+
+    // 第 1 轮: 旧委员会的节点分发份额的份额
+    let mut commitments = Vec::new();
+    let mut dealer_addresses = BTreeMap::new();
+    for address in dealers {
+        let start = DkgMessage::ReshareStart {
+            session: session.to_string(),
+            group: old_group.clone(),
+            threshold,
+            recipients: participants.clone(),
+        };
+        match coordinator.request(address, start).await {
+            Ok(DkgMessage::ReshareDealt { dealer, commitments: dealer_commitments, .. }) => {
+                let dealer_commitments = DealerCommitments { dealer, commitments: dealer_commitments };
+                if dealer_addresses.contains_key(&dealer) || !valid_dealer(old_group, threshold, &dealer_commitments) {
+                    eprintln!("Reshare {}: ignoring invalid commitments from {} (share {})", session, address, dealer);
+                    continue;
+                }
+                println!("Reshare {}: {} deals as share {}", session, address, dealer);
+                dealer_addresses.insert(dealer, address.clone());
+                commitments.push(dealer_commitments);
+            }
+            Ok(reply) => eprintln!("Reshare {}: unexpected reply from {}: {:?}", session, address, reply),
+            Err(e) => eprintln!("Reshare {}: skipping dealer {}: {}", session, address, e),
+        }
+    }
+    if commitments.len() < old_group.threshold {
+        return Err(invalid_data(format!(
+            "only {} of the old committee dealt, the old group needs {}",
+            commitments.len(),
+            old_group.threshold
+        )));
+    }
+
+    // 第 2 轮: 新委员会核对收到的份额并投诉
+    let mut complaints = Vec::new();
+    for participant in &participants {
+        let verify = DkgMessage::ReshareVerify {
+            session: session.to_string(),
+            index: participant.index,
+            threshold,
+            recipients: participants.clone(),
+            group: old_group.clone(),
+            commitments: commitments.clone(),
+        };
+        match coordinator.request(&participant.address, verify).await? {
+            DkgMessage::Complaints { against, .. } => {
+                for dealer in against {
+                    println!("Reshare {}: participant {} complains about dealer {}", session, participant.index, dealer);
+                    complaints.push((participant.index, dealer));
+                }
+            }
+            reply => return Err(invalid_data(format!("unexpected reply from {}: {:?}", participant.address, reply))),
+        }
+    }
+
+    // 第 3 轮: 处理投诉，合格分发者仍需达到旧门限
+    let mut qualified: BTreeSet<u32> = dealer_addresses.keys().copied().collect();
+    let mut revealed =
+        resolve_complaints(coordinator, session, &complaints, &commitments, &dealer_addresses, &mut qualified).await;
+    if qualified.len() < old_group.threshold {
+        return Err(invalid_data(format!(
+            "only {} qualified dealers remain, the old group needs {}",
+            qualified.len(),
+            old_group.threshold
+        )));
+    }
+
+    // 第 4 轮: 新委员会计算新份额，群公钥不变
+    let qualified_list: Vec<u32> = qualified.iter().copied().collect();
+    let indices: Vec<u32> = participants.iter().map(|participant| participant.index).collect();
+    let group = weighted_group(&commitments, &lagrange_weights(&qualified)?, threshold, &indices)?;
+    if group.group_public_key.0 != old_group.group_public_key.0 {
+        return Err(invalid_data("resharing would change the group public key"));
+    }
+    let registration = finalize_round(
+        coordinator,
+        &participants,
+        group,
+        group_id,
+        generation,
+        |participant| DkgMessage::ReshareFinalize {
+            session: session.to_string(),
+            qualified: qualified_list.clone(),
+            revealed: revealed.remove(&participant.index).unwrap_or_default(),
+            group_id: group_id.to_string(),
+            generation,
+        },
+    )
+    .await?;
+
+    // 通知不在新委员会中的分发者结束会话
+    for (dealer, address) in &dealer_addresses {
+        if participants.iter().any(|participant| participant.address == *address) {
+            continue;
+        }
+        let close = DkgMessage::ReshareFinalize {
+            session: session.to_string(),
+            qualified: qualified_list.clone(),
+            revealed: Vec::new(),
+            group_id: group_id.to_string(),
+            generation,
+        };
+        if let Err(e) = coordinator.request(address, close).await {
+            eprintln!("Reshare {}: failed to close session on dealer {} ({}): {}", session, dealer, address, e);
+        }
+    }
+
+    Ok(DkgOutcome { group: registration.group.clone(), qualified: qualified_list, registration })
+}
+
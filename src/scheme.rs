@@ -0,0 +1,66 @@
+// 防御流氓公钥 (rogue key) 的签名方案
+//
+// proof_of_possession: 节点直接对消息签名，公钥登记时必须附带持有证明 (见 pop.rs)，
+//                      同一消息的签名可以用聚合公钥一次验证
+// message_augmentation: 节点对 "域标签 || 压缩公钥 || 消息" 签名，每个签名者签的内容都不同，
+//                       不依赖持有证明登记，但验证时需要对每个签名者做一次配对 (多配对检查)
+//
+// 公钥收集服务以 message_augmentation 方式运行时接受没有持有证明的公钥，登记表按公钥记录其登记方式，
+// 这样的公钥只能用于消息增强签名
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use bn254::PublicKey;
+use serde::{Deserialize, Serialize};
+use crate::keys::invalid_data;
+
+pub const AUGMENTATION_DOMAIN: &[u8] = b"DSS_BN254_AUG_V1:";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    #[default]
+    ProofOfPossession,
+    MessageAugmentation,
+}
+
+impl SignatureScheme {
+    // 公钥为 public_key 的节点对 message 实际签名的内容
+    pub fn signing_message(&self, message: &[u8], public_key: &PublicKey) -> io::Result<Vec<u8>> {
+        match self {
+            SignatureScheme::ProofOfPossession => Ok(message.to_vec()),
+            SignatureScheme::MessageAugmentation => {
+                let mut augmented = AUGMENTATION_DOMAIN.to_vec();
+                augmented.extend_from_slice(&public_key.to_compressed().map_err(invalid_data)?);
+                augmented.extend_from_slice(message);
+                Ok(augmented)
+            }
+        }
+    }
+
+    // 按本方案验证的签名能否使用以 registered 方式登记的公钥: 没有持有证明的公钥只能用于消息增强方案
+    pub fn accepts(&self, registered: SignatureScheme) -> bool {
+        *self == SignatureScheme::MessageAugmentation || registered == SignatureScheme::ProofOfPossession
+    }
+}
+
+impl FromStr for SignatureScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "proof_of_possession" | "pop" => Ok(SignatureScheme::ProofOfPossession),
+            "message_augmentation" | "aug" => Ok(SignatureScheme::MessageAugmentation),
+            _ => Err(format!("unknown signature scheme {:?}, expected proof_of_possession or message_augmentation", s)),
+        }
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::ProofOfPossession => write!(f, "proof_of_possession"),
+            SignatureScheme::MessageAugmentation => write!(f, "message_augmentation"),
+        }
+    }
+}
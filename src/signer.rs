@@ -0,0 +1,48 @@
+use std::io;
+use rand_core::OsRng;
+use bn254::{PrivateKey, PublicKey, Signature, ECDSA};
+use crate::hash::HashScheme;
+use crate::keys::invalid_data;
+
+// 持有节点的BLS私钥并对消息签名
+pub struct Signer {
+    private_key: PrivateKey,
+    public_key: PublicKey,
+}
+
+impl Signer {
+    pub fn new(private_key: PrivateKey) -> Self {
+        let public_key = PublicKey::from_private_key(&private_key);
+        Signer { private_key, public_key }
+    }
+
+    pub fn random() -> Self {
+        Signer::new(PrivateKey::random(&mut OsRng))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let private_key = PrivateKey::try_from(bytes).map_err(invalid_data)?;
+        Ok(Signer::new(private_key))
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        self.private_key.to_bytes().map_err(invalid_data)
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    pub fn sign(&self, message: &[u8]) -> io::Result<Signature> {
+        ECDSA::sign(message, &self.private_key).map_err(invalid_data)
+    }
+
+    // 按指定的哈希方式签名: H(m) * sk
+    pub fn sign_with(&self, scheme: &HashScheme, message: &[u8]) -> io::Result<Signature> {
+        Ok(Signature(scheme.hash_to_g1(message)? * self.private_key.0))
+    }
+}
@@ -0,0 +1,323 @@
+// 键值记录的持久化存储
+//
+// FileStore 为追加写的 JSON 行日志，每行是一次写入或删除:
+//   {"op":"put","key":"...","value":{...}}
+//   {"op":"delete","key":"..."}
+// 加载时按顺序回放，同一个键以最后一行为准。日志中的过期行超过存活记录数
+// 且超过 COMPACT_MIN_GARBAGE 行时，把存活记录写入临时文件并原子替换原日志。
+//
+// put / delete 写入后立即刷盘; 异步调用方持锁时应改用 put_deferred，
+// 在释放锁之后 (如 spawn_blocking 中) 等待返回的 PendingSync，避免刷盘阻塞锁和执行器线程。
+// 需要压缩时压缩同样随 PendingSync 推迟: 先在锁外按快照重写日志，
+// 替换文件前才短暂持有日志锁，把快照之后追加的行接到新文件末尾
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::keys::invalid_data;
+
+const COMPACT_MIN_GARBAGE: usize = 1024;
+
+// 已写入但尚未刷盘的记录，以及可能随之推迟的日志压缩; wait 返回后记录才持久化
+#[must_use = "the record is not durable until the pending sync is waited on"]
+pub struct PendingSync {
+    file: Option<Arc<File>>,
+    compaction: Option<Compaction>,
+}
+
+impl PendingSync {
+    // 无需刷盘 (内存存储或已同步的写入)
+    pub fn done() -> Self {
+        PendingSync { file: None, compaction: None }
+    }
+
+    // 阻塞直到文件数据刷盘，再执行推迟的压缩，应在锁外调用
+    pub fn wait(self) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            file.sync_data()?;
+        }
+        if let Some(compaction) = self.compaction {
+            // 记录已经持久化，压缩失败只留下较长的日志，之后的写入会再次尝试
+            if let Err(e) = compaction.run() {
+                eprintln!("Failed to compact store log: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait Store<T>: Send {
+    // 所有存活记录，按键排序
+    fn load(&mut self) -> io::Result<Vec<(String, T)>>;
+    fn put(&mut self, key: &str, value: &T) -> io::Result<()>;
+    fn delete(&mut self, key: &str) -> io::Result<()>;
+
+    // 写入但不刷盘; 默认实现即同步写入
+    fn put_deferred(&mut self, key: &str, value: &T) -> io::Result<PendingSync> {
+        self.put(key, value)?;
+        Ok(PendingSync::done())
+    }
+}
+
+// 内存存储，进程退出即丢失，用于测试和无需持久化的部署
+pub struct MemoryStore<T> {
+    records: BTreeMap<String, T>,
+}
+
+impl<T> MemoryStore<T> {
+    pub fn new() -> Self {
+        MemoryStore { records: BTreeMap::new() }
+    }
+}
+
+impl<T> Default for MemoryStore<T> {
+    fn default() -> Self {
+        MemoryStore::new()
+    }
+}
+
+impl<T: Clone + Send> Store<T> for MemoryStore<T> {
+    fn load(&mut self) -> io::Result<Vec<(String, T)>> {
+        Ok(self.records.iter().map(|(key, value)| (key.clone(), value.clone())).collect())
+    }
+
+    fn put(&mut self, key: &str, value: &T) -> io::Result<()> {
+        self.records.insert(key.to_string(), value.clone());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<()> {
+        self.records.remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogEntry<T> {
+    Put { key: String, value: T },
+    Delete { key: String },
+}
+
+// 日志文件的追加句柄和行数; 追加和替换文件互斥
+struct Log {
+    // 与 PendingSync 共享，压缩替换文件后旧句柄上的刷盘无害
+    file: Arc<File>,
+    lines: usize,
+    // 已有推迟的压缩尚未执行
+    compacting: bool,
+}
+
+// 追加写日志文件存储
+pub struct FileStore<T> {
+    path: PathBuf,
+    log: Arc<Mutex<Log>>,
+    // 存活的键，用于判断是否需要压缩
+    keys: BTreeSet<String>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> FileStore<T> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut store = FileStore {
+            path: path.to_path_buf(),
+            log: Arc::new(Mutex::new(Log { file: Arc::new(file), lines: 0, compacting: false })),
+            keys: BTreeSet::new(),
+            _marker: PhantomData,
+        };
+        // 回放一遍以统计行数，并清理崩溃时写了一半的末行; 打开时没有并发写入，直接压缩
+        store.replay()?;
+        if let Some(compaction) = store.schedule_compaction() {
+            compaction.run()?;
+        }
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn replay(&mut self) -> io::Result<BTreeMap<String, T>> {
+        let mut log = self.log.lock().unwrap();
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut records = BTreeMap::new();
+        let mut lines = 0;
+        let mut valid_len = 0u64;
+        let mut torn_tail = false;
+
+        for line in reader.split(b'\n') {
+            let line = line?;
+            if torn_tail {
+                // 损坏的行之后还有内容，说明不是末行写了一半，而是文件损坏
+                return Err(invalid_data(format!("corrupted store log {}", self.path.display())));
+            }
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                valid_len += line.len() as u64 + 1;
+                continue;
+            }
+            match serde_json::from_slice::<LogEntry<T>>(&line) {
+                Ok(LogEntry::Put { key, value }) => {
+                    records.insert(key, value);
+                }
+                Ok(LogEntry::Delete { key }) => {
+                    records.remove(&key);
+                }
+                Err(_) => {
+                    torn_tail = true;
+                    continue;
+                }
+            }
+            lines += 1;
+            valid_len += line.len() as u64 + 1;
+        }
+
+        if torn_tail {
+            eprintln!("Discarding incomplete last entry of {}", self.path.display());
+            let file = OpenOptions::new().write(true).open(&self.path)?;
+            file.set_len(valid_len)?;
+            file.sync_all()?;
+        } else if valid_len > fs::metadata(&self.path)?.len() {
+            // 末行完整但缺少换行符，补上以免下一次追加与其拼成一行
+            let mut file = OpenOptions::new().append(true).open(&self.path)?;
+            file.write_all(b"\n")?;
+            file.sync_all()?;
+        }
+        log.lines = lines;
+        self.keys = records.keys().cloned().collect();
+        Ok(records)
+    }
+
+    // 追加一行，返回尚未等待的刷盘
+    fn append<V: Serialize>(&mut self, entry: &LogEntry<V>) -> io::Result<PendingSync> {
+        let mut line = serde_json::to_vec(entry).map_err(invalid_data)?;
+        line.push(b'\n');
+        let mut log = self.log.lock().unwrap();
+        (&*log.file).write_all(&line)?;
+        log.lines += 1;
+        Ok(PendingSync { file: Some(log.file.clone()), compaction: None })
+    }
+
+    // 过期行超过阈值且多于存活记录时安排一次压缩，同一时间最多一次
+    fn schedule_compaction(&self) -> Option<Compaction> {
+        let mut log = self.log.lock().unwrap();
+        let garbage = log.lines.saturating_sub(self.keys.len());
+        if log.compacting || garbage < COMPACT_MIN_GARBAGE || garbage < self.keys.len() {
+            return None;
+        }
+        log.compacting = true;
+        Some(Compaction { path: self.path.clone(), log: self.log.clone() })
+    }
+}
+
+// 推迟到锁外执行的日志压缩，丢弃而未执行时允许之后重新安排
+struct Compaction {
+    path: PathBuf,
+    log: Arc<Mutex<Log>>,
+}
+
+impl Compaction {
+    // 只保留存活记录，写入临时文件后原子替换。记录的值按 JSON 原样保留，不需要记录类型
+    fn run(self) -> io::Result<()> {
+        // 追加在日志锁内写完整行，此时的长度落在行边界上
+        let snapshot_len = {
+            let _log = self.log.lock().unwrap();
+            fs::metadata(&self.path)?.len()
+        };
+        let mut records = BTreeMap::new();
+        let reader = BufReader::new(File::open(&self.path)?.take(snapshot_len));
+        for line in reader.split(b'\n') {
+            let line = line?;
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            match serde_json::from_slice::<LogEntry<serde_json::Value>>(&line).map_err(invalid_data)? {
+                LogEntry::Put { key, value } => {
+                    records.insert(key, value);
+                }
+                LogEntry::Delete { key } => {
+                    records.remove(&key);
+                }
+            }
+        }
+
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = File::create(&tmp_path)?;
+        for (key, value) in records.iter() {
+            let entry = LogEntry::Put { key: key.clone(), value };
+            let mut line = serde_json::to_vec(&entry).map_err(invalid_data)?;
+            line.push(b'\n');
+            tmp.write_all(&line)?;
+        }
+        tmp.sync_data()?;
+
+        // 快照之后追加的行原样接在后面，替换期间暂停追加
+        let mut log = self.log.lock().unwrap();
+        let mut tail = Vec::new();
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(snapshot_len))?;
+        file.read_to_end(&mut tail)?;
+        tmp.write_all(&tail)?;
+        tmp.sync_all()?;
+        drop(tmp);
+        fs::rename(&tmp_path, &self.path)?;
+        sync_parent(&self.path)?;
+        log.file = Arc::new(OpenOptions::new().append(true).open(&self.path)?);
+        log.lines = records.len() + tail.iter().filter(|b| **b == b'\n').count();
+        Ok(())
+    }
+}
+
+impl Drop for Compaction {
+    fn drop(&mut self) {
+        if let Ok(mut log) = self.log.lock() {
+            log.compacting = false;
+        }
+    }
+}
+
+// 目录项落盘后替换才在断电后保留
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl<T: Serialize + DeserializeOwned> Store<T> for FileStore<T> {
+    fn load(&mut self) -> io::Result<Vec<(String, T)>> {
+        Ok(self.replay()?.into_iter().collect())
+    }
+
+    fn put(&mut self, key: &str, value: &T) -> io::Result<()> {
+        self.put_deferred(key, value)?.wait()
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<()> {
+        self.append(&LogEntry::<&T>::Delete { key: key.to_string() })?.wait()?;
+        self.keys.remove(key);
+        Ok(())
+    }
+
+    fn put_deferred(&mut self, key: &str, value: &T) -> io::Result<PendingSync> {
+        let mut pending = self.append(&LogEntry::Put { key: key.to_string(), value })?;
+        self.keys.insert(key.to_string());
+        pending.compaction = self.schedule_compaction();
+        Ok(pending)
+    }
+}
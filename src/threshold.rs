@@ -0,0 +1,243 @@
+// 基于可信分发者的门限 BLS
+//
+// 分发者把主私钥 s 作为 t-1 次多项式 f 的常数项，第 i 个节点 (i 从 1 开始) 的私钥份额为 f(i)，
+// 份额公钥为 f(i)·g2。任意 t 个节点用份额签名后，主节点按拉格朗日系数在 0 点插值:
+//   sig = Σ λ_i · sig_i = f(0)·H(m) = s·H(m)
+// 得到的签名在固定的群公钥 s·g2 下验证，验证者不需要知道签名者集合
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use bn254::{PrivateKey, PublicKey, Signature};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use substrate_bn::{Fr, Group, G1, G2};
+use crate::keys::invalid_data;
+use crate::signer::Signer;
+
+// 群公钥以及每个份额的公钥，不含秘密，可以分发给主节点和验证者
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThresholdGroup {
+    pub threshold: usize,
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub group_public_key: PublicKey,
+    pub shares: Vec<SharePublicKey>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SharePublicKey {
+    pub index: u32,
+    #[serde(with = "crate::keys::public_key_hex")]
+    pub public_key: PublicKey,
+}
+
+impl ThresholdGroup {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let group: ThresholdGroup = serde_json::from_str(&contents).map_err(|e| {
+            invalid_data(format!("{} is not a threshold group file: {}", path.display(), e))
+        })?;
+        group.validate()?;
+        Ok(group)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self).map_err(invalid_data)?;
+        fs::write(path, contents)
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        if self.threshold == 0 || self.threshold > self.shares.len() {
+            return Err(invalid_data(format!(
+                "threshold {} must be between 1 and the number of shares ({})",
+                self.threshold,
+                self.shares.len()
+            )));
+        }
+        let mut seen = Vec::new();
+        for share in &self.shares {
+            if share.index == 0 || seen.contains(&share.index) {
+                return Err(invalid_data(format!("invalid or duplicate share index {}", share.index)));
+            }
+            seen.push(share.index);
+        }
+
+        // 份额公钥必须在同一个 t-1 次多项式上且常数项为群公钥: 用前 threshold 个份额在指数上插值，
+        // 核对 0 点的群公钥和其余各份额的公钥
+        let basis = &self.shares[..self.threshold];
+        let indices: Vec<u32> = basis.iter().map(|share| share.index).collect();
+        let interpolate = |x: u32| -> io::Result<G2> {
+            basis.iter().try_fold(G2::zero(), |acc, share| {
+                Ok(acc + share.public_key.0 * lagrange_coefficient_at(share.index, &indices, x)?)
+            })
+        };
+        if interpolate(0)? != self.group_public_key.0 {
+            return Err(invalid_data("share public keys do not interpolate to the group public key"));
+        }
+        for share in &self.shares[self.threshold..] {
+            if interpolate(share.index)? != share.public_key.0 {
+                return Err(invalid_data(format!("share {} is not consistent with the other shares", share.index)));
+            }
+        }
+        Ok(())
+    }
+
+    // 按份额公钥查找份额序号
+    pub fn share_index(&self, public_key: &PublicKey) -> Option<u32> {
+        self.shares
+            .iter()
+            .find(|share| share.public_key.0 == public_key.0)
+            .map(|share| share.index)
+    }
+
+    pub fn share_public_key(&self, index: u32) -> Option<PublicKey> {
+        self.shares.iter().find(|share| share.index == index).map(|share| share.public_key)
+    }
+}
+
+// 分发结果: 群信息以及每个节点的私钥份额
+pub struct Dealing {
+    pub group: ThresholdGroup,
+    pub shares: Vec<(u32, Signer)>,
+}
+
+// 把主私钥拆分为 nodes 个份额，任意 threshold 个份额即可签名
+pub fn deal(master: &PrivateKey, threshold: usize, nodes: usize) -> io::Result<Dealing> {
+    if threshold == 0 || threshold > nodes {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("threshold {} must be between 1 and the number of nodes ({})", threshold, nodes),
+        ));
+    }
+    if nodes > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many nodes"));
+    }
+
+    let mut coefficients = vec![master.0];
+    coefficients.extend((1..threshold).map(|_| Fr::random(&mut OsRng)));
+
+    let mut shares = Vec::with_capacity(nodes);
+    let mut share_public_keys = Vec::with_capacity(nodes);
+    for index in 1..=nodes as u32 {
+        let signer = Signer::new(PrivateKey(evaluate(&coefficients, fr_from_u32(index)?)));
+        share_public_keys.push(SharePublicKey { index, public_key: signer.public_key() });
+        shares.push((index, signer));
+    }
+
+    Ok(Dealing {
+        group: ThresholdGroup {
+            threshold,
+            group_public_key: PublicKey::from_private_key(master),
+            shares: share_public_keys,
+        },
+        shares,
+    })
+}
+
+pub(crate) fn fr_from_u32(value: u32) -> io::Result<Fr> {
+    Fr::from_str(&value.to_string()).ok_or_else(|| invalid_data(format!("invalid scalar {}", value)))
+}
+
+// Horner 法求多项式在 x 处的值
+pub(crate) fn evaluate(coefficients: &[Fr], x: Fr) -> Fr {
+    coefficients.iter().rev().fold(Fr::zero(), |acc, coefficient| acc * x + *coefficient)
+}
+
+// 份额序号集合 indices 中 index 对应的、在 0 点插值的拉格朗日系数: Π j / (j - i)
+pub fn lagrange_coefficient(index: u32, indices: &[u32]) -> io::Result<Fr> {
+    lagrange_coefficient_at(index, indices, 0)
+}
+
+// 在 x 点插值的拉格朗日系数: Π (j - x) / (j - i)
+pub fn lagrange_coefficient_at(index: u32, indices: &[u32], x: u32) -> io::Result<Fr> {
+    let x_i = fr_from_u32(index)?;
+    let x = fr_from_u32(x)?;
+    let mut numerator = Fr::one();
+    let mut denominator = Fr::one();
+    for &other in indices {
+        if other == index {
+            continue;
+        }
+        let x_j = fr_from_u32(other)?;
+        numerator = numerator * (x_j - x);
+        denominator = denominator * (x_j - x_i);
+    }
+    let inverse = denominator
+        .inverse()
+        .ok_or_else(|| invalid_data("share indices must be distinct"))?;
+    Ok(numerator * inverse)
+}
+
+// 用拉格朗日插值把部分签名合成为群公钥下的签名
+pub fn combine(partials: &[(u32, Signature)]) -> io::Result<Signature> {
+    let indices: Vec<u32> = partials.iter().map(|(index, _)| *index).collect();
+    for (position, index) in indices.iter().enumerate() {
+        if *index == 0 || indices[..position].contains(index) {
+            return Err(invalid_data(format!("invalid or duplicate share index {}", index)));
+        }
+    }
+    let mut combined = G1::zero();
+    for (index, partial) in partials {
+        combined = combined + partial.0 * lagrange_coefficient(*index, &indices)?;
+    }
+    Ok(Signature(combined))
+}
+
+// 收集各份额的部分签名，达到门限后合成
+#[derive(Clone, Debug)]
+pub struct ThresholdAggregator {
+    threshold: usize,
+    partials: BTreeMap<u32, Signature>,
+}
+
+impl ThresholdAggregator {
+    pub fn new(threshold: usize) -> Self {
+        ThresholdAggregator { threshold, partials: BTreeMap::new() }
+    }
+
+    // 同一份额重复提交时返回 false
+    pub fn add(&mut self, index: u32, partial: Signature) -> bool {
+        if self.partials.contains_key(&index) {
+            return false;
+        }
+        self.partials.insert(index, partial);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.partials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partials.is_empty()
+    }
+
+    pub fn indices(&self) -> Vec<u32> {
+        self.partials.keys().copied().collect()
+    }
+
+    // 合成时实际使用的份额序号: 序号最小的 threshold 个，不足门限时为空
+    pub fn used_indices(&self) -> Vec<u32> {
+        if self.partials.len() < self.threshold {
+            return Vec::new();
+        }
+        self.partials.keys().take(self.threshold).copied().collect()
+    }
+
+    // 任意 threshold 个部分签名合成的结果都相同，这里取 used_indices 中的份额
+    pub fn combine(&self) -> io::Result<Signature> {
+        if self.partials.len() < self.threshold {
+            return Err(invalid_data(format!(
+                "{} of {} partial signatures collected",
+                self.partials.len(),
+                self.threshold
+            )));
+        }
+        let partials: Vec<(u32, Signature)> = self
+            .used_indices()
+            .into_iter()
+            .map(|index| (index, self.partials[&index]))
+            .collect();
+        combine(&partials)
+    }
+}
@@ -0,0 +1,344 @@
+// 主节点与签名节点之间的双向 TLS (mTLS)
+//
+// 双方的证书都必须由本地 CA 签发 (keytool ca-init / ca-issue)，在此之上按对端固定证书:
+// 主节点按节点地址固定节点证书的 SHA-256 指纹，节点可以只接受指纹在允许列表中的主节点证书。
+// 未配置指纹的对端只校验证书链
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
+};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, DigitallySignedStruct, DistinguishedName, RootCertStore, ServerConfig};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use crate::keys::invalid_data;
+use crate::keystore::write_private;
+
+pub const CA_CERT_FILE: &str = "ca.pem";
+pub const CA_KEY_FILE: &str = "ca-key.pem";
+const CA_COMMON_NAME: &str = "DSS local CA";
+
+// DER 证书的 SHA-256
+pub type Fingerprint = [u8; 32];
+
+pub fn fingerprint(cert: &CertificateDer<'_>) -> Fingerprint {
+    Sha256::digest(cert.as_ref()).into()
+}
+
+// 十六进制指纹，允许用冒号分隔
+pub fn parse_fingerprint(value: &str) -> io::Result<Fingerprint> {
+    let bytes = hex::decode(value.replace(':', "")).map_err(invalid_data)?;
+    bytes
+        .try_into()
+        .map_err(|_| invalid_data("certificate fingerprint must be 32 bytes (SHA-256)"))
+}
+
+// <节点地址>=<指纹>
+pub fn parse_peer_pin(value: &str) -> Result<(String, Fingerprint), String> {
+    match value.split_once('=') {
+        Some((peer, pin)) if !peer.is_empty() => {
+            Ok((peer.to_string(), parse_fingerprint(pin).map_err(|e| e.to_string())?))
+        }
+        _ => Err(format!("expected <node address>=<sha256 fingerprint>, got {}", value)),
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn tls_error(e: impl std::fmt::Display) -> io::Error {
+    invalid_data(format!("TLS configuration error: {}", e))
+}
+
+pub fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let pem = fs::read(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice()).collect::<io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(invalid_data(format!("no certificate in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+pub fn load_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let pem = fs::read(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())?
+        .ok_or_else(|| invalid_data(format!("no private key in {}", path.display())))
+}
+
+// 本机的 TLS 身份: 可信 CA、证书链和私钥
+pub struct TlsIdentity {
+    roots: Arc<RootCertStore>,
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    pub fn load(ca: &Path, cert: &Path, key: &Path) -> io::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for ca_cert in load_certs(ca)? {
+            roots.add(ca_cert).map_err(tls_error)?;
+        }
+        Ok(TlsIdentity { roots: Arc::new(roots), chain: load_certs(cert)?, key: load_key(key)? })
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        fingerprint(&self.chain[0])
+    }
+}
+
+// 主节点一侧: 按节点地址固定证书指纹，同时出示自己的证书
+pub struct TlsClient {
+    identity: TlsIdentity,
+    verifier: Arc<WebPkiServerVerifier>,
+    pins: HashMap<String, Fingerprint>,
+}
+
+impl TlsClient {
+    pub fn new(identity: TlsIdentity, pins: HashMap<String, Fingerprint>) -> io::Result<Self> {
+        let verifier = WebPkiServerVerifier::builder_with_provider(identity.roots.clone(), provider())
+            .build()
+            .map_err(tls_error)?;
+        Ok(TlsClient { identity, verifier, pins })
+    }
+
+    // 证书须包含地址中的主机名或 IP
+    pub async fn connect(&self, addr: &str) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let verifier = PinnedServerVerifier {
+            inner: self.verifier.clone(),
+            pin: self.pins.get(addr).copied(),
+        };
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_client_auth_cert(self.identity.chain.clone(), self.identity.key.clone_key())
+            .map_err(tls_error)?;
+        let server_name = ServerName::try_from(host(addr).to_string())
+            .map_err(|e| invalid_data(format!("invalid node address {}: {}", addr, e)))?;
+        let stream = TcpStream::connect(addr).await?;
+        TlsConnector::from(Arc::new(config)).connect(server_name, stream).await
+    }
+}
+
+// 去掉端口，IPv6 地址去掉方括号
+fn host(addr: &str) -> &str {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+// 节点一侧: 要求对端出示本地 CA 签发的证书; allowed 非空时只接受其中的证书指纹
+pub struct TlsServer {
+    acceptor: TlsAcceptor,
+}
+
+impl TlsServer {
+    pub fn new(identity: TlsIdentity, allowed: Vec<Fingerprint>) -> io::Result<Self> {
+        let inner = WebPkiClientVerifier::builder_with_provider(identity.roots.clone(), provider())
+            .build()
+            .map_err(tls_error)?;
+        let verifier = PinnedClientVerifier { inner, allowed };
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .with_client_cert_verifier(Arc::new(verifier))
+            .with_single_cert(identity.chain, identity.key)
+            .map_err(tls_error)?;
+        Ok(TlsServer { acceptor: TlsAcceptor::from(Arc::new(config)) })
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<tokio_rustls::server::TlsStream<TcpStream>> {
+        self.acceptor.accept(stream).await
+    }
+}
+
+// 节点通道的传输方式
+#[derive(Clone, Default)]
+pub enum Transport {
+    #[default]
+    Plain,
+    Tls(Arc<TlsClient>),
+}
+
+pub trait NodeIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> NodeIo for T {}
+
+impl Transport {
+    pub async fn connect(&self, addr: &str) -> io::Result<Box<dyn NodeIo>> {
+        match self {
+            Transport::Plain => Ok(Box::new(TcpStream::connect(addr).await?)),
+            Transport::Tls(client) => Ok(Box::new(client.connect(addr).await?)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PinnedServerVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pin: Option<Fingerprint>,
+}
+
+impl ServerCertVerifier for PinnedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        match self.pin {
+            Some(pin) if pin != fingerprint(end_entity) => Err(rustls::Error::General(format!(
+                "certificate of {:?} does not match the pinned fingerprint",
+                server_name
+            ))),
+            _ => Ok(verified),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[derive(Debug)]
+struct PinnedClientVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    allowed: Vec<Fingerprint>,
+}
+
+impl ClientCertVerifier for PinnedClientVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        self.inner.root_hint_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self.inner.verify_client_cert(end_entity, intermediates, now)?;
+        if !self.allowed.is_empty() && !self.allowed.contains(&fingerprint(end_entity)) {
+            return Err(rustls::Error::General("client certificate is not in the allowed list".to_string()));
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// 本地 CA 的证书参数; 签发时按同样的参数和 CA 私钥重建签发者
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+    params
+}
+
+fn cert_error(e: rcgen::Error) -> io::Error {
+    invalid_data(format!("certificate error: {}", e))
+}
+
+// 在 dir 下生成本地 CA 的证书和私钥，已存在时报错; 返回 CA 证书路径
+pub fn create_ca(dir: &Path) -> io::Result<PathBuf> {
+    let cert_path = dir.join(CA_CERT_FILE);
+    let key_path = dir.join(CA_KEY_FILE);
+    if cert_path.exists() || key_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a CA already exists in {}", dir.display()),
+        ));
+    }
+    fs::create_dir_all(dir)?;
+    let key = KeyPair::generate().map_err(cert_error)?;
+    let cert = ca_params().self_signed(&key).map_err(cert_error)?;
+    write_private(&key_path, key.serialize_pem().as_bytes())?;
+    fs::write(&cert_path, cert.pem())?;
+    Ok(cert_path)
+}
+
+// 签发的节点证书
+pub struct IssuedCertificate {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub fingerprint: Fingerprint,
+}
+
+// 用 ca_dir 中的 CA 为 name 签发同时可用于服务端和客户端的证书，写出 <out_dir>/<name>.pem 和 <name>-key.pem;
+// names 为证书中的主机名或 IP，节点证书须包含其节点服务地址的主机部分
+pub fn issue_certificate(ca_dir: &Path, name: &str, names: &[String], out_dir: &Path) -> io::Result<IssuedCertificate> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid certificate name {:?}", name)));
+    }
+    let ca_key = KeyPair::from_pem(&fs::read_to_string(ca_dir.join(CA_KEY_FILE))?).map_err(cert_error)?;
+    let ca = ca_params().self_signed(&ca_key).map_err(cert_error)?;
+
+    let mut params = CertificateParams::new(names.to_vec()).map_err(cert_error)?;
+    params.distinguished_name.push(DnType::CommonName, name);
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth, ExtendedKeyUsagePurpose::ClientAuth];
+    let key = KeyPair::generate().map_err(cert_error)?;
+    let cert = params.signed_by(&key, &ca, &ca_key).map_err(cert_error)?;
+
+    fs::create_dir_all(out_dir)?;
+    let cert_path = out_dir.join(format!("{}.pem", name));
+    let key_path = out_dir.join(format!("{}-key.pem", name));
+    write_private(&key_path, key.serialize_pem().as_bytes())?;
+    fs::write(&cert_path, cert.pem())?;
+    Ok(IssuedCertificate { cert: cert_path, key: key_path, fingerprint: fingerprint(cert.der()) })
+}
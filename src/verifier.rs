@@ -0,0 +1,119 @@
+use std::io;
+use bn254::{PublicKey, Signature, ECDSA};
+use substrate_bn::{pairing_batch, Group, Gt, G2};
+use crate::aggregator::AggregatedSignature;
+use crate::hash::HashScheme;
+use crate::keys::KeySet;
+use crate::scheme::SignatureScheme;
+
+// 用一组公钥的聚合公钥验证聚合签名
+pub struct Verifier {
+    key_set: KeySet,
+    aggregated_public_key: PublicKey,
+}
+
+impl Verifier {
+    pub fn new(key_set: KeySet) -> Self {
+        let aggregated_public_key = key_set.aggregate();
+        Verifier { key_set, aggregated_public_key }
+    }
+
+    // 只聚合位图中标记的签名者公钥
+    pub fn for_signers(registry: &KeySet, aggregated: &AggregatedSignature) -> io::Result<Self> {
+        Ok(Verifier::new(registry.select(&aggregated.signers)?))
+    }
+
+    pub fn key_set(&self) -> &KeySet {
+        &self.key_set
+    }
+
+    pub fn aggregated_public_key(&self) -> PublicKey {
+        self.aggregated_public_key
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        !self.key_set.is_empty() && verify_signature(message, signature, &self.aggregated_public_key)
+    }
+
+    pub fn verify_with(&self, scheme: &HashScheme, message: &[u8], signature: &Signature) -> bool {
+        !self.key_set.is_empty() && verify_signature_with(scheme, message, signature, &self.aggregated_public_key)
+    }
+
+    // 按请求记录的签名方案验证: 消息增强方案下每个签名者签的内容不同，不能使用聚合公钥
+    pub fn verify_with_scheme(
+        &self,
+        scheme: &HashScheme,
+        signature_scheme: SignatureScheme,
+        message: &[u8],
+        signature: &Signature,
+    ) -> bool {
+        match signature_scheme {
+            SignatureScheme::ProofOfPossession => self.verify_with(scheme, message, signature),
+            SignatureScheme::MessageAugmentation => {
+                let messages = vec![message; self.key_set.len()];
+                self.verify_messages_with_scheme(scheme, signature_scheme, &messages, signature)
+            }
+        }
+    }
+
+    // 多消息聚合签名，messages[i] 为 key_set 中第 i 个公钥签的消息
+    pub fn verify_messages(&self, scheme: &HashScheme, messages: &[&[u8]], signature: &Signature) -> bool {
+        self.verify_messages_with_scheme(scheme, SignatureScheme::ProofOfPossession, messages, signature)
+    }
+
+    pub fn verify_messages_with_scheme(
+        &self,
+        scheme: &HashScheme,
+        signature_scheme: SignatureScheme,
+        messages: &[&[u8]],
+        signature: &Signature,
+    ) -> bool {
+        if messages.len() != self.key_set.len() {
+            return false;
+        }
+        let signed = self
+            .key_set
+            .keys()
+            .iter()
+            .zip(messages)
+            .map(|(public_key, message)| Ok((*public_key, signature_scheme.signing_message(message, public_key)?)))
+            .collect::<io::Result<Vec<(PublicKey, Vec<u8>)>>>();
+        match signed {
+            Ok(signed) => {
+                let pairs: Vec<(PublicKey, &[u8])> =
+                    signed.iter().map(|(public_key, message)| (*public_key, message.as_slice())).collect();
+                verify_multi_message(scheme, signature, &pairs)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn verify_signature(message: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+    ECDSA::verify(message, signature, public_key).is_ok()
+}
+
+// 检查 e(sig, -g2) * e(H(m), pk) == 1
+pub fn verify_signature_with(scheme: &HashScheme, message: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+    match scheme.hash_to_g1(message) {
+        Ok(point) => pairing_batch(&[(signature.0, -G2::one()), (point, public_key.0)]) == Gt::one(),
+        Err(_) => false,
+    }
+}
+
+// 检查 e(sig, -g2) * Π e(H(m_i), pk_i) == 1
+// 公钥都以持有证明登记，因此不要求各签名者的消息互不相同
+pub fn verify_multi_message(scheme: &HashScheme, signature: &Signature, pairs: &[(PublicKey, &[u8])]) -> bool {
+    if pairs.is_empty() {
+        return false;
+    }
+    let mut terms = Vec::with_capacity(pairs.len() + 1);
+    terms.push((signature.0, -G2::one()));
+    for (public_key, message) in pairs {
+        match scheme.hash_to_g1(message) {
+            Ok(point) => terms.push((point, public_key.0)),
+            Err(_) => return false,
+        }
+    }
+    pairing_batch(&terms) == Gt::one()
+}
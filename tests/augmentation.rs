@@ -0,0 +1,184 @@
+// 消息增强签名方案: 节点对 "域标签 || 公钥 || 消息" 签名，不依赖持有证明即可防御流氓公钥
+use std::sync::Arc;
+use substrate_bn::{Fr, Group, G2};
+use dss_core::{Aggregator, KeySet, PublicKey, Signature, Signer, Verifier};
+use dss_core::collector::KeyRegistration;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::registry::{RegisterOutcome, Registry, RegistryError};
+use dss_core::request::RequestRecord;
+use dss_core::scheme::{SignatureScheme, AUGMENTATION_DOMAIN};
+mod common;
+use common::spawn_node;
+
+const MESSAGE: &[u8] = b"transfer 100 to alice";
+
+#[test]
+fn augmented_message_binds_the_public_key() {
+    let signer = Signer::random();
+    let public_key = signer.public_key();
+    let pop = SignatureScheme::ProofOfPossession.signing_message(MESSAGE, &public_key).unwrap();
+    assert_eq!(pop, MESSAGE);
+
+    let augmented = SignatureScheme::MessageAugmentation.signing_message(MESSAGE, &public_key).unwrap();
+    let compressed = public_key.to_compressed().unwrap();
+    assert!(augmented.starts_with(AUGMENTATION_DOMAIN));
+    assert_eq!(&augmented[AUGMENTATION_DOMAIN.len()..][..compressed.len()], compressed.as_slice());
+    assert!(augmented.ends_with(MESSAGE));
+    let other = SignatureScheme::MessageAugmentation.signing_message(MESSAGE, &Signer::random().public_key()).unwrap();
+    assert_ne!(augmented, other);
+
+    let names = [
+        ("proof_of_possession", SignatureScheme::ProofOfPossession),
+        ("message_augmentation", SignatureScheme::MessageAugmentation),
+    ];
+    for (name, scheme) in names {
+        assert_eq!(name.parse::<SignatureScheme>().unwrap(), scheme);
+        assert_eq!(scheme.to_string(), name);
+    }
+    assert!("basic".parse::<SignatureScheme>().is_err());
+}
+
+#[test]
+fn rogue_key_is_rejected_under_augmentation() {
+    let scheme = HashScheme::TryAndIncrement;
+    let honest = Signer::random();
+
+    // 攻击者登记 pk' = x·g2 - pk_honest 而不持有对应私钥，聚合公钥为 x·g2，
+    // 攻击者独自生成的 x·H(m) 在聚合公钥下验证通过，看起来像是两者共同签名
+    let x = Fr::random(&mut rand_core::OsRng);
+    let rogue = PublicKey(G2::one() * x - honest.public_key().0);
+    let keys = KeySet::new(vec![honest.public_key(), rogue]);
+    let forged = Signature(scheme.hash_to_g1(MESSAGE).unwrap() * x);
+    let verifier = Verifier::new(keys);
+    assert!(verifier.verify_with_scheme(&scheme, SignatureScheme::ProofOfPossession, MESSAGE, &forged));
+
+    // 消息增强方案下每个公钥签的内容不同，伪造无效
+    assert!(!verifier.verify_with_scheme(&scheme, SignatureScheme::MessageAugmentation, MESSAGE, &forged));
+}
+
+#[test]
+fn augmented_aggregate_verifies_per_signer() {
+    let scheme = HashScheme::TryAndIncrement;
+    let augmentation = SignatureScheme::MessageAugmentation;
+    let signers: Vec<Signer> = (0..4).map(|_| Signer::random()).collect();
+    let registry = KeySet::new(signers.iter().map(|signer| signer.public_key()).collect());
+
+    let mut aggregator = Aggregator::new(registry.len());
+    for index in [0, 2, 3] {
+        let signed = augmentation.signing_message(MESSAGE, &signers[index].public_key()).unwrap();
+        aggregator.add(index, signers[index].sign_with(&scheme, &signed).unwrap());
+    }
+    let aggregated = aggregator.aggregate();
+    let verifier = Verifier::for_signers(&registry, &aggregated).unwrap();
+    assert!(verifier.verify_with_scheme(&scheme, augmentation, MESSAGE, &aggregated.signature));
+    assert!(!verifier.verify_with_scheme(&scheme, augmentation, b"other message", &aggregated.signature));
+    assert!(!verifier.verify_with_scheme(&scheme, SignatureScheme::ProofOfPossession, MESSAGE, &aggregated.signature));
+
+    // 多消息请求同样可以使用消息增强
+    let messages: [&[u8]; 3] = [b"a", b"b", b"c"];
+    let mut aggregator = Aggregator::new(registry.len());
+    for (index, message) in [0, 2, 3].into_iter().zip(messages) {
+        let signed = augmentation.signing_message(message, &signers[index].public_key()).unwrap();
+        aggregator.add(index, signers[index].sign_with(&scheme, &signed).unwrap());
+    }
+    let aggregated = aggregator.aggregate();
+    let verifier = Verifier::for_signers(&registry, &aggregated).unwrap();
+    assert!(verifier.verify_messages_with_scheme(&scheme, augmentation, &messages, &aggregated.signature));
+    assert!(!verifier.verify_messages(&scheme, &messages, &aggregated.signature));
+}
+
+fn unproven(signer: &Signer, index: usize, nonce: u64) -> KeyRegistration {
+    KeyRegistration::unproven(signer.public_key(), &format!("node-{}", index), &format!("127.0.0.1:{}", 9000 + index), None, nonce)
+}
+
+#[test]
+fn augmentation_deployments_register_keys_without_proof_of_possession() {
+    let scheme = HashScheme::TryAndIncrement;
+    let augmentation = SignatureScheme::MessageAugmentation;
+    let pop = SignatureScheme::ProofOfPossession;
+    let signers: Vec<Signer> = (0..4).map(|_| Signer::random()).collect();
+
+    // 持有证明部署拒绝没有持有证明的登记
+    assert_eq!(Registry::new().register(&unproven(&signers[0], 0, 1), 1, 0), Err(RegistryError::InvalidProof));
+
+    // 消息增强部署接受没有持有证明的公钥，并记录其登记方式; 附带持有证明的公钥仍按持有证明记录
+    let mut registry = Registry::new().with_signature_scheme(augmentation);
+    for (index, signer) in signers.iter().enumerate().take(3) {
+        assert_eq!(registry.register(&unproven(signer, index, 1), 1, 0).unwrap(), RegisterOutcome::Created);
+    }
+    let proven = KeyRegistration::at(&signers[3], "node-3", "127.0.0.1:9003", None, 1).unwrap();
+    let tampered = KeyRegistration { nonce: 2, ..proven.clone() };
+    assert_eq!(registry.register(&tampered, 1, 0), Err(RegistryError::InvalidProof));
+    assert_eq!(registry.register(&proven, 1, 0).unwrap(), RegisterOutcome::Created);
+    assert_eq!(registry.get("node-0").unwrap().scheme_at(Some(0)), augmentation);
+    assert_eq!(registry.get("node-3").unwrap().scheme_at(Some(0)), pop);
+
+    // 登记方式随记录保存，公钥收集服务返回的记录重建后不变
+    let json = serde_json::to_value(registry.records()).unwrap();
+    assert_eq!(json[0]["keys"][0]["signature_scheme"], "message_augmentation");
+    let restored = Registry::from_records(serde_json::from_value(json).unwrap()).unwrap();
+
+    // 包含没有持有证明的签名者的消息增强聚合签名可以验证
+    let mut aggregator = Aggregator::new(restored.len());
+    for index in [0, 2, 3] {
+        let signed = augmentation.signing_message(MESSAGE, &signers[index].public_key()).unwrap();
+        aggregator.add(index, signers[index].sign_with(&scheme, &signed).unwrap());
+    }
+    let aggregated = aggregator.aggregate();
+    let signer_indices = aggregated.signers.indices();
+    restored.check_scheme(&signer_indices, Some(0), augmentation).unwrap();
+    let verifier = Verifier::for_signers(&restored.key_set_at(0), &aggregated).unwrap();
+    assert!(verifier.verify_with_scheme(&scheme, augmentation, MESSAGE, &aggregated.signature));
+
+    // 没有持有证明的公钥不能用于持有证明方案的签名
+    assert_eq!(
+        restored.check_scheme(&signer_indices, Some(0), pop),
+        Err(RegistryError::ProofRequired("node-0".to_string()))
+    );
+    assert_eq!(restored.check_scheme(&[3], None, pop), Ok(()));
+
+    // 没有持有证明时，已有节点的地址变更需要已登记公钥的授权签名
+    let moved = KeyRegistration { address: "10.0.0.66:9000".to_string(), ..unproven(&signers[0], 0, 2) };
+    assert_eq!(registry.register(&moved, 2, 0), Err(RegistryError::Unauthorized("node-0".to_string())));
+    assert_eq!(registry.register(&moved.clone().authorize(&signers[1]).unwrap(), 2, 0), Err(RegistryError::Unauthorized("node-0".to_string())));
+    assert_eq!(registry.register(&moved.authorize(&signers[0]).unwrap(), 2, 0).unwrap(), RegisterOutcome::Updated);
+    // 重复登记当前公钥是幂等的
+    assert_eq!(registry.register(&unproven(&signers[1], 1, 1), 3, 0).unwrap(), RegisterOutcome::Unchanged);
+}
+
+#[test]
+fn records_without_a_scheme_default_to_proof_of_possession() {
+    let record = RequestRecord::new("old", "message", &HashScheme::TryAndIncrement, &["node".to_string()], 1);
+    let mut json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["signature_scheme"], "proof_of_possession");
+    json.as_object_mut().unwrap().remove("signature_scheme");
+    let restored: RequestRecord = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.signature_scheme, SignatureScheme::ProofOfPossession);
+}
+
+#[tokio::test]
+async fn node_service_signs_augmented_messages() {
+    let scheme = HashScheme::TryAndIncrement;
+    let mut addrs = Vec::new();
+    for _ in 0..3 {
+        addrs.push(spawn_node(NodeService::from_signer(Arc::new(Signer::random()))).await);
+    }
+
+    let mut keys = KeySet::default();
+    let mut aggregator = Aggregator::new(addrs.len());
+    let options = SignOptions::new(scheme.clone()).with_signature_scheme(SignatureScheme::MessageAugmentation);
+    for (index, addr) in addrs.iter().enumerate() {
+        let node_signature = request_signature(addr, MESSAGE, &options).await.unwrap();
+        keys.push(node_signature.public_key);
+        aggregator.add(index, node_signature.signature);
+    }
+    let aggregated = aggregator.aggregate();
+    let verifier = Verifier::new(keys);
+    assert!(verifier.verify_with_scheme(&scheme, SignatureScheme::MessageAugmentation, MESSAGE, &aggregated.signature));
+    assert!(!verifier.verify_with(&scheme, MESSAGE, &aggregated.signature));
+
+    // 同一节点在默认方案下签的是原始消息
+    let node_signature = request_signature(&addrs[0], MESSAGE, &SignOptions::new(scheme.clone())).await.unwrap();
+    assert!(dss_core::verifier::verify_signature_with(&scheme, MESSAGE, &node_signature.signature, &node_signature.public_key));
+}
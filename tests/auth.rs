@@ -0,0 +1,99 @@
+// 签名请求的主节点认证: 节点只为允许列表中的主节点签名，时间戳和随机数防止重放
+use std::sync::Arc;
+use dss_core::Signer;
+use dss_core::auth::{parse_aggregator_key, AggregatorIdentity, RequestAuthenticator};
+use dss_core::epoch::KeySchedule;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::protocol::{sign_request_body, ErrorCode, ProtocolError};
+use dss_core::scheme::SignatureScheme;
+mod common;
+use common::spawn_node;
+
+const MESSAGE: &[u8] = b"authenticated message";
+const NOW: u64 = 1_700_000_000_000;
+
+fn body(message: &[u8]) -> Vec<u8> {
+    sign_request_body(message, &HashScheme::TryAndIncrement, SignatureScheme::ProofOfPossession, None)
+}
+
+#[test]
+fn identity_keys_round_trip() {
+    let dir = std::env::temp_dir().join(format!("dss-auth-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("aggregator.key");
+    let (identity, is_new) = AggregatorIdentity::load_or_generate(&path).unwrap();
+    assert!(is_new);
+    let (loaded, is_new) = AggregatorIdentity::load_or_generate(&path).unwrap();
+    assert!(!is_new);
+    assert_eq!(loaded.public_key(), identity.public_key());
+
+    let public_key = hex::encode(identity.public_key());
+    assert_eq!(parse_aggregator_key(&public_key).unwrap(), identity.public_key());
+    assert!(parse_aggregator_key(&public_key[..40]).is_err());
+    assert!(parse_aggregator_key(&format!("05{}", &public_key[2..])).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_allowed_aggregators_pass() {
+    let aggregator = AggregatorIdentity::random();
+    let stranger = AggregatorIdentity::random();
+    let authenticator = RequestAuthenticator::new(vec![aggregator.public_key()]).with_max_skew(1000);
+
+    let auth = aggregator.authorize(&body(MESSAGE), NOW);
+    assert_eq!(authenticator.check(Some(&auth), &body(MESSAGE), NOW + 500), Ok(aggregator.public_key()));
+
+    assert!(authenticator.check(None, &body(MESSAGE), NOW).unwrap_err().contains("not authenticated"));
+    let auth = stranger.authorize(&body(MESSAGE), NOW);
+    assert!(authenticator.check(Some(&auth), &body(MESSAGE), NOW).unwrap_err().contains("not authorized"));
+
+    // 签名覆盖请求内容、时间戳和随机数
+    let auth = aggregator.authorize(&body(MESSAGE), NOW);
+    assert!(authenticator.check(Some(&auth), &body(b"other message"), NOW).is_err());
+    let mut tampered = aggregator.authorize(&body(MESSAGE), NOW);
+    tampered.timestamp += 1;
+    assert!(authenticator.check(Some(&tampered), &body(MESSAGE), NOW).is_err());
+    let mut tampered = aggregator.authorize(&body(MESSAGE), NOW);
+    tampered.nonce[0] ^= 1;
+    assert!(authenticator.check(Some(&tampered), &body(MESSAGE), NOW).is_err());
+}
+
+#[test]
+fn stale_and_replayed_requests_are_rejected() {
+    let aggregator = AggregatorIdentity::random();
+    let authenticator = RequestAuthenticator::new(vec![aggregator.public_key()]).with_max_skew(1000);
+
+    let auth = aggregator.authorize(&body(MESSAGE), NOW);
+    assert!(authenticator.check(Some(&auth), &body(MESSAGE), NOW + 1001).unwrap_err().contains("away from the node clock"));
+    assert!(authenticator.check(Some(&auth), &body(MESSAGE), NOW - 1001).is_err());
+
+    assert!(authenticator.check(Some(&auth), &body(MESSAGE), NOW).is_ok());
+    assert_eq!(authenticator.check(Some(&auth), &body(MESSAGE), NOW + 10), Err("replayed sign request".to_string()));
+    // 同一内容的新请求使用新的随机数
+    let again = aggregator.authorize(&body(MESSAGE), NOW);
+    assert_ne!(again.nonce, auth.nonce);
+    assert!(authenticator.check(Some(&again), &body(MESSAGE), NOW + 10).is_ok());
+}
+
+#[tokio::test]
+async fn node_service_rejects_unauthorized_requests() {
+    let scheme = HashScheme::TryAndIncrement;
+    let aggregator = AggregatorIdentity::random();
+    let signer = Arc::new(Signer::random());
+    let keys = Arc::new(KeySchedule::new(signer.clone()));
+    let authenticator = Arc::new(RequestAuthenticator::new(vec![aggregator.public_key()]));
+    let addr = spawn_node(NodeService::new(keys).with_authenticator(authenticator)).await;
+
+    let options = SignOptions::new(scheme.clone()).with_identity(Arc::new(aggregator));
+    let node_signature = request_signature(&addr, MESSAGE, &options).await.unwrap();
+    assert_eq!(node_signature.public_key.0, signer.public_key().0);
+
+    // 未认证和未授权的请求以协议错误返回
+    let unauthorized = |e: std::io::Error| ProtocolError::from_io(&e).map(|e| e.code);
+    let e = request_signature(&addr, MESSAGE, &SignOptions::new(scheme.clone())).await.unwrap_err();
+    assert_eq!(unauthorized(e), Some(ErrorCode::Unauthorized));
+    let options = SignOptions::new(scheme).with_identity(Arc::new(AggregatorIdentity::random()));
+    let e = request_signature(&addr, MESSAGE, &options).await.unwrap_err();
+    assert_eq!(unauthorized(e), Some(ErrorCode::Unauthorized));
+}
@@ -0,0 +1,105 @@
+// 批量验证: 随机线性组合后一次多配对，失败时对半拆分找出无效条目
+use substrate_bn::{Group, G1, G2};
+use dss_core::{Aggregator, KeySet, PublicKey, Signature, Signer, Verifier};
+use dss_core::aggregator::aggregate_signatures;
+use dss_core::batch::{find_invalid, verify_batch, BatchEntry};
+use dss_core::hash::HashScheme;
+
+// 交替生成单签名、多签聚合签名和多消息聚合签名
+fn entries(count: usize) -> Vec<BatchEntry> {
+    let scheme = HashScheme::TryAndIncrement;
+    let signers: Vec<Signer> = (0..3).map(|_| Signer::random()).collect();
+    let registry = KeySet::new(signers.iter().map(|signer| signer.public_key()).collect());
+    (0..count)
+        .map(|i| {
+            let message = format!("result #{}", i).into_bytes();
+            match i % 3 {
+                0 => {
+                    let signer = &signers[i % signers.len()];
+                    BatchEntry::new(&scheme, &message, signer.sign(&message).unwrap(), signer.public_key())
+                }
+                1 => {
+                    let mut aggregator = Aggregator::new(registry.len());
+                    for (index, signer) in signers.iter().enumerate() {
+                        aggregator.add(index, signer.sign(&message).unwrap());
+                    }
+                    let aggregated = aggregator.aggregate();
+                    let verifier = Verifier::for_signers(&registry, &aggregated).unwrap();
+                    BatchEntry::new(&scheme, &message, aggregated.signature, verifier.aggregated_public_key())
+                }
+                _ => {
+                    let pairs: Vec<_> = signers
+                        .iter()
+                        .enumerate()
+                        .map(|(j, signer)| (signer.public_key(), format!("{}/{}", i, j).into_bytes()))
+                        .collect();
+                    let signatures: Vec<Signature> = signers
+                        .iter()
+                        .zip(&pairs)
+                        .map(|(signer, (_, message))| signer.sign(message).unwrap())
+                        .collect();
+                    BatchEntry::multi_message(&scheme, aggregate_signatures(&signatures), pairs)
+                }
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn valid_batch_passes_with_one_check() {
+    let batch = entries(30);
+    assert!(verify_batch(&batch));
+    assert!(find_invalid(&batch).is_empty());
+    assert!(verify_batch(&[]));
+    assert!(find_invalid(&[]).is_empty());
+}
+
+#[test]
+fn bisection_finds_every_invalid_entry() {
+    let mut batch = entries(64);
+    // 消息被篡改、签名属于其他条目、公钥错误、多消息中的一条消息被替换、没有公钥
+    batch[3].pairs[0].1 = b"tampered".to_vec();
+    batch[17].signature = batch[18].signature;
+    batch[40].pairs[0].0 = Signer::random().public_key();
+    batch[41].pairs[2].1 = b"41/0".to_vec();
+    batch[63].pairs.clear();
+    assert!(!verify_batch(&batch));
+    assert_eq!(find_invalid(&batch), vec![3, 17, 40, 41, 63]);
+
+    // 单个无效条目以及全部无效
+    let mut single = entries(5);
+    single[4].signature = Signature(G1::zero());
+    assert_eq!(find_invalid(&single), vec![4]);
+    let mut all = entries(4);
+    for entry in &mut all {
+        entry.signature = Signature(entry.signature.0 + G1::one());
+    }
+    assert_eq!(find_invalid(&all), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn two_invalid_signatures_cannot_cancel_out() {
+    // 把一个条目的签名误差转移到另一个条目，签名之和不变，但随机系数使批量检查失败
+    let mut batch = entries(6);
+    batch[0].signature = Signature(batch[0].signature.0 + G1::one());
+    batch[3].signature = Signature(batch[3].signature.0 - G1::one());
+    assert!(!verify_batch(&batch));
+    assert_eq!(find_invalid(&batch), vec![0, 3]);
+}
+
+#[test]
+fn identity_points_and_empty_signer_sets_are_rejected() {
+    let scheme = HashScheme::TryAndIncrement;
+    // 零签名配零公钥对任何消息都满足验证方程
+    let forged = BatchEntry::new(&scheme, b"anything", Signature(G1::zero()), PublicKey(G2::zero()));
+    assert!(!verify_batch(std::slice::from_ref(&forged)));
+
+    let mut batch = entries(6);
+    batch.push(forged);
+    // 多消息条目中混入零公钥，以及没有签名者的条目
+    batch[2].pairs[1].0 = PublicKey(G2::zero());
+    batch[4].pairs.clear();
+    batch[4].signature = Signature(G1::zero());
+    assert!(!verify_batch(&batch));
+    assert_eq!(find_invalid(&batch), vec![2, 4, 6]);
+}
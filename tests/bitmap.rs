@@ -0,0 +1,64 @@
+// 签名者位图: 置位和查询、十六进制解析以及按位图选取公钥时的越界检查
+use dss_core::{Aggregator, KeySet, Signer, SignerBitmap};
+
+#[test]
+fn bits_are_set_and_listed_in_order() {
+    let mut bitmap = SignerBitmap::new(10);
+    assert!(bitmap.is_empty());
+    assert_eq!(bitmap.to_hex(), "0000");
+    for index in [9, 0, 3, 9] {
+        bitmap.set(index);
+    }
+    assert_eq!(bitmap.count(), 3);
+    assert_eq!(bitmap.indices(), vec![0, 3, 9]);
+    assert!(bitmap.contains(3) && !bitmap.contains(4));
+    // 第 0 位在第一个字节的最低位
+    assert_eq!(bitmap.to_hex(), "0902");
+
+    // 超出当前长度的下标视为未置位，置位时自动扩展
+    assert!(!bitmap.contains(1000));
+    bitmap.set(20);
+    assert_eq!(bitmap.to_hex(), "090210");
+    assert_eq!(bitmap.indices(), vec![0, 3, 9, 20]);
+}
+
+#[test]
+fn hex_parsing() {
+    let bitmap = SignerBitmap::from_hex("0902").unwrap();
+    assert_eq!(bitmap.indices(), vec![0, 3, 9]);
+    assert_eq!(SignerBitmap::from_hex(&bitmap.to_hex()).unwrap(), bitmap);
+    assert!(SignerBitmap::from_hex("").unwrap().is_empty());
+    assert!(SignerBitmap::from_hex("090").is_err());
+    assert!(SignerBitmap::from_hex("zz").is_err());
+
+    let json = serde_json::to_string(&bitmap).unwrap();
+    assert_eq!(json, "\"0902\"");
+    assert_eq!(serde_json::from_str::<SignerBitmap>(&json).unwrap(), bitmap);
+    assert!(serde_json::from_str::<SignerBitmap>("\"not hex\"").is_err());
+}
+
+#[test]
+fn selection_is_bounded_by_the_registry() {
+    let keys: Vec<_> = (0..3).map(|_| Signer::random().public_key()).collect();
+    let registry = KeySet::new(keys.clone());
+
+    let selected = registry.select(&SignerBitmap::from_hex("05").unwrap()).unwrap();
+    assert_eq!(selected.len(), 2);
+    assert_eq!(selected.keys()[0].0, keys[0].0);
+    assert_eq!(selected.keys()[1].0, keys[2].0);
+    // 位图长度可以超过登记表，但置位的下标不能
+    assert_eq!(registry.select(&SignerBitmap::from_hex("0300").unwrap()).unwrap().len(), 2);
+    let e = registry.select(&SignerBitmap::from_hex("08").unwrap()).unwrap_err();
+    assert!(e.to_string().contains("out of range"));
+}
+
+#[test]
+fn aggregator_records_each_signer_once() {
+    let signer = Signer::random();
+    let signature = signer.sign(b"bitmap").unwrap();
+    let mut aggregator = Aggregator::new(4);
+    assert!(aggregator.add(2, signature));
+    assert!(!aggregator.add(2, signature));
+    assert_eq!(aggregator.len(), 1);
+    assert_eq!(aggregator.aggregate().signers.indices(), vec![2]);
+}
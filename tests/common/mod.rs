@@ -0,0 +1,38 @@
+// 集成测试共用的辅助函数: 在本地端口上启动节点服务
+#![allow(dead_code)]
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use dss_core::node::NodeService;
+
+// 没有服务监听的本地地址，用作离线节点
+pub fn free_addr() -> String {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+}
+
+// 绑定本地空闲端口，监听套接字直接交给服务，避免先探测端口再重新绑定时被其他进程占用
+pub async fn bind_local() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+// 重试连接直到服务开始接受连接
+pub async fn wait_ready(addr: &str) {
+    for _ in 0..200 {
+        if TcpStream::connect(addr).await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("{} did not start accepting connections", addr);
+}
+
+// 在本地空闲端口上启动节点服务，返回可连接的地址
+pub async fn spawn_node(service: NodeService) -> String {
+    let (listener, addr) = bind_local().await;
+    tokio::spawn(async move {
+        service.serve(listener).await.unwrap();
+    });
+    wait_ready(&addr).await;
+    addr
+}
@@ -0,0 +1,514 @@
+// 分布式密钥生成: 库层面的各轮计算，以及通过节点服务运行的进程内委员会
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use substrate_bn::{Fr, Group, G2};
+use zeroize::Zeroizing;
+use dss_core::{PublicKey, Signer};
+use dss_core::auth::{AggregatorIdentity, RequestAuthenticator};
+use dss_core::dkg::{
+    dkg_request, group_public_key, run_dkg, share_public_key, DealerCommitments, DkgCoordinator, DkgMessage, DkgNode,
+    DkgParticipant, Participant, ReceivedDeal, RevealedShare,
+};
+use dss_core::epoch::KeySchedule;
+use dss_core::hash::HashScheme;
+use dss_core::keystore;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::protocol::{read_message, write_message, ErrorCode, Message, ProtocolError};
+use dss_core::tls::{create_ca, issue_certificate, IssuedCertificate, TlsClient, TlsIdentity, TlsServer, Transport};
+use dss_core::threshold::{combine, ThresholdAggregator};
+use dss_core::verifier::{verify_signature, verify_signature_with};
+mod common;
+use common::{bind_local, free_addr, spawn_node};
+
+const PASSWORD: &str = "dkg-test";
+const MESSAGE: &[u8] = b"committee message";
+
+fn participants(n: u32) -> Vec<Participant> {
+    (1..=n).map(|index| Participant { index, address: format!("node-{}", index) }).collect()
+}
+
+// 不经过网络，直接在内存中交换份额
+fn deal_all(dealers: &[DkgParticipant]) -> (Vec<DealerCommitments>, Vec<BTreeMap<u32, ReceivedDeal>>) {
+    let commitments = dealers
+        .iter()
+        .map(|dealer| DealerCommitments { dealer: dealer.index, commitments: dealer.commitments().to_vec() })
+        .collect();
+    let inboxes = dealers
+        .iter()
+        .map(|recipient| {
+            dealers
+                .iter()
+                .map(|dealer| {
+                    let deal = ReceivedDeal {
+                        commitments: dealer.commitments().to_vec(),
+                        share: dealer.share_for(recipient.index).unwrap(),
+                    };
+                    (dealer.index, deal)
+                })
+                .collect()
+        })
+        .collect();
+    (commitments, inboxes)
+}
+
+#[test]
+fn honest_committee_produces_consistent_shares() {
+    let (n, t) = (5, 3);
+    let dealers: Vec<DkgParticipant> = (1..=n)
+        .map(|index| DkgParticipant::new("honest", t, index, participants(n)).unwrap())
+        .collect();
+    let (commitments, inboxes) = deal_all(&dealers);
+    let qualified: BTreeSet<u32> = (1..=n).collect();
+
+    let mut partials = Vec::new();
+    for (participant, inbox) in dealers.iter().zip(&inboxes) {
+        assert!(participant.complaints(&commitments, inbox).is_empty());
+        let share = participant.finalize(&commitments, &qualified, inbox, &[], "group").unwrap();
+        assert_eq!(share.group_public_key.0, group_public_key(&commitments, &qualified).0);
+        assert_eq!(
+            share.signer.public_key().0,
+            share_public_key(&commitments, &qualified, participant.index).unwrap().0
+        );
+        partials.push((participant.index, share.signer.sign(MESSAGE).unwrap()));
+    }
+
+    // 群公钥即各分发者常数项承诺之和
+    let expected = commitments.iter().fold(G2::zero(), |acc, dealer| acc + dealer.commitments[0].0);
+    let group_key = PublicKey(expected);
+    assert_eq!(group_public_key(&commitments, &qualified).0, expected);
+
+    // 任意 t 个份额的签名合成后在群公钥下验证，t - 1 个不行
+    for subset in [[0, 1, 2], [1, 3, 4], [0, 2, 4]] {
+        let selected: Vec<_> = subset.iter().map(|&i| partials[i]).collect();
+        assert!(verify_signature(MESSAGE, &combine(&selected).unwrap(), &group_key));
+    }
+    assert!(!verify_signature(MESSAGE, &combine(&partials[..2]).unwrap(), &group_key));
+}
+
+#[test]
+fn bad_share_is_resolved_by_reveal() {
+    let (n, t) = (4, 2);
+    let dealers: Vec<DkgParticipant> = (1..=n)
+        .map(|index| DkgParticipant::new("complaint", t, index, participants(n)).unwrap())
+        .collect();
+    let (commitments, mut inboxes) = deal_all(&dealers);
+
+    // 分发者 2 发给参与者 3 的份额被篡改，参与者 4 没有收到分发者 1 的份额
+    let deal = inboxes[2].get_mut(&2).unwrap();
+    deal.share = deal.share + Fr::one();
+    inboxes[3].remove(&1);
+    assert_eq!(dealers[2].complaints(&commitments, &inboxes[2]), vec![2]);
+    assert_eq!(dealers[3].complaints(&commitments, &inboxes[3]), vec![1]);
+    assert!(dealers[0].complaints(&commitments, &inboxes[0]).is_empty());
+
+    // 未公开份额时无法完成
+    let qualified: BTreeSet<u32> = (1..=n).collect();
+    assert!(dealers[2].finalize(&commitments, &qualified, &inboxes[2], &[], "group").is_err());
+
+    // 公开的有效份额覆盖收到的份额
+    let revealed = [RevealedShare { dealer: 2, share: dealers[1].share_for(3).unwrap() }];
+    let share = dealers[2].finalize(&commitments, &qualified, &inboxes[2], &revealed, "group").unwrap();
+    assert_eq!(share.signer.public_key().0, share_public_key(&commitments, &qualified, 3).unwrap().0);
+
+    // 公开的份额本身无效时拒绝
+    let revealed = [RevealedShare { dealer: 1, share: Fr::one() }];
+    assert!(dealers[3].finalize(&commitments, &qualified, &inboxes[3], &revealed, "group").is_err());
+}
+
+#[test]
+fn disqualified_dealer_is_excluded_from_the_group_key() {
+    let (n, t) = (4, 3);
+    let dealers: Vec<DkgParticipant> = (1..=n)
+        .map(|index| DkgParticipant::new("disqualify", t, index, participants(n)).unwrap())
+        .collect();
+    let (commitments, inboxes) = deal_all(&dealers);
+    let qualified: BTreeSet<u32> = [1, 2, 4].into_iter().collect();
+
+    let mut partials = Vec::new();
+    for (participant, inbox) in dealers.iter().zip(&inboxes) {
+        let share = participant.finalize(&commitments, &qualified, inbox, &[], "group").unwrap();
+        partials.push((participant.index, share.signer.sign(MESSAGE).unwrap()));
+    }
+    let group_key = group_public_key(&commitments, &qualified);
+    let all = group_public_key_of_all(&commitments);
+    assert_ne!(group_key.0, all.0);
+    assert!(verify_signature(MESSAGE, &combine(&partials[1..]).unwrap(), &group_key));
+}
+
+fn group_public_key_of_all(commitments: &[DealerCommitments]) -> PublicKey {
+    group_public_key(commitments, &commitments.iter().map(|dealer| dealer.dealer).collect())
+}
+
+#[test]
+fn invalid_parameters_are_rejected() {
+    assert!(DkgParticipant::new("s", 0, 1, participants(3)).is_err());
+    // 门限为 1 时每个份额都等于主私钥
+    assert!(DkgParticipant::new("s", 1, 1, participants(3)).is_err());
+    assert!(DkgParticipant::new("s", 4, 1, participants(3)).is_err());
+    assert!(DkgParticipant::new("s", 2, 4, participants(3)).is_err());
+    let mut duplicated = participants(3);
+    duplicated[2].index = 1;
+    assert!(DkgParticipant::new("s", 2, 1, duplicated).is_err());
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-dkg-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 节点通道: 本地 CA 签发的证书 (节点和协调者共用) 以及节点允许的协调者身份
+struct Channel {
+    ca_dir: PathBuf,
+    issued: IssuedCertificate,
+    coordinator: PathBuf,
+}
+
+impl Channel {
+    fn new(dir: &Path) -> Self {
+        let ca_dir = dir.join("ca");
+        create_ca(&ca_dir).unwrap();
+        let issued =
+            issue_certificate(&ca_dir, "node", &["127.0.0.1".to_string(), "localhost".to_string()], &ca_dir).unwrap();
+        let coordinator = dir.join("coordinator.key");
+        AggregatorIdentity::random().save(&coordinator).unwrap();
+        Channel { ca_dir, issued, coordinator }
+    }
+
+    fn identity(&self) -> TlsIdentity {
+        TlsIdentity::load(&self.ca_dir.join("ca.pem"), &self.issued.cert, &self.issued.key).unwrap()
+    }
+
+    fn transport(&self) -> Transport {
+        Transport::Tls(Arc::new(TlsClient::new(self.identity(), Default::default()).unwrap()))
+    }
+
+    fn server(&self) -> Arc<TlsServer> {
+        Arc::new(TlsServer::new(self.identity(), Vec::new()).unwrap())
+    }
+
+    fn coordinator(&self) -> DkgCoordinator {
+        DkgCoordinator::new(self.transport(), AggregatorIdentity::load(&self.coordinator).unwrap()).unwrap()
+    }
+}
+
+// 启用 DKG 的节点只经双向 TLS 接受允许的协调者
+async fn spawn_dkg_node(channel: &Channel, dkg: Arc<DkgNode>) -> String {
+    let keys = Arc::new(KeySchedule::new(Arc::new(Signer::random())));
+    let tls = channel.server();
+    let coordinator = AggregatorIdentity::load(&channel.coordinator).unwrap().public_key();
+    let auth = Arc::new(RequestAuthenticator::new(vec![coordinator]));
+    spawn_node(NodeService::new(keys).with_dkg(dkg).with_tls(tls).with_authenticator(auth)).await
+}
+
+async fn spawn_committee(dir: &Path, channel: &Channel, n: usize) -> (Vec<String>, Vec<Arc<DkgNode>>) {
+    let mut addrs = Vec::new();
+    let mut nodes = Vec::new();
+    for i in 0..n {
+        let node_dir = dir.join(format!("node-{}", i + 1));
+        std::fs::create_dir_all(&node_dir).unwrap();
+        let dkg = Arc::new(DkgNode::new(node_dir, Zeroizing::new(PASSWORD.to_string())).with_transport(channel.transport()));
+        addrs.push(spawn_dkg_node(channel, dkg.clone()).await);
+        nodes.push(dkg);
+    }
+    (addrs, nodes)
+}
+
+// 用 DKG 保存的份额重启节点，通过节点服务收集部分签名并合成
+async fn threshold_sign(nodes: &[Arc<DkgNode>], session: &str, threshold: usize, group: &dss_core::threshold::ThresholdGroup) {
+    let scheme = HashScheme::TryAndIncrement;
+    let mut aggregator = ThresholdAggregator::new(threshold);
+    for dkg in nodes.iter().rev().take(threshold) {
+        let share = keystore::load(&dkg.share_path(session), PASSWORD).unwrap();
+        let addr = spawn_node(NodeService::from_signer(Arc::new(share))).await;
+        let node_signature = request_signature(&addr, MESSAGE, &SignOptions::new(scheme.clone())).await.unwrap();
+        let index = group.share_index(&node_signature.public_key).unwrap();
+        assert!(aggregator.add(index, node_signature.signature));
+    }
+    let signature = aggregator.combine().unwrap();
+    assert!(verify_signature_with(&scheme, MESSAGE, &signature, &group.group_public_key));
+}
+
+#[tokio::test]
+async fn committee_runs_dkg_over_node_channel() {
+    let dir = temp_dir("committee");
+    let channel = Channel::new(&dir);
+    let coordinator = channel.coordinator();
+    let (addrs, nodes) = spawn_committee(&dir, &channel, 4).await;
+
+    let outcome = run_dkg(&coordinator, "committee", 3, &addrs, "committee-group").await.unwrap();
+    assert_eq!(outcome.qualified, vec![1, 2, 3, 4]);
+    assert!(outcome.registration.verify());
+    assert_eq!(outcome.registration.group_id, "committee-group");
+    for (dkg, share) in nodes.iter().zip(&outcome.group.shares) {
+        let stored = keystore::EncryptedKeystore::read(&dkg.share_path("committee")).unwrap();
+        assert_eq!(stored.public_key().unwrap().0, share.public_key.0);
+    }
+    threshold_sign(&nodes, "committee", 3, &outcome.group).await;
+
+    // 同一会话不能重复运行，份额文件不会被覆盖
+    assert!(run_dkg(&coordinator, "committee", 3, &addrs, "committee-group").await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn node_without_dkg_aborts_the_session() {
+    let dir = temp_dir("disabled");
+    let channel = Channel::new(&dir);
+    let (mut addrs, _nodes) = spawn_committee(&dir, &channel, 2).await;
+    addrs.push(spawn_node(NodeService::from_signer(Arc::new(Signer::random()))).await);
+    assert!(run_dkg(&channel.coordinator(), "disabled", 2, &addrs, "group").await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn threshold_of_one_is_rejected_before_contacting_nodes() {
+    let dir = temp_dir("single");
+    let channel = Channel::new(&dir);
+    let (addrs, nodes) = spawn_committee(&dir, &channel, 3).await;
+    let error = match run_dkg(&channel.coordinator(), "single", 1, &addrs, "group").await {
+        Ok(_) => panic!("DKG with threshold 1 succeeded"),
+        Err(e) => e,
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    // 节点同样拒绝协调者发来的门限 1
+    let participants: Vec<Participant> = addrs
+        .iter()
+        .enumerate()
+        .map(|(i, address)| Participant { index: i as u32 + 1, address: address.clone() })
+        .collect();
+    let start = DkgMessage::Start { session: "single".to_string(), threshold: 1, index: 1, participants };
+    let refused = channel.coordinator().request(&addrs[0], start).await.unwrap_err();
+    assert_eq!(ProtocolError::from_io(&refused).unwrap().code, ErrorCode::DkgFailed);
+    assert!(nodes.iter().all(|dkg| !dkg.share_path("single").exists()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn dkg_needs_mutual_tls_and_an_authorized_coordinator() {
+    let dir = temp_dir("unauthorized");
+    let channel = Channel::new(&dir);
+    let (addrs, _nodes) = spawn_committee(&dir, &channel, 2).await;
+    let start = DkgMessage::Start {
+        session: "unauthorized".to_string(),
+        threshold: 2,
+        index: 1,
+        participants: addrs
+            .iter()
+            .enumerate()
+            .map(|(position, address)| Participant { index: position as u32 + 1, address: address.clone() })
+            .collect(),
+    };
+
+    // 协调者和 DKG 消息都不经明文通道发送
+    let identity = AggregatorIdentity::load(&channel.coordinator).unwrap();
+    assert!(DkgCoordinator::new(Transport::Plain, identity).is_err());
+    assert!(dkg_request(&Transport::Plain, &addrs[0], start.clone(), None).await.is_err());
+
+    // 没有认证或由其他协调者签名的消息被拒绝
+    let unauthenticated = dkg_request(&channel.transport(), &addrs[0], start.clone(), None).await.unwrap_err();
+    assert_eq!(ProtocolError::from_io(&unauthenticated).unwrap().code, ErrorCode::Unauthorized);
+    let intruder = DkgCoordinator::new(channel.transport(), AggregatorIdentity::random()).unwrap();
+    let forged = intruder.request(&addrs[0], start.clone()).await.unwrap_err();
+    assert_eq!(ProtocolError::from_io(&forged).unwrap().code, ErrorCode::Unauthorized);
+
+    // 未启用 TLS 的节点服务拒绝 DKG 帧
+    let node_dir = dir.join("plain");
+    std::fs::create_dir_all(&node_dir).unwrap();
+    let dkg = Arc::new(DkgNode::new(node_dir, Zeroizing::new(PASSWORD.to_string())));
+    let plain = spawn_node(NodeService::from_signer(Arc::new(Signer::random())).with_dkg(dkg)).await;
+    let mut socket = tokio::net::TcpStream::connect(&plain).await.unwrap();
+    write_message(&mut socket, &Message::Dkg { message: start, auth: None }).await.unwrap();
+    match read_message(&mut socket).await.unwrap() {
+        Some(Message::Error(e)) => assert_eq!(e.code, ErrorCode::Unauthorized),
+        other => panic!("expected an error, got {:?}", other),
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn reveal_needs_recorded_complaints_and_is_answered_once() {
+    let dir = temp_dir("reveals");
+    let channel = Channel::new(&dir);
+    let coordinator = channel.coordinator();
+    let (mut addrs, _nodes) = spawn_committee(&dir, &channel, 1).await;
+    addrs.push(free_addr());
+    addrs.push(free_addr());
+    let participants: Vec<Participant> = addrs
+        .iter()
+        .enumerate()
+        .map(|(position, address)| Participant { index: position as u32 + 1, address: address.clone() })
+        .collect();
+    let reveal = |accuser| DkgMessage::Reveal { session: "reveals".to_string(), accuser };
+
+    let start = DkgMessage::Start { session: "reveals".to_string(), threshold: 2, index: 1, participants };
+    let commitments = match coordinator.request(&addrs[0], start).await.unwrap() {
+        DkgMessage::Dealt { commitments, .. } => commitments,
+        reply => panic!("unexpected reply {:?}", reply),
+    };
+    // 投诉轮之前不公开份额
+    assert!(coordinator.request(&addrs[0], reveal(2)).await.is_err());
+
+    // 承诺数量与门限不符的统一承诺集合被拒绝，会话仍可用正确的集合验证
+    let malformed = |dealer_commitments: Vec<PublicKey>| DkgMessage::Verify {
+        session: "reveals".to_string(),
+        commitments: vec![
+            DealerCommitments { dealer: 1, commitments: commitments.clone() },
+            DealerCommitments { dealer: 2, commitments: dealer_commitments },
+        ],
+    };
+    for dealer_commitments in [Vec::new(), vec![commitments[0]; 3]] {
+        let refused = coordinator.request(&addrs[0], malformed(dealer_commitments)).await.unwrap_err();
+        assert_eq!(ProtocolError::from_io(&refused).unwrap().code, ErrorCode::DkgFailed);
+    }
+
+    let verify = DkgMessage::Verify {
+        session: "reveals".to_string(),
+        commitments: vec![DealerCommitments { dealer: 1, commitments: commitments.clone() }],
+    };
+    assert!(matches!(coordinator.request(&addrs[0], verify).await.unwrap(), DkgMessage::Complaints { .. }));
+    match coordinator.request(&addrs[0], reveal(2)).await.unwrap() {
+        DkgMessage::Revealed { share, .. } => assert!(dss_core::dkg::verify_share(&commitments, 2, share)),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+    // 同一投诉者只公开一次，自己和非参与者不能投诉; 门限为 2 时再公开一个份额即可恢复分发的秘密
+    assert!(coordinator.request(&addrs[0], reveal(2)).await.is_err());
+    assert!(coordinator.request(&addrs[0], reveal(1)).await.is_err());
+    assert!(coordinator.request(&addrs[0], reveal(4)).await.is_err());
+    assert!(coordinator.request(&addrs[0], reveal(3)).await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// 代理在前面的真实节点，模拟作恶的分发者或收不到份额的参与者
+#[derive(Clone, Copy)]
+enum Misbehaviour {
+    // 对 Reveal 返回无效份额
+    LieOnReveal,
+    // 丢弃来自该分发者的份额
+    DropDealsFrom(u32),
+}
+
+async fn spawn_proxy(channel: &Channel, backend: String, misbehaviour: Misbehaviour) -> String {
+    let (listener, addr) = bind_local().await;
+    let tls = channel.server();
+    let transport = Arc::new(channel.transport());
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (backend, tls, transport) = (backend.clone(), tls.clone(), transport.clone());
+            tokio::spawn(async move {
+                let mut socket = tls.accept(socket).await.unwrap();
+                while let Ok(Some(Message::Dkg { message, auth })) = read_message(&mut socket).await {
+                    let reply = match (misbehaviour, message) {
+                        (Misbehaviour::LieOnReveal, DkgMessage::Reveal { session, .. }) => {
+                            DkgMessage::Revealed { session, share: Fr::one() }
+                        }
+                        (Misbehaviour::DropDealsFrom(dropped), DkgMessage::Deal { session, dealer, .. })
+                            if dealer == dropped =>
+                        {
+                            DkgMessage::DealAccepted { session }
+                        }
+                        (_, other) => dkg_request(&transport, &backend, other, auth).await.unwrap(),
+                    };
+                    write_message(&mut socket, &Message::Dkg { message: reply, auth: None }).await.unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn complaint_against_lying_dealer_disqualifies_it() {
+    let dir = temp_dir("lying");
+    let channel = Channel::new(&dir);
+    let (mut addrs, nodes) = spawn_committee(&dir, &channel, 4).await;
+    // 参与者 2 收不到分发者 1 的份额而投诉，分发者 1 公开的份额无效
+    addrs[0] = spawn_proxy(&channel, addrs[0].clone(), Misbehaviour::LieOnReveal).await;
+    addrs[1] = spawn_proxy(&channel, addrs[1].clone(), Misbehaviour::DropDealsFrom(1)).await;
+
+    let outcome = run_dkg(&channel.coordinator(), "lying", 3, &addrs, "lying-group").await.unwrap();
+    assert_eq!(outcome.qualified, vec![2, 3, 4]);
+    assert!(outcome.registration.verify());
+    threshold_sign(&nodes, "lying", 3, &outcome.group).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn withheld_share_is_resolved_by_honest_reveal() {
+    let dir = temp_dir("reveal");
+    let channel = Channel::new(&dir);
+    let (mut addrs, nodes) = spawn_committee(&dir, &channel, 3).await;
+    addrs[2] = spawn_proxy(&channel, addrs[2].clone(), Misbehaviour::DropDealsFrom(1)).await;
+
+    let outcome = run_dkg(&channel.coordinator(), "reveal", 2, &addrs, "reveal-group").await.unwrap();
+    assert_eq!(outcome.qualified, vec![1, 2, 3]);
+    threshold_sign(&nodes, "reveal", 2, &outcome.group).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn forged_deal_does_not_displace_the_real_one() {
+    let dir = temp_dir("forged");
+    let channel = Channel::new(&dir);
+    let (addrs, nodes) = spawn_committee(&dir, &channel, 3).await;
+
+    // 会话开始前以分发者 1 的名义向参与者 2、3 投递无效份额。若先到的份额生效，两次投诉需要分发者 1
+    // 公开两个份额，超出门限为 2 时允许的数量而被取消资格; Verify 轮按统一承诺选用真实的份额
+    for addr in &addrs[1..] {
+        let forged = DkgMessage::Deal {
+            session: "forged".to_string(),
+            dealer: 1,
+            commitments: vec![PublicKey(G2::one()); 2],
+            share: Fr::one(),
+        };
+        let reply = dkg_request(&channel.transport(), addr, forged, None).await.unwrap();
+        assert!(matches!(reply, DkgMessage::DealAccepted { .. }));
+    }
+
+    let outcome = run_dkg(&channel.coordinator(), "forged", 2, &addrs, "forged-group").await.unwrap();
+    assert_eq!(outcome.qualified, vec![1, 2, 3]);
+    threshold_sign(&nodes, "forged", 2, &outcome.group).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn deal(session: &str, dealer: u32, threshold: usize) -> DkgMessage {
+    DkgMessage::Deal { session: session.to_string(), dealer, commitments: vec![PublicKey(G2::one()); threshold], share: Fr::one() }
+}
+
+#[tokio::test]
+async fn deals_are_checked_against_the_session() {
+    let dir = temp_dir("deal-checks");
+    let channel = Channel::new(&dir);
+    let dkg = DkgNode::new(dir.clone(), Zeroizing::new(PASSWORD.to_string())).with_transport(channel.transport());
+    let keys = KeySchedule::new(Arc::new(Signer::random()));
+
+    // 其他参与者不在线，份额送达失败不影响会话开始
+    let participants: Vec<Participant> =
+        (1..=3).map(|index| Participant { index, address: free_addr() }).collect();
+    let start = DkgMessage::Start { session: "checked".to_string(), threshold: 2, index: 1, participants };
+    assert!(matches!(dkg.handle(start, &keys).await.unwrap(), DkgMessage::Dealt { .. }));
+
+    // 已开始的会话只接受参与者的份额，承诺长度必须等于门限
+    assert!(matches!(dkg.handle(deal("checked", 2, 2), &keys).await.unwrap(), DkgMessage::DealAccepted { .. }));
+    assert!(dkg.handle(deal("checked", 4, 2), &keys).await.is_err());
+    assert!(dkg.handle(deal("checked", 3, 3), &keys).await.is_err());
+    assert!(dkg.handle(deal("../checked", 2, 2), &keys).await.is_err());
+
+    // 尚未开始的会话可以暂存份额，但会话数有上限
+    for i in 0..16 {
+        let reply = dkg.handle(deal(&format!("pending-{}", i), 2, 2), &keys).await.unwrap();
+        assert!(matches!(reply, DkgMessage::DealAccepted { .. }));
+    }
+    assert!(dkg.handle(deal("pending-0", 3, 2), &keys).await.is_ok());
+    assert!(dkg.handle(deal("pending-16", 2, 2), &keys).await.is_err());
+    // 会话开始后不再占用暂存名额
+    let participants: Vec<Participant> =
+        (1..=3).map(|index| Participant { index, address: free_addr() }).collect();
+    let start = DkgMessage::Start { session: "pending-0".to_string(), threshold: 2, index: 1, participants };
+    dkg.handle(start, &keys).await.unwrap();
+    assert!(dkg.handle(deal("pending-16", 2, 2), &keys).await.is_ok());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,265 @@
+// 按纪元轮换密钥: 登记表保存公钥历史，纪元 N 的签名按纪元 N 的公钥集合验证
+use std::sync::Arc;
+use dss_core::{Aggregator, Signer, Verifier};
+use dss_core::collector::KeyRegistration;
+use dss_core::epoch::{epoch_at, epoch_keystore_path, load_epoch_keys, EpochInfo, KeySchedule};
+use dss_core::hash::HashScheme;
+use dss_core::keystore;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::pop::prove_deregistration;
+use dss_core::registry::{NodeRecord, RegisterOutcome, Registry, RegistryError};
+mod common;
+use common::spawn_node;
+
+const MESSAGE: &[u8] = b"epoch message";
+
+fn registration(signer: &Signer, node_id: &str, epoch: Option<u64>, nonce: u64) -> KeyRegistration {
+    KeyRegistration::at(signer, node_id, &format!("{}:9000", node_id), epoch, nonce).unwrap()
+}
+
+// 由节点当前登记的密钥授权的轮换
+fn rotation(signer: &Signer, current: &Signer, node_id: &str, epoch: Option<u64>, nonce: u64) -> KeyRegistration {
+    registration(signer, node_id, epoch, nonce).authorize(current).unwrap()
+}
+
+#[test]
+fn epochs_follow_the_collector_clock() {
+    assert_eq!(epoch_at(0, 1000), 0);
+    assert_eq!(epoch_at(2999, 1000), 2);
+    assert_eq!(epoch_at(3000, 1000), 3);
+    let info = EpochInfo::at(3500, 1000);
+    assert_eq!((info.epoch, info.next_epoch_at), (3, 4000));
+}
+
+#[test]
+fn rotated_key_takes_effect_in_a_future_epoch() {
+    let old = Signer::random();
+    let new = Signer::random();
+    let other = Signer::random();
+    let mut registry = Registry::new();
+    assert_eq!(registry.register(&registration(&old, "a", None, 1), 1, 5).unwrap(), RegisterOutcome::Created);
+    assert_eq!(registry.register(&registration(&other, "b", None, 1), 1, 5).unwrap(), RegisterOutcome::Created);
+
+    // 已开始的纪元不能更换公钥，默认安排在下一纪元
+    assert_eq!(
+        registry.register(&rotation(&new, &old, "a", Some(5), 2), 2, 5),
+        Err(RegistryError::EpochStarted { epoch: 5, current: 5 })
+    );
+    assert_eq!(registry.register(&rotation(&new, &old, "a", None, 2), 2, 5).unwrap(), RegisterOutcome::Updated);
+    let record = registry.get("a").unwrap();
+    assert_eq!(record.public_key.0, new.public_key().0);
+    assert_eq!(record.key_at(5).unwrap().0, old.public_key().0);
+    assert_eq!(record.key_at(6).unwrap().0, new.public_key().0);
+    assert!(record.key_at(4).is_none());
+
+    // 纪元 5 内原公钥仍然有效，重复登记原公钥是幂等的
+    assert_eq!(registry.register(&registration(&old, "a", None, 3), 3, 5).unwrap(), RegisterOutcome::Unchanged);
+    assert_eq!(registry.active_index_at(&old.public_key(), 5), Some(0));
+    assert_eq!(registry.active_index_at(&new.public_key(), 5), None);
+    assert_eq!(registry.active_index_at(&new.public_key(), 6), Some(0));
+    assert_eq!(registry.active_index_at(&old.public_key(), 6), None);
+    assert_eq!(registry.key_set_at(5).keys()[0].0, old.public_key().0);
+    assert_eq!(registry.key_set_at(9).keys()[0].0, new.public_key().0);
+
+    // 轮换掉的公钥不能重新启用，也不能被其他节点登记
+    assert_eq!(
+        registry.register(&rotation(&old, &new, "a", None, 4), 4, 6),
+        Err(RegistryError::KeyRetired("a".to_string()))
+    );
+    assert_eq!(
+        registry.register(&registration(&old, "c", None, 4), 4, 6),
+        Err(RegistryError::KeyInUse("a".to_string()))
+    );
+
+    // 轮换掉的公钥不能注销节点
+    let proof = prove_deregistration(&old, "a", 5).unwrap();
+    assert_eq!(registry.deregister("a", &proof, 5, 5, 6).unwrap_err(), RegistryError::InvalidProof);
+    let proof = prove_deregistration(&new, "a", 5).unwrap();
+    assert!(!registry.deregister("a", &proof, 5, 5, 6).unwrap().is_active());
+}
+
+#[test]
+fn pending_rotation_can_be_rescheduled() {
+    let first = Signer::random();
+    let second = Signer::random();
+    let third = Signer::random();
+    let mut registry = Registry::new();
+    registry.register(&registration(&first, "a", None, 1), 1, 0).unwrap();
+    registry.register(&rotation(&second, &first, "a", Some(10), 2), 2, 0).unwrap();
+    // 尚未生效的安排被更早的新安排替换
+    registry.register(&rotation(&third, &first, "a", Some(4), 3), 3, 1).unwrap();
+    let record = registry.get("a").unwrap();
+    assert_eq!(record.keys.iter().map(|key| key.epoch).collect::<Vec<_>>(), vec![0, 4]);
+    assert_eq!(record.key_at(3).unwrap().0, first.public_key().0);
+    assert_eq!(record.key_at(10).unwrap().0, third.public_key().0);
+}
+
+#[test]
+fn records_without_history_use_their_key_for_every_epoch() {
+    let signer = Signer::random();
+    let mut registry = Registry::new();
+    registry.register(&registration(&signer, "a", None, 1), 1, 7).unwrap();
+    let mut json = serde_json::to_value(registry.get("a").unwrap()).unwrap();
+    json.as_object_mut().unwrap().remove("keys");
+    let record: NodeRecord = serde_json::from_value(json).unwrap();
+    assert!(record.keys.is_empty());
+    assert_eq!(record.key_at(0).unwrap().0, signer.public_key().0);
+
+    // 旧记录轮换时原公钥视为从纪元 0 起生效
+    let mut registry = Registry::from_records(vec![record]).unwrap();
+    let new = Signer::random();
+    registry.register(&rotation(&new, &signer, "a", None, 2), 2, 7).unwrap();
+    let record = registry.get("a").unwrap();
+    assert_eq!(record.key_at(7).unwrap().0, signer.public_key().0);
+    assert_eq!(record.key_at(8).unwrap().0, new.public_key().0);
+}
+
+#[test]
+fn signatures_verify_against_their_epoch_key_set() {
+    let scheme = HashScheme::TryAndIncrement;
+    let old: Vec<Signer> = (0..3).map(|_| Signer::random()).collect();
+    let mut registry = Registry::new();
+    for (i, signer) in old.iter().enumerate() {
+        registry.register(&registration(signer, &format!("n{}", i), None, 1), 1, 2).unwrap();
+    }
+    let rotated = Signer::random();
+    registry.register(&rotation(&rotated, &old[1], "n1", None, 2), 2, 2).unwrap();
+
+    let sign = |signers: [&Signer; 3]| {
+        let mut aggregator = Aggregator::new(3);
+        for (index, signer) in signers.into_iter().enumerate() {
+            aggregator.add(index, signer.sign_with(&scheme, MESSAGE).unwrap());
+        }
+        aggregator.aggregate()
+    };
+    let in_epoch_2 = sign([&old[0], &old[1], &old[2]]);
+    let in_epoch_3 = sign([&old[0], &rotated, &old[2]]);
+
+    let verify = |epoch: u64, aggregated| {
+        Verifier::for_signers(&registry.key_set_at(epoch), aggregated).unwrap().verify_with(&scheme, MESSAGE, &aggregated.signature)
+    };
+    assert!(verify(2, &in_epoch_2));
+    assert!(!verify(3, &in_epoch_2));
+    assert!(verify(3, &in_epoch_3));
+    assert!(!verify(2, &in_epoch_3));
+}
+
+#[test]
+fn scheduled_keys_are_saved_next_to_the_keystore() {
+    let dir = std::env::temp_dir().join(format!("dss-epochs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("node.json");
+    let path = path.as_path();
+    keystore::save(path, &Signer::random(), "pw").unwrap();
+    let scheduled = [(12, Signer::random()), (3, Signer::random())];
+    for (epoch, signer) in &scheduled {
+        keystore::save(&epoch_keystore_path(path, *epoch), signer, "pw").unwrap();
+    }
+    assert_eq!(epoch_keystore_path(path, 3), dir.join("node.json.epoch-3"));
+
+    let loaded = load_epoch_keys(path, "pw").unwrap();
+    assert_eq!(loaded.iter().map(|(epoch, _)| *epoch).collect::<Vec<_>>(), vec![3, 12]);
+    assert_eq!(loaded[0].1.public_key().0, scheduled[1].1.public_key().0);
+    assert_eq!(loaded[1].1.public_key().0, scheduled[0].1.public_key().0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restarted_node_keeps_its_registered_and_scheduled_keys() {
+    let dir = std::env::temp_dir().join(format!("dss-epochs-restart-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("node.json");
+    let path = path.as_path();
+
+    // 首次启动生成并登记密钥，运行中把新密钥安排在纪元 3
+    let (first, is_new) = keystore::load_or_generate(path, "pw").unwrap();
+    assert!(is_new);
+    let mut registry = Registry::new();
+    assert_eq!(registry.register(&registration(&first, "a", None, 1), 1, 0).unwrap(), RegisterOutcome::Created);
+    let scheduled = Signer::random();
+    keystore::save(&epoch_keystore_path(path, 3), &scheduled, "pw").unwrap();
+    assert_eq!(registry.register(&rotation(&scheduled, &first, "a", Some(3), 2), 2, 1).unwrap(), RegisterOutcome::Updated);
+
+    // 纪元 2 中重启: 与节点启动时相同地加载密钥文件和安排的密钥
+    let (restarted, is_new) = keystore::load_or_generate(path, "pw").unwrap();
+    assert!(!is_new);
+    let keys = KeySchedule::new(Arc::new(restarted));
+    for (epoch, signer) in load_epoch_keys(path, "pw").unwrap() {
+        keys.insert(epoch, Arc::new(signer));
+    }
+    assert_eq!(keys.epochs(), vec![0, 3]);
+    // 不知道纪元长度时无法判断纪元 3 是否已开始，只使用已登记的密钥
+    assert_eq!(keys.signer_at(2).unwrap().public_key().0, first.public_key().0);
+    assert_eq!(keys.signer_at(3).unwrap().public_key().0, first.public_key().0);
+    let current = keys.current();
+    assert_eq!(registry.register(&registration(&current, "a", None, 3), 3, 2).unwrap(), RegisterOutcome::Unchanged);
+    assert_eq!(registry.get("a").unwrap().key_at(3).unwrap().0, scheduled.public_key().0);
+
+    // 没有密钥文件时重启得到的新密钥无法替换已登记的公钥
+    assert!(matches!(
+        registry.register(&registration(&Signer::random(), "a", None, 4), 4, 2),
+        Err(RegistryError::Unauthorized(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn node_signs_with_the_key_of_the_requested_epoch() {
+    let scheme = HashScheme::TryAndIncrement;
+    let old = Arc::new(Signer::random());
+    let keys = Arc::new(KeySchedule::new(old.clone()).with_epoch_length(60_000));
+    let current = keys.current_epoch().unwrap();
+    let addr = spawn_node(NodeService::new(keys.clone())).await;
+
+    // 运行中加入的密钥在其纪元到达后使用
+    let new = Arc::new(Signer::random());
+    keys.insert(current - 10, new.clone());
+    assert_eq!(keys.epochs(), vec![0, current - 10]);
+    for (epoch, expected) in [(Some(3), &old), (Some(current - 10), &new), (Some(current), &new), (None, &new)] {
+        let options = SignOptions::new(scheme.clone()).with_epoch(epoch);
+        let node_signature = request_signature(&addr, MESSAGE, &options).await.unwrap();
+        assert_eq!(node_signature.public_key.0, expected.public_key().0, "epoch {:?}", epoch);
+        assert!(dss_core::verifier::verify_signature_with(&scheme, MESSAGE, &node_signature.signature, &expected.public_key()));
+    }
+    let node_signature = request_signature(&addr, MESSAGE, &SignOptions::new(scheme)).await.unwrap();
+    assert_eq!(node_signature.public_key.0, new.public_key().0);
+}
+
+#[tokio::test]
+async fn node_refuses_to_sign_with_a_key_scheduled_for_a_future_epoch() {
+    let scheme = HashScheme::TryAndIncrement;
+    let old = Arc::new(Signer::random());
+    let keys = Arc::new(KeySchedule::new(old.clone()).with_epoch_length(60_000));
+    let current = keys.current_epoch().unwrap();
+    let scheduled = Arc::new(Signer::random());
+    keys.insert(current + 5, scheduled.clone());
+    assert!(keys.signer_at(current + 5).is_none());
+    assert!(keys.signer_at(current + 1).is_none());
+    assert_eq!(keys.signer_at(current).unwrap().public_key().0, old.public_key().0);
+    assert_eq!(keys.current().public_key().0, old.public_key().0);
+
+    let addr = spawn_node(NodeService::new(keys.clone())).await;
+
+    let options = SignOptions::new(scheme);
+    let err = request_signature(&addr, MESSAGE, &options.clone().with_epoch(Some(current + 5))).await.unwrap_err();
+    assert!(err.to_string().contains("has not started"), "{}", err);
+    let node_signature = request_signature(&addr, MESSAGE, &options.with_epoch(Some(current))).await.unwrap();
+    assert_eq!(node_signature.public_key.0, old.public_key().0);
+}
+
+#[test]
+fn scheduled_key_does_not_match_before_its_epoch() {
+    let old = Signer::random();
+    let scheduled = Signer::random();
+    let mut registry = Registry::new();
+    assert_eq!(registry.register(&registration(&old, "a", None, 1), 1, 5).unwrap(), RegisterOutcome::Created);
+    assert_eq!(registry.register(&rotation(&scheduled, &old, "a", Some(8), 2), 2, 5).unwrap(), RegisterOutcome::Updated);
+
+    // 最近登记的公钥是安排在纪元 8 的公钥，纪元 5 到 7 仍只接受原公钥
+    assert_eq!(registry.get("a").unwrap().public_key.0, scheduled.public_key().0);
+    for epoch in 5..8 {
+        assert_eq!(registry.active_index_at(&scheduled.public_key(), epoch), None, "epoch {}", epoch);
+        assert_eq!(registry.active_index_at(&old.public_key(), epoch), Some(0), "epoch {}", epoch);
+    }
+    assert_eq!(registry.active_index_at(&scheduled.public_key(), 8), Some(0));
+}
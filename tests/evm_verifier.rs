@@ -0,0 +1,241 @@
+// 在嵌入式 EVM (revm) 中部署验证合约，确认节点服务产生的聚合签名
+// 能通过 ecAdd / ecMul / ecPairing 预编译合约 (0x06 - 0x08) 验证，且篡改后验证失败
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use revm::db::InMemoryDB;
+use revm::primitives::{Address, ExecutionResult, Output, TxKind};
+use substrate_bn::{Group, G1};
+use dss_core::{AggregatedSignature, Aggregator, KeySet, Signer, Verifier};
+use dss_core::evm::{self, g1_words, VerifyCall, Word};
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+mod common;
+use common::spawn_node;
+
+const MESSAGE: &[u8] = b"bridge transfer #42";
+
+struct Evm {
+    evm: revm::Evm<'static, (), InMemoryDB>,
+}
+
+impl Evm {
+    fn new() -> Self {
+        let evm = revm::Evm::builder().with_db(InMemoryDB::default()).build();
+        Evm { evm }
+    }
+
+    fn execute(&mut self, transact_to: TxKind, data: Vec<u8>) -> ExecutionResult {
+        self.evm.tx_mut().transact_to = transact_to;
+        self.evm.tx_mut().data = data.into();
+        self.evm.transact_commit().expect("evm transaction")
+    }
+
+    fn deploy(&mut self, init_code: Vec<u8>) -> Address {
+        match self.execute(TxKind::Create, init_code) {
+            ExecutionResult::Success { output: Output::Create(_, Some(address)), .. } => address,
+            other => panic!("deployment failed: {:?}", other),
+        }
+    }
+
+    // 成功时返回输出，revert 或 halt 时返回 None
+    fn call(&mut self, to: Address, data: Vec<u8>) -> Option<Vec<u8>> {
+        match self.execute(TxKind::Call(to), data) {
+            ExecutionResult::Success { output: Output::Call(bytes), .. } => Some(bytes.to_vec()),
+            _ => None,
+        }
+    }
+
+    fn verify(&mut self, verifier: Address, call: &VerifyCall) -> bool {
+        let output = self.call(verifier, call.encode().unwrap()).expect("verify call reverted");
+        assert_eq!(output.len(), 32);
+        output[..31].iter().all(|b| *b == 0) && output[31] == 1
+    }
+}
+
+fn precompile(number: u8) -> Address {
+    let mut address = [0u8; 20];
+    address[19] = number;
+    Address::from(address)
+}
+
+fn concat(words: &[Word]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.iter().copied()).collect()
+}
+
+fn committee(size: usize) -> Vec<Arc<Signer>> {
+    (0..size).map(|_| Arc::new(Signer::random())).collect()
+}
+
+// 通过节点服务收集 signer_indices 中节点的签名并聚合
+async fn sign_through_nodes(
+    signers: &[Arc<Signer>],
+    signer_indices: &[usize],
+    scheme: &HashScheme,
+    message: &[u8],
+) -> (KeySet, AggregatedSignature) {
+    let key_set = KeySet::new(signers.iter().map(|signer| signer.public_key()).collect());
+    let mut aggregator = Aggregator::new(signers.len());
+    for &index in signer_indices {
+        let addr = spawn_node(NodeService::from_signer(signers[index].clone())).await;
+        let node_signature = request_signature(&addr, message, &SignOptions::new(scheme.clone())).await.unwrap();
+        assert_eq!(key_set.position(&node_signature.public_key), Some(index));
+        assert!(aggregator.add(index, node_signature.signature));
+    }
+    (key_set, aggregator.aggregate())
+}
+
+fn verify_call(key_set: &KeySet, aggregated: &AggregatedSignature, scheme: &HashScheme, message: &[u8]) -> VerifyCall {
+    let verifier = Verifier::for_signers(key_set, aggregated).unwrap();
+    assert!(verifier.verify_with(scheme, message, &aggregated.signature));
+    VerifyCall::new(scheme, message, aggregated.signature, verifier.aggregated_public_key()).unwrap()
+}
+
+async fn check_scheme(scheme: HashScheme) {
+    let signers = committee(3);
+    let (key_set, aggregated) = sign_through_nodes(&signers, &[0, 1, 2], &scheme, MESSAGE).await;
+    let call = verify_call(&key_set, &aggregated, &scheme, MESSAGE);
+
+    let mut evm = Evm::new();
+    let verifier = evm.deploy(evm::verifier_init_code().unwrap());
+    assert!(evm.verify(verifier, &call), "valid aggregated signature rejected under {}", scheme);
+
+    // 篡改签名
+    let mut tampered = call;
+    tampered.signature.0 = tampered.signature.0 + G1::one();
+    assert!(!evm.verify(verifier, &tampered));
+
+    // 其他消息的哈希点
+    let mut tampered = call;
+    tampered.message_point = scheme.hash_to_g1(b"bridge transfer #43").unwrap();
+    assert!(!evm.verify(verifier, &tampered));
+
+    // 缺少一个签名者的聚合公钥
+    let mut tampered = call;
+    tampered.public_key = signers[0].public_key() + signers[1].public_key();
+    assert!(!evm.verify(verifier, &tampered));
+
+    // 不在曲线上的签名点: 预编译合约失败，验证合约返回 false
+    let mut calldata = call.encode().unwrap();
+    calldata[4 + 31] ^= 1;
+    let output = evm.call(verifier, calldata).expect("verify call reverted");
+    assert_eq!(output, vec![0u8; 32]);
+}
+
+#[tokio::test]
+async fn aggregated_signature_verifies_with_try_and_increment() {
+    check_scheme(HashScheme::TryAndIncrement).await;
+}
+
+#[tokio::test]
+async fn aggregated_signature_verifies_with_svdw() {
+    check_scheme("svdw".parse().unwrap()).await;
+}
+
+#[tokio::test]
+async fn partial_signer_set_verifies() {
+    let scheme = HashScheme::TryAndIncrement;
+    let signers = committee(4);
+    let (key_set, aggregated) = sign_through_nodes(&signers, &[1, 3], &scheme, MESSAGE).await;
+    assert_eq!(aggregated.signers.indices(), vec![1, 3]);
+    let call = verify_call(&key_set, &aggregated, &scheme, MESSAGE);
+
+    let mut evm = Evm::new();
+    let verifier = evm.deploy(evm::verifier_init_code().unwrap());
+    assert!(evm.verify(verifier, &call));
+
+    // 用全部公钥的聚合公钥验证部分签名必须失败
+    let mut tampered = call;
+    tampered.public_key = key_set.aggregate();
+    assert!(!evm.verify(verifier, &tampered));
+}
+
+// ecAdd / ecMul 的结果与 Rust 端的点运算和坐标编码一致
+#[tokio::test]
+async fn point_arithmetic_matches_precompiles() {
+    let scheme = HashScheme::TryAndIncrement;
+    let signers = committee(2);
+    let mut evm = Evm::new();
+
+    let point = scheme.hash_to_g1(MESSAGE).unwrap();
+    let mut signatures = Vec::new();
+    for signer in &signers {
+        let signature = signer.sign_with(&scheme, MESSAGE).unwrap();
+        let mut input = concat(&g1_words(&point).unwrap());
+        input.extend_from_slice(&signer.to_bytes().unwrap());
+        let output = evm.call(precompile(0x07), input).expect("ecMul failed");
+        assert_eq!(output, concat(&g1_words(&signature.0).unwrap()));
+        signatures.push(signature);
+    }
+
+    let mut input = concat(&g1_words(&signatures[0].0).unwrap());
+    input.extend(concat(&g1_words(&signatures[1].0).unwrap()));
+    let output = evm.call(precompile(0x06), input).expect("ecAdd failed");
+    assert_eq!(output, concat(&g1_words(&(signatures[0] + signatures[1]).0).unwrap()));
+}
+
+#[test]
+fn unknown_selector_and_short_calldata_revert() {
+    let mut evm = Evm::new();
+    let verifier = evm.deploy(evm::verifier_init_code().unwrap());
+    assert!(evm.call(verifier, vec![0u8; 260]).is_none());
+    assert!(evm.call(verifier, evm::VERIFY_SELECTOR.to_vec()).is_none());
+}
+
+// 使用本机的 solc 编译生成的 Solidity 合约; 找不到 solc 或编译失败时测试失败
+fn compile_with_solc(source: &str) -> Vec<u8> {
+    let mut child = Command::new("solc")
+        .args(["--bin", "--optimize", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .expect("solc not found on PATH");
+    child.stdin.take().unwrap().write_all(source.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "solc failed to compile the generated verifier");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    lines.find(|line| line.trim() == "Binary:").expect("solc printed no binary");
+    hex::decode(lines.next().expect("solc printed no binary").trim()).unwrap()
+}
+
+// 生成的合约与手写的验证合约对同一组调用给出相同的结果
+#[tokio::test]
+#[ignore = "requires solc on PATH; run with `cargo test -- --ignored`"]
+async fn generated_solidity_contract_matches_handwritten_verifier() {
+    let init_code = compile_with_solc(&evm::solidity_verifier("DssBlsVerifier").unwrap());
+
+    let scheme: HashScheme = "svdw".parse().unwrap();
+    let signers = committee(3);
+    let (key_set, aggregated) = sign_through_nodes(&signers, &[0, 2], &scheme, MESSAGE).await;
+    let call = verify_call(&key_set, &aggregated, &scheme, MESSAGE);
+
+    let mut evm = Evm::new();
+    let generated = evm.deploy(init_code);
+    let handwritten = evm.deploy(evm::verifier_init_code().unwrap());
+    assert!(evm.verify(generated, &call));
+
+    let mut tampered_signature = call;
+    tampered_signature.signature.0 = tampered_signature.signature.0 + G1::one();
+    let mut tampered_message = call;
+    tampered_message.message_point = scheme.hash_to_g1(b"another message").unwrap();
+    let mut wrong_key = call;
+    wrong_key.public_key = key_set.aggregate();
+    let mut off_curve = call.encode().unwrap();
+    off_curve[4 + 31] ^= 1;
+
+    let cases = [
+        ("valid", call.encode().unwrap()),
+        ("tampered signature", tampered_signature.encode().unwrap()),
+        ("tampered message point", tampered_message.encode().unwrap()),
+        ("wrong public key", wrong_key.encode().unwrap()),
+        ("off-curve signature", off_curve),
+        ("unknown selector", vec![0u8; 260]),
+        ("short calldata", evm::VERIFY_SELECTOR.to_vec()),
+    ];
+    for (name, calldata) in cases {
+        let expected = evm.call(handwritten, calldata.clone());
+        assert_eq!(evm.call(generated, calldata), expected, "{}", name);
+    }
+}
@@ -0,0 +1,109 @@
+// 消息到 G1 的两种哈希方式: RFC 9380 的测试向量、域分隔以及签名验证
+use substrate_bn::{AffineG1, Fq, G1};
+use dss_core::Signer;
+use dss_core::hash::{expand_message_xmd, HashScheme, DEFAULT_DST};
+use dss_core::verifier::verify_signature_with;
+
+const EXPANDER_DST: &[u8] = b"QUUX-V01-CS02-with-expander-SHA256-128";
+const SVDW_DST: &str = "QUUX-V01-CS02-with-BN254G1_XMD:SHA-256_SVDW_RO_";
+
+fn point(x: &str, y: &str) -> G1 {
+    let x = Fq::from_slice(&hex::decode(x).unwrap()).unwrap();
+    let y = Fq::from_slice(&hex::decode(y).unwrap()).unwrap();
+    AffineG1::new(x, y).unwrap().into()
+}
+
+#[test]
+fn expand_message_matches_rfc_vectors() {
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"", EXPANDER_DST, 0x20).unwrap()),
+        "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235"
+    );
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"abc", EXPANDER_DST, 0x20).unwrap()),
+        "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615"
+    );
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"", EXPANDER_DST, 0x80).unwrap()),
+        "af84c27ccfd45d41914fdff5df25293e221afc53d8ad2ac06d5e3e29485dadbee0d121587713a3e0dd4d5e69e93eb7cd\
+         4f5df4cd103e188cf60cb02edc3edf18eda8576c412b18ffb658e3dd6ec849469b979d444cf7b26911a08e63cf31f9dc\
+         c541708d3491184472c2c29bb749d4286b004ceb5ee6b9a7fa5b646c993f0ced"
+    );
+    assert!(expand_message_xmd(b"", EXPANDER_DST, 256 * 32).is_err());
+}
+
+#[test]
+fn svdw_matches_gnark_vectors() {
+    let scheme = HashScheme::svdw(SVDW_DST).unwrap();
+    assert_eq!(
+        scheme.hash_to_g1(b"").unwrap(),
+        point(
+            "0a976ab906170db1f9638d376514dbf8c42aef256a54bbd48521f20749e59e86",
+            "02925ead66b9e68bfc309b014398640ab55f6619ab59bc1fab2210ad4c4d53d5"
+        )
+    );
+    assert_eq!(
+        scheme.hash_to_g1(b"abc").unwrap(),
+        point(
+            "23f717bee89b1003957139f193e6be7da1df5f1374b26a4643b0378b5baf53d1",
+            "04142f826b71ee574452dbc47e05bc3e1a647478403a7ba38b7b93948f4e151d"
+        )
+    );
+}
+
+#[test]
+fn try_and_increment_matches_bn254_vectors() {
+    let scheme = HashScheme::TryAndIncrement;
+    for (message, expected) in [
+        (&b"sample"[..], "0211e028f08c500889891cc294fe758a60e84495ec1e2d0bce208c9fc67b6486fd"),
+        (&b"hello"[..], "0200b201235f522abbd3863b7496dfa213be0ed1f4c7a22196d8afddec7e64c8ec"),
+    ] {
+        assert_eq!(scheme.hash_to_g1(message).unwrap(), G1::from_compressed(&hex::decode(expected).unwrap()).unwrap());
+    }
+}
+
+#[test]
+fn schemes_are_deterministic_and_domain_separated() {
+    let svdw = HashScheme::svdw(DEFAULT_DST).unwrap();
+    let other = HashScheme::svdw("another-dst").unwrap();
+    let try_and_increment = HashScheme::TryAndIncrement;
+    for scheme in [&svdw, &other, &try_and_increment] {
+        assert_eq!(scheme.hash_to_g1(b"message").unwrap(), scheme.hash_to_g1(b"message").unwrap());
+        assert_ne!(scheme.hash_to_g1(b"message").unwrap(), scheme.hash_to_g1(b"massage").unwrap());
+    }
+    assert_ne!(svdw.hash_to_g1(b"message").unwrap(), other.hash_to_g1(b"message").unwrap());
+    assert_ne!(svdw.hash_to_g1(b"message").unwrap(), try_and_increment.hash_to_g1(b"message").unwrap());
+}
+
+#[test]
+fn signatures_only_verify_under_their_scheme() {
+    let signer = Signer::random();
+    let svdw = HashScheme::svdw(DEFAULT_DST).unwrap();
+    let other = HashScheme::svdw("another-dst").unwrap();
+    let try_and_increment = HashScheme::TryAndIncrement;
+    for scheme in [&svdw, &try_and_increment] {
+        let signature = signer.sign_with(scheme, b"message").unwrap();
+        assert!(verify_signature_with(scheme, b"message", &signature, &signer.public_key()));
+        assert!(!verify_signature_with(scheme, b"other", &signature, &signer.public_key()));
+    }
+    let signature = signer.sign_with(&svdw, b"message").unwrap();
+    assert!(!verify_signature_with(&try_and_increment, b"message", &signature, &signer.public_key()));
+    assert!(!verify_signature_with(&other, b"message", &signature, &signer.public_key()));
+}
+
+#[test]
+fn schemes_parse_and_serialize() {
+    assert_eq!("try_and_increment".parse::<HashScheme>().unwrap(), HashScheme::TryAndIncrement);
+    assert_eq!("svdw".parse::<HashScheme>().unwrap(), HashScheme::svdw(DEFAULT_DST).unwrap());
+    let scheme: HashScheme = "svdw:my-dst".parse().unwrap();
+    assert_eq!(scheme.to_string(), "svdw:my-dst");
+    assert_eq!(scheme.to_string().parse::<HashScheme>().unwrap(), scheme);
+    assert!("svdw:".parse::<HashScheme>().is_err());
+    assert!(format!("svdw:{}", "x".repeat(256)).parse::<HashScheme>().is_err());
+    assert!("sha256".parse::<HashScheme>().is_err());
+
+    let json = serde_json::to_value(&scheme).unwrap();
+    assert_eq!(json, serde_json::json!({ "name": "svdw", "dst": "my-dst" }));
+    assert_eq!(serde_json::from_value::<HashScheme>(json).unwrap(), scheme);
+    assert_eq!(serde_json::to_value(HashScheme::TryAndIncrement).unwrap(), serde_json::json!({ "name": "try_and_increment" }));
+}
@@ -0,0 +1,155 @@
+// 口令加密的密钥文件: 加解密往返、错误口令、篡改的信封、KDF 参数上限以及文件权限
+use std::path::PathBuf;
+use dss_core::Signer;
+use dss_core::keystore::{self, read_password, EncryptedKeystore};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-keystore-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Signer 不实现 Debug，不能直接 unwrap_err
+fn decrypt_error(envelope: &EncryptedKeystore, password: &str) -> String {
+    match envelope.decrypt(password) {
+        Ok(_) => panic!("keystore decrypted with {:?}", password),
+        Err(e) => e.to_string(),
+    }
+}
+
+fn is_wrong_passphrase(e: String) -> bool {
+    e.contains("wrong passphrase or corrupted keystore")
+}
+
+#[test]
+fn keys_round_trip_through_the_envelope() {
+    let signer = Signer::random();
+    let envelope = EncryptedKeystore::encrypt(&signer, "correct horse").unwrap();
+    assert_eq!(envelope.public_key().unwrap().0, signer.public_key().0);
+    assert_eq!((envelope.kdf.log_n, envelope.kdf.r, envelope.kdf.p), (15, 8, 1));
+
+    let decrypted = envelope.decrypt("correct horse").unwrap();
+    assert_eq!(decrypted.to_bytes().unwrap(), signer.to_bytes().unwrap());
+    // 每次加密使用新的盐和随机数
+    let again = EncryptedKeystore::encrypt(&signer, "correct horse").unwrap();
+    assert_ne!(again.kdf.salt, envelope.kdf.salt);
+    assert_ne!(again.ciphertext, envelope.ciphertext);
+
+    assert!(is_wrong_passphrase(decrypt_error(&envelope, "wrong horse")));
+}
+
+#[test]
+fn tampered_envelopes_fail_to_decrypt() {
+    let signer = Signer::random();
+    let envelope = EncryptedKeystore::encrypt(&signer, "pass").unwrap();
+
+    // 公钥是附加数据，换成其他公钥后认证失败
+    let mut tampered = envelope.clone();
+    tampered.public_key = EncryptedKeystore::encrypt(&Signer::random(), "pass").unwrap().public_key;
+    assert!(is_wrong_passphrase(decrypt_error(&tampered, "pass")));
+
+    let mut tampered = envelope.clone();
+    let mut ciphertext = hex::decode(&tampered.ciphertext).unwrap();
+    ciphertext[0] ^= 1;
+    tampered.ciphertext = hex::encode(ciphertext);
+    assert!(is_wrong_passphrase(decrypt_error(&tampered, "pass")));
+
+    let mut tampered = envelope.clone();
+    tampered.kdf.salt = hex::encode([0u8; 32]);
+    assert!(is_wrong_passphrase(decrypt_error(&tampered, "pass")));
+
+    let mut tampered = envelope.clone();
+    tampered.cipher.nonce = "00".to_string();
+    assert!(decrypt_error(&tampered, "pass").contains("nonce length"));
+
+    let mut tampered = envelope;
+    tampered.version = 2;
+    assert!(decrypt_error(&tampered, "pass").contains("unsupported keystore version"));
+}
+
+#[test]
+fn oversized_kdf_parameters_are_rejected() {
+    let envelope = EncryptedKeystore::encrypt(&Signer::random(), "pass").unwrap();
+    let cases = [(21, 8, 1), (15, 0, 1), (15, 33, 1), (15, 8, 0), (15, 8, 17), (20, 16, 1)];
+    for (log_n, r, p) in cases {
+        let mut tampered = envelope.clone();
+        tampered.kdf.log_n = log_n;
+        tampered.kdf.r = r;
+        tampered.kdf.p = p;
+        let e = decrypt_error(&tampered, "pass");
+        assert!(e.starts_with("scrypt"), "log_n {} r {} p {}: {}", log_n, r, p, e);
+    }
+}
+
+#[test]
+fn keystore_files_are_private() {
+    let dir = temp_dir("files");
+    let path = dir.join("node.json");
+    let (signer, is_new) = keystore::load_or_generate(&path, "pass").unwrap();
+    assert!(is_new);
+    let (loaded, is_new) = keystore::load_or_generate(&path, "pass").unwrap();
+    assert!(!is_new);
+    assert_eq!(loaded.public_key().0, signer.public_key().0);
+    assert!(keystore::load(&path, "other").is_err());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &PathBuf| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        // 覆盖已存在的宽松权限文件时同样收紧为 0600
+        let existing = dir.join("existing.json");
+        std::fs::write(&existing, "{}").unwrap();
+        std::fs::set_permissions(&existing, std::fs::Permissions::from_mode(0o644)).unwrap();
+        keystore::save(&existing, &signer, "pass").unwrap();
+        assert_eq!(mode(&existing), 0o600);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keystore_is_replaced_atomically() {
+    let dir = temp_dir("atomic");
+    let path = dir.join("node.json");
+    let tmp = dir.join("node.json.tmp");
+    let first = Signer::random();
+    keystore::save(&path, &first, "pass").unwrap();
+
+    // 临时文件无法写入时保存失败，原文件不受影响
+    std::fs::create_dir(&tmp).unwrap();
+    assert!(keystore::save(&path, &Signer::random(), "pass").is_err());
+    assert_eq!(keystore::load(&path, "pass").unwrap().public_key().0, first.public_key().0);
+    std::fs::remove_dir(&tmp).unwrap();
+
+    // 覆盖时写入新文件再替换，不在原文件上截断重写，也不留下临时文件
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(&path).unwrap().ino()
+    };
+    let second = Signer::random();
+    keystore::save(&path, &second, "pass").unwrap();
+    assert_eq!(keystore::load(&path, "pass").unwrap().public_key().0, second.public_key().0);
+    assert!(!tmp.exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_ne!(metadata.ino(), inode);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn passphrases_are_read_from_file_or_environment() {
+    let dir = temp_dir("password");
+    let path = dir.join("password");
+    std::fs::write(&path, "secret\r\n").unwrap();
+    assert_eq!(read_password(Some(&path), "DSS_TEST_UNSET_PASSWORD").unwrap().as_str(), "secret");
+    std::fs::write(&path, "\n").unwrap();
+    assert!(read_password(Some(&path), "DSS_TEST_UNSET_PASSWORD").is_err());
+    assert!(read_password(None, "DSS_TEST_UNSET_PASSWORD").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
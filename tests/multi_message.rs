@@ -0,0 +1,103 @@
+// 多消息聚合签名: 每个签名者签自己的消息，聚合签名按 (公钥, 消息) 列表用多配对验证
+use std::sync::Arc;
+use dss_core::{Aggregator, KeySet, Quorum, Signer, Verifier};
+use dss_core::aggregator::aggregate_signatures;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::request::{now_millis, NodeOutcome, RequestRecord, SigningMode};
+use dss_core::verifier::verify_multi_message;
+mod common;
+use common::spawn_node;
+
+const OBSERVATIONS: [&[u8]; 4] = [b"price=101", b"price=102", b"price=100", b"price=101"];
+
+#[test]
+fn aggregate_verifies_against_each_signers_message() {
+    let scheme = HashScheme::TryAndIncrement;
+    let signers: Vec<Signer> = (0..OBSERVATIONS.len()).map(|_| Signer::random()).collect();
+    let signatures: Vec<_> = signers
+        .iter()
+        .zip(OBSERVATIONS)
+        .map(|(signer, message)| signer.sign_with(&scheme, message).unwrap())
+        .collect();
+    let aggregated = aggregate_signatures(&signatures);
+
+    let pairs: Vec<_> = signers.iter().map(|signer| signer.public_key()).zip(OBSERVATIONS).collect();
+    assert!(verify_multi_message(&scheme, &aggregated, &pairs));
+
+    // 消息与签名者对应错误、缺少签名者或没有签名者时都不通过
+    let mut swapped = pairs.clone();
+    swapped[0].1 = OBSERVATIONS[1];
+    swapped[1].1 = OBSERVATIONS[0];
+    assert!(!verify_multi_message(&scheme, &aggregated, &swapped));
+    assert!(!verify_multi_message(&scheme, &aggregated, &pairs[1..]));
+    assert!(!verify_multi_message(&scheme, &aggregated, &[]));
+
+    // 签名者 0 和 3 的观测相同，仍按各自的公钥验证
+    let partial = aggregate_signatures(&[signatures[0], signatures[3]]);
+    assert!(verify_multi_message(&scheme, &partial, &[pairs[0], pairs[3]]));
+}
+
+#[test]
+fn request_record_lists_messages_in_signer_order() {
+    let scheme = HashScheme::TryAndIncrement;
+    let signers: Vec<Signer> = (0..4).map(|_| Signer::random()).collect();
+    let registry = KeySet::new(signers.iter().map(|signer| signer.public_key()).collect());
+    let nodes = ["node-a", "node-b", "node-c", "node-d"];
+    let messages = nodes
+        .iter()
+        .zip(OBSERVATIONS)
+        .map(|(node, message)| (node.to_string(), String::from_utf8(message.to_vec()).unwrap()))
+        .collect();
+    let mut record = RequestRecord::with_messages("multi", messages, &scheme, &Quorum::Count(3)).unwrap();
+    assert!(matches!(record.signing_mode, SigningMode::MultiMessage));
+    assert_eq!(record.missing_nodes().len(), 4);
+    assert_eq!(record.node_message("node-c"), "price=100");
+
+    // 节点在登记表中的位置与节点名的顺序不同
+    let registry_index = [2, 0, 3, 1];
+    let mut aggregator = Aggregator::new(registry.len());
+    for (i, node) in nodes.iter().enumerate().take(3) {
+        let signature = signers[registry_index[i]].sign_with(&scheme, OBSERVATIONS[i]).unwrap();
+        assert!(aggregator.add(registry_index[i], signature));
+        let outcome = NodeOutcome::Signed { signer_index: registry_index[i], signature, at: now_millis() };
+        record.record_node(node, outcome);
+    }
+    record.complete(aggregator.aggregate());
+
+    let signed = record.signed_messages();
+    assert_eq!(signed, vec![(0, "price=102"), (2, "price=101"), (3, "price=100")]);
+    let aggregated = record.result.clone().unwrap();
+    let verifier = Verifier::for_signers(&registry, &aggregated).unwrap();
+    let messages: Vec<&[u8]> = signed.iter().map(|(_, message)| message.as_bytes()).collect();
+    assert!(verifier.verify_messages(&scheme, &messages, &aggregated.signature));
+    assert!(!verifier.verify_messages(&scheme, &messages[..2], &aggregated.signature));
+    assert!(!verifier.verify_with(&scheme, messages[0], &aggregated.signature));
+
+    // 记录经 JSON 往返后保留各节点的消息
+    let restored: RequestRecord = serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+    assert_eq!(restored.signed_messages(), signed);
+}
+
+#[tokio::test]
+async fn nodes_sign_their_own_observations() {
+    let scheme = HashScheme::TryAndIncrement;
+    let mut addrs = Vec::new();
+    for _ in 0..OBSERVATIONS.len() {
+        addrs.push(spawn_node(NodeService::from_signer(Arc::new(Signer::random()))).await);
+    }
+
+    let mut signatures = Vec::new();
+    let mut pairs = Vec::new();
+    for (addr, message) in addrs.iter().zip(OBSERVATIONS) {
+        let node_signature = request_signature(addr, message, &SignOptions::new(scheme.clone())).await.unwrap();
+        signatures.push(node_signature.signature);
+        pairs.push((node_signature.public_key, message));
+    }
+    let aggregated = aggregate_signatures(&signatures);
+    assert!(verify_multi_message(&scheme, &aggregated, &pairs));
+
+    // 把任一节点的消息换成其他节点的观测后不通过
+    pairs[2].1 = OBSERVATIONS[0];
+    assert!(!verify_multi_message(&scheme, &aggregated, &pairs));
+}
@@ -0,0 +1,191 @@
+// 节点签名策略: 签名前检查消息内容和请求频率，拒绝原因返回给主节点
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde_json::json;
+use dss_core::Signer;
+use dss_core::epoch::KeySchedule;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::policy::{content_type, Policy, PolicyEngine, RateLimit};
+use dss_core::protocol::{ErrorCode, ProtocolError};
+use dss_core::request::NodeOutcome;
+mod common;
+use common::spawn_node;
+
+fn policy(value: serde_json::Value) -> Policy {
+    Policy::parse(&value.to_string()).unwrap()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-policy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+#[test]
+fn payloads_are_classified_by_content_type() {
+    assert_eq!(content_type(br#"{"price": 101}"#), "application/json");
+    assert_eq!(content_type(b"[1, 2]"), "application/json");
+    assert_eq!(content_type(b"101"), "text/plain");
+    assert_eq!(content_type(b"price=101"), "text/plain");
+    assert_eq!(content_type(&[0xff, 0xfe]), "application/octet-stream");
+}
+
+#[test]
+fn payload_rules_are_checked_before_signing() {
+    let empty = Policy::default();
+    assert!(empty.check_payload(&[0; 4096]).is_ok());
+
+    let rules = policy(json!({
+        "max_payload_bytes": 32,
+        "content_types": ["text/plain", "application/json"],
+        "allow": [{ "prefix": "price=" }, { "regex": "^\\{" }],
+        "deny": [{ "regex": "(?i)withdraw" }],
+    }));
+    assert!(rules.check_payload(b"price=101").is_ok());
+    assert!(rules.check_payload(br#"{"price": 101}"#).is_ok());
+    assert!(rules.check_payload(format!("price={}", "1".repeat(40)).as_bytes()).unwrap_err().contains("exceeds"));
+    assert!(rules.check_payload(&[0xff]).unwrap_err().contains("application/octet-stream"));
+    assert!(rules.check_payload(b"transfer 100").unwrap_err().contains("allow rule"));
+    assert!(rules.check_payload(b"price=1 WITHDRAW").unwrap_err().contains("deny rule"));
+}
+
+#[test]
+fn structured_payloads_follow_the_json_schema() {
+    let rules = policy(json!({
+        "json_schema": {
+            "type": "object",
+            "properties": { "price": { "type": "integer", "minimum": 0 } },
+            "required": ["price"],
+            "additionalProperties": false,
+        },
+    }));
+    assert!(rules.check_payload(br#"{"price": 101}"#).is_ok());
+    assert!(rules.check_payload(br#"{"price": -1}"#).unwrap_err().contains("JSON schema"));
+    assert!(rules.check_payload(br#"{"price": 1, "to": "alice"}"#).is_err());
+    assert!(rules.check_payload(br#"[1]"#).is_err());
+    // 非 JSON 消息由 content_types 限制
+    assert!(rules.check_payload(b"price=101").is_ok());
+}
+
+#[test]
+fn invalid_policies_are_rejected() {
+    assert!(Policy::parse(r#"{"allow": [{"regex": "("}]}"#).is_err());
+    assert!(Policy::parse(r#"{"json_schema": {"type": 12}}"#).is_err());
+    assert!(Policy::parse(r#"{"max_payload": 10}"#).is_err());
+    assert!(Policy::parse(r#"{"allow": [{"suffix": "x"}]}"#).is_err());
+}
+
+#[test]
+fn rate_limits_apply_per_aggregator() {
+    let path = temp_file("rate.json");
+    let limits = json!({
+        "rate_limit": { "requests": 2, "window_ms": 1000 },
+        "aggregator_rate_limits": { "trusted": { "requests": 3, "window_ms": 1000 } },
+    });
+    std::fs::write(&path, limits.to_string()).unwrap();
+    let engine = PolicyEngine::load(&path).unwrap();
+    assert_eq!(engine.policy().rate_limit_for("other"), Some(RateLimit { requests: 2, window_ms: 1000 }));
+
+    // 节点签名前检查，签名后计数
+    let sign =
+        |requester: &str, at: u64| engine.evaluate(requester, b"m", at).and_then(|()| engine.record(requester, at));
+    assert!(sign("a", 0).is_ok());
+    assert!(sign("a", 10).is_ok());
+    assert!(sign("a", 20).unwrap_err().contains("rate limit"));
+    // 其他主节点分别计数
+    assert!(sign("b", 20).is_ok());
+    for at in [30, 40, 50] {
+        assert!(sign("trusted", at).is_ok());
+    }
+    assert!(sign("trusted", 60).is_err());
+    // 窗口滑过后恢复
+    assert!(sign("a", 1000).is_ok());
+    assert!(sign("a", 1001).is_err());
+    assert!(sign("a", 1010).is_ok());
+}
+
+#[test]
+fn only_produced_signatures_are_counted() {
+    let path = temp_file("count.json");
+    std::fs::write(&path, json!({ "rate_limit": { "requests": 2, "window_ms": 100 } }).to_string()).unwrap();
+    let engine = PolicyEngine::load(&path).unwrap();
+
+    // 通过检查但没有签名的请求不占用额度
+    for at in 0..5 {
+        assert!(engine.evaluate("a", b"m", at).is_ok());
+    }
+    assert_eq!(engine.tracked_requesters(), 0);
+    engine.record("a", 5).unwrap();
+    engine.record("a", 6).unwrap();
+    assert!(engine.evaluate("a", b"m", 7).is_err());
+    // 并发请求在检查之后占满窗口时，签名后的计数同样拒绝
+    assert!(engine.evaluate("b", b"m", 7).is_ok());
+    engine.record("b", 7).unwrap();
+    engine.record("b", 8).unwrap();
+    assert!(engine.record("b", 9).is_err());
+    assert_eq!(engine.tracked_requesters(), 2);
+
+    // 窗口内没有请求的主节点不再保留，按来源 IP 计数时记录不会无限增长
+    for ip in 0..100 {
+        engine.record(&format!("10.0.0.{}", ip), 10).unwrap();
+    }
+    assert_eq!(engine.tracked_requesters(), 102);
+    engine.record("c", 500).unwrap();
+    assert_eq!(engine.tracked_requesters(), 1);
+    assert!(engine.evaluate("a", b"m", 500).is_ok());
+}
+
+#[test]
+fn policy_file_is_reloaded_when_it_changes() {
+    let path = temp_file("reload.json");
+    std::fs::write(&path, json!({ "allow": [{ "prefix": "price=" }] }).to_string()).unwrap();
+    let engine = PolicyEngine::load(&path).unwrap();
+    assert!(engine.evaluate("a", b"price=1", 0).is_ok());
+    assert!(engine.evaluate("a", b"volume=1", 0).is_err());
+
+    std::fs::write(&path, json!({ "allow": [{ "prefix": "volume=" }, { "prefix": "price=" }] }).to_string()).unwrap();
+    assert!(engine.evaluate("a", b"volume=1", 0).is_ok());
+
+    // 无效的新文件不替换当前策略
+    std::fs::write(&path, "{ not json").unwrap();
+    assert!(engine.evaluate("a", b"volume=1", 0).is_ok());
+    assert!(engine.evaluate("a", b"other=1", 0).is_err());
+}
+
+#[test]
+fn refusals_are_recorded_with_their_reason() {
+    let outcome = NodeOutcome::Refused { reason: "payload matches deny rule".to_string(), at: 1 };
+    let value = serde_json::to_value(&outcome).unwrap();
+    assert_eq!(value, json!({ "status": "refused", "reason": "payload matches deny rule", "at": 1 }));
+    let decoded: NodeOutcome = serde_json::from_value(value.clone()).unwrap();
+    assert!(matches!(decoded, NodeOutcome::Refused { at: 1, .. }));
+    assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+}
+
+#[tokio::test]
+async fn node_service_returns_the_refusal_reason() {
+    let options = SignOptions::new(HashScheme::TryAndIncrement);
+    let path = temp_file("service.json");
+    let rules = json!({
+        "allow": [{ "prefix": "price=" }],
+        "rate_limit": { "requests": 2, "window_ms": 60000 },
+    });
+    std::fs::write(&path, rules.to_string()).unwrap();
+    let engine = Arc::new(PolicyEngine::load(&path).unwrap());
+    let keys = Arc::new(KeySchedule::new(Arc::new(Signer::random())));
+    let addr = spawn_node(NodeService::new(keys).with_policy(engine)).await;
+
+    let refusal = |e: std::io::Error| {
+        let refusal = ProtocolError::from_io(&e).unwrap();
+        assert_eq!(refusal.code, ErrorCode::PolicyRefused);
+        refusal.reason.clone()
+    };
+    assert!(request_signature(&addr, b"price=101", &options).await.is_ok());
+    let reason = refusal(request_signature(&addr, b"transfer 100", &options).await.unwrap_err());
+    assert_eq!(reason, "payload does not match any allow rule");
+    // 被拒绝的请求不计入频率限制，未认证的请求按来源 IP 计数
+    assert!(request_signature(&addr, b"price=102", &options).await.is_ok());
+    let reason = refusal(request_signature(&addr, b"price=103", &options).await.unwrap_err());
+    assert!(reason.contains("rate limit"));
+}
@@ -0,0 +1,125 @@
+// 公钥持有证明与注销证明: 正确的证明被接受，换节点、换地址、换公钥、流氓公钥或重放的请求被拒绝
+use dss_core::{KeySet, PublicKey, Signer};
+use dss_core::collector::KeyRegistration;
+use dss_core::pop::{
+    deregister_message, group_pop_message, is_reserved_message, pop_message, prove_deregistration,
+    prove_possession, verify_deregistration, verify_possession,
+};
+use dss_core::registry::{RegisterOutcome, Registry, RegistryError};
+use dss_core::threshold::deal;
+use dss_core::verifier::verify_signature;
+
+const ADDRESS: &str = "127.0.0.1:9001";
+
+#[test]
+fn possession_proofs_bind_key_node_address_epoch_and_nonce() {
+    let signer = Signer::random();
+    let pk = signer.public_key();
+    let proof = prove_possession(&signer, "node-a", ADDRESS, Some(3), 7).unwrap();
+    assert!(verify_possession(&pk, "node-a", ADDRESS, Some(3), 7, &proof));
+
+    // 截获的证明不能用于其他节点 ID、地址、纪元、序号或公钥
+    assert!(!verify_possession(&pk, "node-b", ADDRESS, Some(3), 7, &proof));
+    assert!(!verify_possession(&pk, "node-a", "127.0.0.1:9999", Some(3), 7, &proof));
+    assert!(!verify_possession(&pk, "node-a", ADDRESS, None, 7, &proof));
+    assert!(!verify_possession(&pk, "node-a", ADDRESS, Some(3), 8, &proof));
+    assert!(!verify_possession(&Signer::random().public_key(), "node-a", ADDRESS, Some(3), 7, &proof));
+    // 对普通消息的签名不是持有证明
+    let plain = signer.sign(b"node-a").unwrap();
+    assert!(!verify_possession(&pk, "node-a", ADDRESS, Some(3), 7, &plain));
+    // 字段带长度前缀，移动节点 ID 与地址的边界得到不同的消息
+    assert_ne!(
+        pop_message(&pk, "node-a1", "27.0.0.1", None, 0).unwrap(),
+        pop_message(&pk, "node-a", "127.0.0.1", None, 0).unwrap()
+    );
+
+    let message = pop_message(&pk, "node-a", ADDRESS, Some(3), 7).unwrap();
+    assert!(is_reserved_message(&message));
+    assert!(verify_signature(&message, &proof, &pk));
+}
+
+#[test]
+fn deregistration_proofs_are_separate_from_possession_proofs() {
+    let signer = Signer::random();
+    let proof = prove_deregistration(&signer, "node-a", 5).unwrap();
+    assert!(verify_deregistration(&signer.public_key(), "node-a", 5, &proof));
+    assert!(!verify_deregistration(&signer.public_key(), "node-a", 6, &proof));
+    assert!(!verify_deregistration(&signer.public_key(), "node-b", 5, &proof));
+    assert!(!verify_deregistration(&Signer::random().public_key(), "node-a", 5, &proof));
+
+    // 两种证明的域标签不同，不能互相替代
+    let possession = prove_possession(&signer, "node-a", ADDRESS, None, 5).unwrap();
+    assert!(!verify_deregistration(&signer.public_key(), "node-a", 5, &possession));
+    assert!(!verify_possession(&signer.public_key(), "node-a", ADDRESS, None, 5, &proof));
+    assert!(is_reserved_message(&deregister_message("node-a", 5)));
+    assert!(is_reserved_message(&group_pop_message(&deal(signer.private_key(), 1, 1).unwrap().group, "group", 0).unwrap()));
+    assert!(is_reserved_message(b"DSS_BN254_POP_V1:legacy"));
+    assert!(!is_reserved_message(b"transfer 10 tokens"));
+}
+
+#[test]
+fn registry_rejects_registrations_without_a_valid_proof() {
+    let honest = Signer::random();
+    let attacker = Signer::random();
+    let mut registry = Registry::new();
+    let registration = KeyRegistration::at(&honest, "honest", ADDRESS, None, 1).unwrap();
+    assert!(registration.verify());
+    assert_eq!(registry.register(&registration, 1, 0).unwrap(), RegisterOutcome::Created);
+
+    // 流氓公钥 attacker - honest 与诚实公钥聚合后等于攻击者公钥，但攻击者无法为其出具持有证明
+    let rogue = PublicKey(attacker.public_key().0 - honest.public_key().0);
+    assert_eq!(KeySet::new(vec![honest.public_key(), rogue]).aggregate().0, attacker.public_key().0);
+    let forged = KeyRegistration {
+        public_key: rogue,
+        ..KeyRegistration::at(&attacker, "rogue", "127.0.0.1:9002", None, 1).unwrap()
+    };
+    assert!(!forged.verify());
+    assert_eq!(registry.register(&forged, 2, 0), Err(RegistryError::InvalidProof));
+
+    // 把其他节点的登记请求改成自己的节点 ID、地址或序号同样无效
+    let stolen = KeyRegistration { node_id: "thief".to_string(), ..registration.clone() };
+    assert_eq!(registry.register(&stolen, 3, 0), Err(RegistryError::InvalidProof));
+    let redirected = KeyRegistration { address: "10.0.0.66:9001".to_string(), nonce: 2, ..registration };
+    assert_eq!(registry.register(&redirected, 3, 0), Err(RegistryError::InvalidProof));
+    assert_eq!(registry.len(), 1);
+}
+
+#[test]
+fn key_changes_need_the_registered_key_and_a_fresh_nonce() {
+    let current = Signer::random();
+    let attacker = Signer::random();
+    let mut registry = Registry::new();
+    registry.register(&KeyRegistration::at(&current, "a", ADDRESS, None, 10).unwrap(), 1, 0).unwrap();
+
+    // 持有证明只证明持有新私钥，不授权替换已登记节点的公钥
+    let takeover = KeyRegistration::at(&attacker, "a", ADDRESS, None, 11).unwrap();
+    assert_eq!(registry.register(&takeover, 2, 0), Err(RegistryError::Unauthorized("a".to_string())));
+    let self_authorized = takeover.clone().authorize(&attacker).unwrap();
+    assert_eq!(registry.register(&self_authorized, 2, 0), Err(RegistryError::Unauthorized("a".to_string())));
+    // 授权签名绑定整个登记声明，不能挪用到其他公钥
+    let rotation = KeyRegistration::at(&Signer::random(), "a", ADDRESS, None, 11).unwrap().authorize(&current).unwrap();
+    let moved = KeyRegistration { authorization: rotation.authorization, ..takeover };
+    assert_eq!(registry.register(&moved, 2, 0), Err(RegistryError::Unauthorized("a".to_string())));
+    assert_eq!(registry.register(&rotation, 2, 0).unwrap(), RegisterOutcome::Updated);
+    assert_eq!(registry.get("a").unwrap().nonce, 11);
+
+    // 地址变更由当前公钥的持有证明授权，序号必须递增
+    let moved = KeyRegistration::at(&current, "a", "127.0.0.1:9100", None, 11).unwrap();
+    assert_eq!(registry.register(&moved, 3, 0), Err(RegistryError::StaleNonce { nonce: 11, last: 11 }));
+    let moved = KeyRegistration::at(&current, "a", "127.0.0.1:9100", None, 12).unwrap();
+    assert_eq!(registry.register(&moved, 3, 0).unwrap(), RegisterOutcome::Updated);
+
+    // 注销后重放旧的登记或注销请求都不能改变记录
+    let deregistration = prove_deregistration(&current, "a", 13).unwrap();
+    assert_eq!(registry.deregister("a", &deregistration, 12, 4, 0).unwrap_err(), RegistryError::InvalidProof);
+    assert!(!registry.deregister("a", &deregistration, 13, 4, 0).unwrap().is_active());
+    assert_eq!(registry.register(&moved, 5, 0), Err(RegistryError::StaleNonce { nonce: 12, last: 13 }));
+    let reactivation = KeyRegistration::at(&current, "a", "127.0.0.1:9100", None, 14).unwrap();
+    assert_eq!(registry.register(&reactivation, 5, 0).unwrap(), RegisterOutcome::Updated);
+    assert!(registry.get("a").unwrap().is_active());
+    assert_eq!(
+        registry.deregister("a", &deregistration, 13, 6, 0).unwrap_err(),
+        RegistryError::StaleNonce { nonce: 13, last: 14 }
+    );
+    assert!(registry.get("a").unwrap().is_active());
+}
@@ -0,0 +1,226 @@
+// 节点协议的帧格式: 各类消息的往返编码、截断和超长的帧、版本范围以及与旧版本节点的协商
+use std::sync::Arc;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use dss_core::Signer;
+use dss_core::auth::AggregatorIdentity;
+use dss_core::dkg::DkgMessage;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, SignOptions};
+use dss_core::protocol::{
+    dkg_body, message_digest, read_frame, read_message, write_message, write_message_version, ErrorCode, Message,
+    ProtocolError, MAX_FRAME_LEN, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use dss_core::scheme::SignatureScheme;
+mod common;
+use common::bind_local;
+
+async fn round_trip(message: &Message, version: u8) -> (u8, Message) {
+    let (mut client, mut server) = duplex(64 * 1024);
+    write_message_version(&mut client, message, version).await.unwrap();
+    read_frame(&mut server).await.unwrap().unwrap()
+}
+
+// 直接读取原始字节，返回协议错误
+async fn read_raw(bytes: &[u8]) -> std::io::Result<Option<Message>> {
+    let (mut client, mut server) = duplex(64 * 1024);
+    client.write_all(bytes).await.unwrap();
+    drop(client);
+    read_message(&mut server).await
+}
+
+fn frame(version: u8, message_type: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = ((payload.len() + 2) as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(&[version, message_type]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn error_code(e: &std::io::Error) -> Option<ErrorCode> {
+    ProtocolError::from_io(e).map(|e| e.code)
+}
+
+fn sign_request(scheme: HashScheme, signature_scheme: SignatureScheme, epoch: Option<u64>) -> Message {
+    Message::SignRequest { message: b"frame".to_vec(), scheme, signature_scheme, epoch, auth: None }
+}
+
+#[tokio::test]
+async fn messages_round_trip() {
+    let identity = AggregatorIdentity::random();
+    let auth = identity.authorize(b"body", 1);
+    let request = Message::SignRequest {
+        message: b"price=101".to_vec(),
+        scheme: HashScheme::svdw("DSS-TEST").unwrap(),
+        signature_scheme: SignatureScheme::MessageAugmentation,
+        epoch: Some(7),
+        auth: Some(auth.clone()),
+    };
+    match round_trip(&request, PROTOCOL_VERSION).await {
+        (PROTOCOL_VERSION, Message::SignRequest { message, scheme, signature_scheme, epoch, auth: decoded }) => {
+            assert_eq!(message, b"price=101");
+            assert_eq!(scheme, HashScheme::svdw("DSS-TEST").unwrap());
+            assert!(matches!(signature_scheme, SignatureScheme::MessageAugmentation));
+            assert_eq!(epoch, Some(7));
+            assert_eq!(decoded, Some(auth));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let signer = Signer::random();
+    let signature = signer.sign(b"frame").unwrap();
+    let response = Message::SignResponse { digest: message_digest(b"frame"), public_key: signer.public_key(), signature };
+    match round_trip(&response, PROTOCOL_VERSION).await.1 {
+        Message::SignResponse { digest, public_key, signature: decoded } => {
+            assert_eq!(digest, message_digest(b"frame"));
+            assert_eq!(public_key.0, signer.public_key().0);
+            assert_eq!(decoded.0, signature.0);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let error = Message::Error(ProtocolError::new(ErrorCode::PolicyRefused, "rate limit"));
+    match round_trip(&error, PROTOCOL_VERSION).await.1 {
+        Message::Error(e) => assert_eq!(e, ProtocolError::new(ErrorCode::PolicyRefused, "rate limit")),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // 协调者的 DKG 消息带有认证，节点之间的消息没有
+    let start = DkgMessage::Start { session: "frame".to_string(), threshold: 2, index: 1, participants: Vec::new() };
+    let dkg_auth = identity.authorize_dkg(&dkg_body(&start).unwrap(), 1);
+    let dkg = Message::Dkg { message: start.clone(), auth: Some(dkg_auth.clone()) };
+    assert_eq!(dkg.min_version(), 6);
+    match round_trip(&dkg, PROTOCOL_VERSION).await.1 {
+        Message::Dkg { message, auth } => {
+            assert_eq!(dkg_body(&message).unwrap(), dkg_body(&start).unwrap());
+            assert_eq!(auth, Some(dkg_auth));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    let unauthenticated = Message::Dkg { message: start, auth: None };
+    assert_eq!(unauthenticated.min_version(), 2);
+    assert!(matches!(round_trip(&unauthenticated, 5).await.1, Message::Dkg { auth: None, .. }));
+    let (mut client, _server) = duplex(1024);
+    let e = write_message_version(&mut client, &dkg, 5).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::UnsupportedVersion));
+
+    // 连接在帧边界处关闭
+    assert!(read_raw(&[]).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn older_versions_use_defaults_for_missing_fields() {
+    let plain = sign_request(HashScheme::TryAndIncrement, SignatureScheme::ProofOfPossession, None);
+    for version in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+        match round_trip(&plain, version).await {
+            (decoded_version, Message::SignRequest { scheme, signature_scheme, epoch, auth, .. }) => {
+                assert_eq!(decoded_version, version);
+                assert_eq!(scheme, HashScheme::TryAndIncrement);
+                assert!(matches!(signature_scheme, SignatureScheme::ProofOfPossession));
+                assert_eq!((epoch, auth), (None, None));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    // 版本 1 的签名请求只有消息本身
+    let v1 = frame(1, 1, &[0, 0, 0, 2, b'h', b'i']);
+    assert!(matches!(read_raw(&v1).await.unwrap(), Some(Message::SignRequest { message, .. }) if message == b"hi"));
+
+    // 消息用到的字段在目标版本中不存在时拒绝编码
+    assert_eq!(plain.min_version(), 1);
+    let cases = [
+        (sign_request(HashScheme::svdw("DSS-TEST").unwrap(), SignatureScheme::ProofOfPossession, None), 2),
+        (sign_request(HashScheme::TryAndIncrement, SignatureScheme::MessageAugmentation, None), 3),
+        (sign_request(HashScheme::TryAndIncrement, SignatureScheme::ProofOfPossession, Some(1)), 4),
+    ];
+    for (message, min_version) in cases {
+        assert_eq!(message.min_version(), min_version);
+        let (mut client, _server) = duplex(1024);
+        let e = write_message_version(&mut client, &message, min_version - 1).await.unwrap_err();
+        assert_eq!(error_code(&e), Some(ErrorCode::UnsupportedVersion));
+    }
+}
+
+#[tokio::test]
+async fn malformed_frames_are_rejected() {
+    // 版本超出支持范围
+    for version in [0, PROTOCOL_VERSION + 1] {
+        let e = read_raw(&frame(version, 3, &[])).await.unwrap_err();
+        assert_eq!(error_code(&e), Some(ErrorCode::UnsupportedVersion));
+    }
+    // 长度超过上限时不读取负载
+    let oversize = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+    assert_eq!(error_code(&read_raw(&oversize).await.unwrap_err()), Some(ErrorCode::FrameTooLarge));
+    // 长度小于帧头
+    assert_eq!(error_code(&read_raw(&[0, 0, 0, 1, PROTOCOL_VERSION]).await.unwrap_err()), Some(ErrorCode::Malformed));
+    // 帧在负载中间被截断
+    let truncated = frame(PROTOCOL_VERSION, 1, &[0, 0, 0, 9, 1, 2]);
+    let e = read_raw(&truncated[..truncated.len() - 2]).await.unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+    // 长度字段完整但负载内容不足
+    let e = read_raw(&frame(PROTOCOL_VERSION, 1, &[0, 0, 0, 9, 1, 2])).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::Malformed));
+    // 多余的尾部字节、未知的消息类型和错误码
+    let e = read_raw(&frame(1, 1, &[0, 0, 0, 0, 0xff])).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::Malformed));
+    let e = read_raw(&frame(PROTOCOL_VERSION, 42, &[])).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::UnknownMessageType));
+    let e = read_raw(&frame(PROTOCOL_VERSION, 3, &[200, 0, 0, 0, 0])).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::Malformed));
+    // 版本 1 没有 Dkg 消息
+    let e = read_raw(&frame(1, 4, &[0, 0, 0, 2, b'{', b'}'])).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::Malformed));
+}
+
+#[tokio::test]
+async fn oversize_frames_are_not_written() {
+    let message = Message::SignRequest {
+        message: vec![0; MAX_FRAME_LEN],
+        scheme: HashScheme::TryAndIncrement,
+        signature_scheme: SignatureScheme::ProofOfPossession,
+        epoch: None,
+        auth: None,
+    };
+    let (mut client, _server) = duplex(1024);
+    let e = write_message(&mut client, &message).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::FrameTooLarge));
+}
+
+// 只接受版本 4 的旧节点: 其他版本以 UnsupportedVersion 拒绝，返回收到的帧版本
+async fn run_old_node(listener: TcpListener, signer: Signer) {
+    loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut header = [0u8; 6];
+        socket.read_exact(&mut header).await.unwrap();
+        let mut payload = vec![0u8; u32::from_be_bytes(header[..4].try_into().unwrap()) as usize - 2];
+        socket.read_exact(&mut payload).await.unwrap();
+        let reply = if header[4] == 4 {
+            let message = &payload[4..4 + u32::from_be_bytes(payload[..4].try_into().unwrap()) as usize];
+            Message::SignResponse {
+                digest: message_digest(message),
+                public_key: signer.public_key(),
+                signature: signer.sign(message).unwrap(),
+            }
+        } else {
+            Message::Error(ProtocolError::new(ErrorCode::UnsupportedVersion, "expected 4"))
+        };
+        write_message_version(&mut socket, &reply, 4).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn aggregators_fall_back_to_older_nodes() {
+    let signer = Signer::random();
+    let public_key = signer.public_key();
+    let (listener, addr) = bind_local().await;
+    tokio::spawn(run_old_node(listener, signer));
+
+    let options = SignOptions::new(HashScheme::TryAndIncrement).with_epoch(Some(3));
+    let node_signature = request_signature(&addr, b"frame", &options).await.unwrap();
+    assert_eq!(node_signature.public_key.0, public_key.0);
+
+    // 带认证的请求无法用版本 4 表达，不降级
+    let options = SignOptions::new(HashScheme::TryAndIncrement).with_identity(Arc::new(AggregatorIdentity::random()));
+    let e = request_signature(&addr, b"frame", &options).await.unwrap_err();
+    assert_eq!(error_code(&e), Some(ErrorCode::UnsupportedVersion));
+}
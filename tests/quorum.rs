@@ -0,0 +1,62 @@
+// 门限的解析与计算，以及验证前对结果签名者数量的检查
+use dss_core::{AggregatedSignature, Quorum, Signer, SignerBitmap};
+use dss_core::hash::HashScheme;
+use dss_core::request::RequestRecord;
+
+#[test]
+fn quorum_forms_parse() {
+    assert_eq!("all".parse::<Quorum>().unwrap(), Quorum::all());
+    assert_eq!("ALL".parse::<Quorum>().unwrap(), Quorum::Fraction(1.0));
+    assert_eq!("3".parse::<Quorum>().unwrap(), Quorum::Count(3));
+    assert_eq!(" 2/3 ".parse::<Quorum>().unwrap(), Quorum::Fraction(2.0 / 3.0));
+    assert_eq!("0.67".parse::<Quorum>().unwrap(), Quorum::Fraction(0.67));
+    assert_eq!("1.0".parse::<Quorum>().unwrap(), Quorum::Fraction(1.0));
+
+    for invalid in ["", "0", "-1", "abc", "1/0", "3/2", "0.0", "1.5", "2/x", "0/3"] {
+        assert!(invalid.parse::<Quorum>().is_err(), "{:?} should be rejected", invalid);
+    }
+    assert_eq!(Quorum::Count(3).to_string(), "3");
+    assert_eq!("0.5".parse::<Quorum>().unwrap().to_string(), "0.5");
+}
+
+#[test]
+fn required_signers_round_up() {
+    assert_eq!(Quorum::all().required(5), 5);
+    assert_eq!(Quorum::Count(3).required(5), 3);
+    // 2/3 * 3 的浮点误差不会多要一个签名
+    assert_eq!("2/3".parse::<Quorum>().unwrap().required(3), 2);
+    assert_eq!("2/3".parse::<Quorum>().unwrap().required(4), 3);
+    assert_eq!(Quorum::Fraction(0.01).required(5), 1);
+    assert_eq!(Quorum::all().required(0), 1);
+
+    assert!(Quorum::Count(3).is_met(3, 5));
+    assert!(!Quorum::Count(3).is_met(2, 5));
+    assert!(Quorum::Count(3).validate(3).is_ok());
+    assert!(Quorum::Count(4).validate(3).unwrap_err().contains("only has 3 nodes"));
+    assert!(Quorum::all().validate(0).is_err());
+}
+
+fn result(signers: &[usize]) -> AggregatedSignature {
+    let mut bitmap = SignerBitmap::default();
+    for index in signers {
+        bitmap.set(*index);
+    }
+    AggregatedSignature { signature: Signer::random().sign(b"quorum").unwrap(), signers: bitmap }
+}
+
+#[test]
+fn results_below_the_quorum_are_rejected() {
+    let nodes: Vec<String> = (1..=4).map(|i| format!("127.0.0.1:900{}", i)).collect();
+    let record = RequestRecord::new("request", "quorum", &HashScheme::TryAndIncrement, &nodes, 2);
+
+    assert!(record.check_signers(&result(&[0, 3]), None).is_ok());
+    assert_eq!(record.check_signers(&result(&[1]), None), Err("result has 1 signers, 2 required".to_string()));
+    // 验证者可以要求比记录更高的门限
+    assert!(record.check_signers(&result(&[0, 3]), Some(&Quorum::Fraction(0.75))).is_err());
+    assert!(record.check_signers(&result(&[0, 1, 3]), Some(&Quorum::Fraction(0.75))).is_ok());
+    assert!(record.check_signers(&result(&[0, 1, 3]), Some(&Quorum::all())).is_err());
+
+    // 记录的门限为 0 时仍至少需要一个签名者
+    let empty = RequestRecord::new("request", "quorum", &HashScheme::TryAndIncrement, &nodes, 0);
+    assert!(empty.check_signers(&result(&[]), None).is_err());
+}
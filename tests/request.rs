@@ -0,0 +1,169 @@
+// 签名请求的生命周期: 状态转换、节点结果、聚合结果、记录的序列化格式以及重启后的恢复
+use std::collections::BTreeMap;
+use serde_json::json;
+use dss_core::{Aggregator, Quorum, Signer};
+use dss_core::hash::HashScheme;
+use dss_core::request::{NodeOutcome, RequestRecord, RequestState, RequestTable, SigningMode};
+use dss_core::store::FileStore;
+
+fn nodes() -> Vec<String> {
+    (1..=3).map(|i| format!("127.0.0.1:900{}", i)).collect()
+}
+
+fn signed(signer_index: usize) -> NodeOutcome {
+    NodeOutcome::Signed { signer_index, signature: Signer::random().sign(b"request").unwrap(), at: 1 }
+}
+
+// 用已记录的节点签名重建聚合器，与主节点恢复聚合进度的方式相同
+fn aggregator(record: &RequestRecord, registry_size: usize) -> Aggregator {
+    let mut aggregator = Aggregator::new(registry_size);
+    for (signer_index, signature) in record.signatures() {
+        aggregator.add(signer_index, signature);
+    }
+    aggregator
+}
+
+#[test]
+fn requests_move_from_accepted_to_complete() {
+    let mut record = RequestRecord::new("r1", "request", &HashScheme::TryAndIncrement, &nodes(), 2);
+    assert_eq!(record.state, RequestState::Accepted);
+    assert!(!record.state.is_terminal());
+    assert!(record.nodes.values().all(NodeOutcome::is_pending));
+    assert_eq!(record.missing_nodes(), nodes());
+    assert_eq!(record.shortfall(), 2);
+
+    record.set_state(RequestState::Collecting);
+    assert!(!record.state.is_terminal());
+    record.record_node("127.0.0.1:9002", signed(4));
+    record.record_node("127.0.0.1:9001", NodeOutcome::Failed { reason: "refused".to_string(), at: 2 });
+    assert_eq!(record.signer_count(), 1);
+    assert_eq!(record.shortfall(), 1);
+    assert_eq!(record.missing_nodes(), vec!["127.0.0.1:9001".to_string(), "127.0.0.1:9003".to_string()]);
+
+    record.record_node("127.0.0.1:9003", signed(0));
+    let partial = aggregator(&record, 5);
+    assert_eq!(partial.signers().indices(), vec![0, 4]);
+    record.complete(partial.aggregate());
+    assert_eq!(record.state, RequestState::Complete);
+    assert!(record.state.is_terminal());
+    assert_eq!(record.shortfall(), 0);
+    let completed_at = record.completed_at.unwrap();
+
+    // 迟到的签名更新结果，但不改变完成时间
+    record.record_node("127.0.0.1:9001", signed(2));
+    record.complete(aggregator(&record, 5).aggregate());
+    assert_eq!(record.completed_at, Some(completed_at));
+    assert_eq!(record.result.as_ref().unwrap().signers.indices(), vec![0, 2, 4]);
+    assert!(record.error.is_none());
+}
+
+#[test]
+fn requests_fail_or_expire_with_a_reason() {
+    for state in [RequestState::Failed, RequestState::Expired] {
+        let mut record = RequestRecord::new("r2", "request", &HashScheme::TryAndIncrement, &nodes(), 3);
+        record.set_state(RequestState::Collecting);
+        record.record_node("127.0.0.1:9001", NodeOutcome::TimedOut { at: 5 });
+        record.fail(state, "quorum not reached".to_string());
+        assert_eq!(record.state, state);
+        assert!(record.state.is_terminal());
+        assert_eq!(record.error.as_deref(), Some("quorum not reached"));
+        assert!(record.result.is_none() && record.completed_at.is_none());
+    }
+}
+
+#[test]
+fn multi_message_requests_list_messages_by_signer() {
+    let messages: BTreeMap<String, String> = nodes()
+        .into_iter()
+        .enumerate()
+        .map(|(i, node)| (node, format!("observation {}", i)))
+        .collect();
+    // 列出的节点少于门限时请求无法完成，创建时即拒绝
+    let error = RequestRecord::with_messages("r3", messages.clone(), &HashScheme::TryAndIncrement, &Quorum::Count(4))
+        .map(|_| ())
+        .unwrap_err();
+    assert!(error.contains("only has 3 nodes"), "{}", error);
+    let all = RequestRecord::with_messages("r3", messages.clone(), &HashScheme::TryAndIncrement, &Quorum::all()).unwrap();
+    assert_eq!(all.required, 3);
+
+    let mut record = RequestRecord::with_messages("r3", messages, &HashScheme::TryAndIncrement, &Quorum::Count(2)).unwrap();
+    assert_eq!(record.required, 2);
+    assert!(matches!(record.signing_mode, SigningMode::MultiMessage));
+    assert_eq!(record.node_message("127.0.0.1:9002"), "observation 1");
+    assert_eq!(record.node_message("127.0.0.1:9999"), "");
+
+    record.record_node("127.0.0.1:9001", signed(7));
+    record.record_node("127.0.0.1:9003", signed(1));
+    // 按签名者序号排序，与位图选出的公钥顺序一致
+    assert_eq!(record.signed_messages(), vec![(1, "observation 2"), (7, "observation 0")]);
+}
+
+#[test]
+fn records_serialize_states_and_outcomes() {
+    let mut record = RequestRecord::new("r4", "request", &HashScheme::TryAndIncrement, &nodes()[..1], 1);
+    record.record_node("127.0.0.1:9001", NodeOutcome::Rejected { reason: "bad signature".to_string(), at: 3 });
+    let value = serde_json::to_value(&record).unwrap();
+    assert_eq!(value["state"], json!("accepted"));
+    assert_eq!(value["signing_mode"], json!({ "mode": "multisig" }));
+    assert_eq!(value["nodes"]["127.0.0.1:9001"], json!({ "status": "rejected", "reason": "bad signature", "at": 3 }));
+    assert!(value.get("epoch").is_none() && value.get("messages").is_none());
+
+    let decoded: RequestRecord = serde_json::from_value(value).unwrap();
+    assert_eq!(serde_json::to_value(&decoded.nodes).unwrap(), serde_json::to_value(&record.nodes).unwrap());
+    assert_eq!(serde_json::to_value(RequestState::Expired).unwrap(), json!("expired"));
+    assert_eq!(serde_json::to_value(NodeOutcome::Pending).unwrap(), json!({ "status": "pending" }));
+
+    // 旧记录缺少的字段取默认值
+    let mut old = serde_json::to_value(&record).unwrap();
+    for field in ["hash_scheme", "signature_scheme", "signing_mode"] {
+        old.as_object_mut().unwrap().remove(field);
+    }
+    let decoded: RequestRecord = serde_json::from_value(old).unwrap();
+    assert_eq!(decoded.hash_scheme, HashScheme::TryAndIncrement);
+    assert!(matches!(decoded.signing_mode, SigningMode::Multisig));
+}
+
+#[test]
+fn unfinished_requests_resume_after_restart() {
+    let dir = std::env::temp_dir().join(format!("dss-requests-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("requests.log");
+    let open = || RequestTable::open(Box::new(FileStore::<RequestRecord>::open(&path).unwrap())).unwrap();
+
+    let mut table = open();
+    for request_id in ["done", "collecting", "accepted"] {
+        let record = RequestRecord::new(request_id, "request", &HashScheme::TryAndIncrement, &nodes(), 2);
+        table.insert(record).unwrap().wait().unwrap();
+    }
+    let update = |table: &mut RequestTable, request_id: &str, f: &dyn Fn(&mut RequestRecord)| {
+        table.update(request_id, f).unwrap().wait().unwrap();
+    };
+    update(&mut table, "done", &|record| {
+        record.record_node("127.0.0.1:9001", signed(0));
+        record.record_node("127.0.0.1:9002", signed(1));
+        record.complete(aggregator(record, 3).aggregate());
+    });
+    update(&mut table, "collecting", &|record| {
+        record.set_state(RequestState::Collecting);
+        record.record_node("127.0.0.1:9002", signed(1));
+        record.record_node("127.0.0.1:9003", NodeOutcome::Pending);
+    });
+    // 未知的请求不写入存储
+    table.update("unknown", |_| panic!("unknown request updated")).unwrap().wait().unwrap();
+    drop(table);
+
+    // 重启后只继续未结束的请求，已记录的签名不再重新请求
+    let table = open();
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.unfinished(), vec!["accepted".to_string(), "collecting".to_string()]);
+    assert_eq!(table.get("done").unwrap().state, RequestState::Complete);
+    assert_eq!(table.get("done").unwrap().result.as_ref().unwrap().signers.indices(), vec![0, 1]);
+
+    let collecting = table.get("collecting").unwrap();
+    assert_eq!(collecting.state, RequestState::Collecting);
+    assert_eq!(collecting.missing_nodes(), vec!["127.0.0.1:9001".to_string(), "127.0.0.1:9003".to_string()]);
+    assert_eq!(collecting.shortfall(), 1);
+    assert_eq!(aggregator(collecting, 3).signers().indices(), vec![1]);
+    assert!(table.get("unknown").is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
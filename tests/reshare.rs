@@ -0,0 +1,427 @@
+// 份额重分享: 库层面的重分享计算，以及通过节点服务更换委员会和刷新份额
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use substrate_bn::{Group, G2};
+use zeroize::Zeroizing;
+use dss_core::{PublicKey, Signer};
+use dss_core::auth::{AggregatorIdentity, RequestAuthenticator};
+use dss_core::dkg::{DealerCommitments, DkgCoordinator, DkgNode, Participant, ReceivedDeal};
+use dss_core::epoch::KeySchedule;
+use dss_core::hash::HashScheme;
+use dss_core::keystore;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::collector::GroupRegistration;
+use dss_core::pop::group_pop_message;
+use dss_core::registry::{GroupRecord, RegisterOutcome, RegistryError};
+use dss_core::reshare::{run_reshare, valid_dealer, ReshareDealer, ResharePlan, ReshareRecipient};
+use dss_core::threshold::{combine, deal, Dealing, ThresholdAggregator, ThresholdGroup};
+use dss_core::tls::{create_ca, issue_certificate, IssuedCertificate, TlsClient, TlsIdentity, TlsServer, Transport};
+use dss_core::verifier::{verify_signature, verify_signature_with};
+mod common;
+use common::{free_addr, spawn_node};
+
+const PASSWORD: &str = "reshare-test";
+const MESSAGE: &[u8] = b"resharing keeps the group key";
+
+fn participants(n: u32) -> Vec<Participant> {
+    (1..=n).map(|index| Participant { index, address: format!("node-{}", index) }).collect()
+}
+
+// 不经过网络，旧份额持有者直接把份额的份额交给新委员会
+fn reshare_all(
+    dealers: &[ReshareDealer],
+    recipients: &[Participant],
+) -> (Vec<DealerCommitments>, Vec<BTreeMap<u32, ReceivedDeal>>) {
+    let commitments = dealers
+        .iter()
+        .map(|dealer| DealerCommitments { dealer: dealer.index, commitments: dealer.commitments().to_vec() })
+        .collect();
+    let inboxes = recipients
+        .iter()
+        .map(|recipient| {
+            dealers
+                .iter()
+                .map(|dealer| {
+                    let deal = ReceivedDeal {
+                        commitments: dealer.commitments().to_vec(),
+                        share: dealer.share_for(recipient.index).unwrap(),
+                    };
+                    (dealer.index, deal)
+                })
+                .collect()
+        })
+        .collect();
+    (commitments, inboxes)
+}
+
+#[test]
+fn reshare_to_new_threshold_keeps_group_key() {
+    let master = Signer::random();
+    let old = deal(master.private_key(), 3, 4).unwrap();
+    let recipients = participants(3);
+
+    // 旧份额 2 不参与，其余 3 个达到旧门限
+    let dealers: Vec<ReshareDealer> = old
+        .shares
+        .iter()
+        .filter(|(index, _)| *index != 2)
+        .map(|(_, share)| ReshareDealer::new(share, &old.group, 2, &recipients).unwrap())
+        .collect();
+    let (commitments, inboxes) = reshare_all(&dealers, &recipients);
+    let qualified: BTreeSet<u32> = [1, 3, 4].into_iter().collect();
+
+    let mut partials = Vec::new();
+    for (participant, inbox) in recipients.iter().zip(&inboxes) {
+        let recipient =
+            ReshareRecipient::new(participant.index, 2, &recipients, old.group.clone(), commitments.clone()).unwrap();
+        assert!(recipient.complaints(inbox).is_empty());
+        let share = recipient.finalize(&qualified, inbox, &[], "group", 1).unwrap();
+        assert_eq!(share.group_public_key.0, master.public_key().0);
+        partials.push((participant.index, share.signer.sign(MESSAGE).unwrap()));
+    }
+
+    // 任意 2 个新份额即可签名，1 个不行
+    for subset in [[0, 1], [1, 2], [0, 2]] {
+        let selected: Vec<_> = subset.iter().map(|&i| partials[i]).collect();
+        assert!(verify_signature(MESSAGE, &combine(&selected).unwrap(), &master.public_key()));
+    }
+    assert!(!verify_signature(MESSAGE, &combine(&partials[..1]).unwrap(), &master.public_key()));
+
+    // 合格分发者不足旧门限时无法完成
+    let recipient = ReshareRecipient::new(1, 2, &recipients, old.group.clone(), commitments.clone()).unwrap();
+    let too_few: BTreeSet<u32> = [1, 3].into_iter().collect();
+    assert!(recipient.finalize(&too_few, &inboxes[0], &[], "group", 1).is_err());
+}
+
+#[test]
+fn dealer_must_commit_to_its_old_share() {
+    let master = Signer::random();
+    let old = deal(master.private_key(), 2, 3).unwrap();
+    let recipients = participants(3);
+
+    // 不持有旧份额的节点不能分发
+    assert!(ReshareDealer::new(&Signer::random(), &old.group, 2, &recipients).is_err());
+    assert!(ReshareDealer::new(&old.shares[0].1, &old.group, 4, &recipients).is_err());
+    // 门限为 1 时每个新份额都等于主私钥
+    assert!(ReshareDealer::new(&old.shares[0].1, &old.group, 1, &recipients).is_err());
+
+    let dealer = ReshareDealer::new(&old.shares[0].1, &old.group, 2, &recipients).unwrap();
+    assert!(dealer.share_for(4).is_err());
+    let honest = DealerCommitments { dealer: dealer.index, commitments: dealer.commitments().to_vec() };
+    assert!(valid_dealer(&old.group, 2, &honest));
+    assert!(!valid_dealer(&old.group, 3, &honest));
+
+    // 常数项不是旧份额公钥的分发者会改变群公钥，直接拒绝
+    let mut forged = honest.clone();
+    forged.commitments[0] = PublicKey(forged.commitments[0].0 + G2::one());
+    assert!(!valid_dealer(&old.group, 2, &forged));
+    assert!(ReshareRecipient::new(1, 2, &recipients, old.group.clone(), vec![honest.clone(), forged]).is_err());
+    // 不在新委员会中的序号不能作为参与者
+    assert!(ReshareRecipient::new(4, 2, &recipients, old.group.clone(), vec![honest]).is_err());
+}
+
+// 可信分发者生成的群登记请求，持有证明由所有份额合成
+fn dealt_registration(dealing: &Dealing, group_id: &str, generation: u64) -> GroupRegistration {
+    let message = group_pop_message(&dealing.group, group_id, generation).unwrap();
+    let partials: Vec<_> = dealing.shares.iter().map(|(index, share)| (*index, share.sign(&message).unwrap())).collect();
+    GroupRegistration {
+        group_id: group_id.to_string(),
+        group: dealing.group.clone(),
+        generation,
+        proof_of_possession: combine(&partials).unwrap(),
+    }
+}
+
+#[test]
+fn group_record_binds_generations_and_retires_shares() {
+    let master = Signer::random();
+    let old = deal(master.private_key(), 2, 3).unwrap();
+    let recipients = participants(4);
+    let dealers: Vec<ReshareDealer> = old
+        .shares
+        .iter()
+        .map(|(_, share)| ReshareDealer::new(share, &old.group, 3, &recipients).unwrap())
+        .collect();
+    let (commitments, inboxes) = reshare_all(&dealers, &recipients);
+    let qualified: BTreeSet<u32> = [1, 2, 3].into_iter().collect();
+
+    let mut aggregator = ThresholdAggregator::new(3);
+    let mut shares = Vec::new();
+    for (participant, inbox) in recipients.iter().zip(&inboxes) {
+        let recipient =
+            ReshareRecipient::new(participant.index, 3, &recipients, old.group.clone(), commitments.clone()).unwrap();
+        let share = recipient.finalize(&qualified, inbox, &[], "counted", 1).unwrap();
+        shares.push(dss_core::threshold::SharePublicKey { index: participant.index, public_key: share.signer.public_key() });
+        aggregator.add(participant.index, share.proof_share);
+    }
+    let new_group = ThresholdGroup { threshold: 3, group_public_key: master.public_key(), shares };
+    let registration = GroupRegistration {
+        group_id: "counted".to_string(),
+        group: new_group,
+        generation: 1,
+        proof_of_possession: aggregator.combine().unwrap(),
+    };
+    assert!(registration.verify());
+
+    // 持有证明绑定代数和份额列表
+    assert!(!GroupRegistration { generation: 2, ..registration.clone() }.verify());
+    let other_shares = deal(master.private_key(), 3, 4).unwrap().group;
+    assert!(!GroupRegistration { group: other_shares, ..registration.clone() }.verify());
+
+    let original = dealt_registration(&old, "counted", 0);
+    assert!(matches!(
+        GroupRecord::apply(None, &registration, 1),
+        Err(RegistryError::StaleGeneration { generation: 1, expected: 0 })
+    ));
+    let (record, outcome) = GroupRecord::apply(None, &original, 1).unwrap();
+    assert_eq!((outcome, record.generation), (RegisterOutcome::Created, 0));
+    let (record, outcome) = GroupRecord::apply(Some(&record), &registration, 2).unwrap();
+    assert_eq!((outcome, record.generation, record.registered_at), (RegisterOutcome::Updated, 1, 1));
+    assert_eq!(record.retired_shares.len(), 3);
+    let (record, outcome) = GroupRecord::apply(Some(&record), &registration, 3).unwrap();
+    assert_eq!((outcome, record.generation), (RegisterOutcome::Unchanged, 1));
+
+    // 重放第 0 代的登记被拒绝; 旧份额即使为下一代重新签名也不能再登记
+    assert!(matches!(GroupRecord::apply(Some(&record), &original, 4), Err(RegistryError::StaleGeneration { .. })));
+    let revived = dealt_registration(&old, "counted", 2);
+    assert!(matches!(GroupRecord::apply(Some(&record), &revived, 4), Err(RegistryError::ShareRetired(_))));
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-reshare-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 节点通道: 本地 CA 签发的证书 (节点和协调者共用) 以及节点允许的协调者身份
+struct Channel {
+    ca_dir: PathBuf,
+    issued: IssuedCertificate,
+    coordinator: PathBuf,
+}
+
+impl Channel {
+    fn new(dir: &Path) -> Self {
+        let ca_dir = dir.join("ca");
+        create_ca(&ca_dir).unwrap();
+        let issued =
+            issue_certificate(&ca_dir, "node", &["127.0.0.1".to_string(), "localhost".to_string()], &ca_dir).unwrap();
+        let coordinator = dir.join("coordinator.key");
+        AggregatorIdentity::random().save(&coordinator).unwrap();
+        Channel { ca_dir, issued, coordinator }
+    }
+
+    fn identity(&self) -> TlsIdentity {
+        TlsIdentity::load(&self.ca_dir.join("ca.pem"), &self.issued.cert, &self.issued.key).unwrap()
+    }
+
+    fn transport(&self) -> Transport {
+        Transport::Tls(Arc::new(TlsClient::new(self.identity(), Default::default()).unwrap()))
+    }
+
+    fn coordinator(&self) -> DkgCoordinator {
+        DkgCoordinator::new(self.transport(), AggregatorIdentity::load(&self.coordinator).unwrap()).unwrap()
+    }
+}
+
+fn copy_signer(signer: &Signer) -> Signer {
+    Signer::from_bytes(&signer.to_bytes().unwrap()).unwrap()
+}
+
+// 以给定密钥启动启用 DKG 的节点，份额保存在 dir/<name>; 节点只经双向 TLS 接受允许的协调者
+async fn spawn_dkg_node(dir: &Path, channel: &Channel, name: &str, signer: Signer) -> (String, Arc<DkgNode>) {
+    let node_dir = dir.join(name);
+    std::fs::create_dir_all(&node_dir).unwrap();
+    let dkg = DkgNode::new(node_dir, Zeroizing::new(PASSWORD.to_string())).with_transport(channel.transport());
+    serve_dkg_node(channel, dkg, signer).await
+}
+
+// 旧份额保存在节点密钥文件 dir/<name>.key 中，节点以该文件启动
+async fn spawn_share_node(dir: &Path, channel: &Channel, name: &str, share: &Signer) -> (String, Arc<DkgNode>, PathBuf) {
+    let node_dir = dir.join(name);
+    std::fs::create_dir_all(&node_dir).unwrap();
+    let keystore_path = dir.join(format!("{}.key", name));
+    keystore::save(&keystore_path, share, PASSWORD).unwrap();
+    let dkg = DkgNode::new(node_dir, Zeroizing::new(PASSWORD.to_string()))
+        .with_transport(channel.transport())
+        .with_keystore(keystore_path.clone());
+    let (addr, dkg) = serve_dkg_node(channel, dkg, copy_signer(share)).await;
+    (addr, dkg, keystore_path)
+}
+
+async fn serve_dkg_node(channel: &Channel, dkg: DkgNode, signer: Signer) -> (String, Arc<DkgNode>) {
+    let dkg = Arc::new(dkg);
+    let keys = Arc::new(KeySchedule::new(Arc::new(signer)));
+    let tls = Arc::new(TlsServer::new(channel.identity(), Vec::new()).unwrap());
+    let coordinator = AggregatorIdentity::load(&channel.coordinator).unwrap().public_key();
+    let auth = Arc::new(RequestAuthenticator::new(vec![coordinator]));
+    let service = NodeService::new(keys).with_dkg(dkg.clone()).with_tls(tls).with_authenticator(auth);
+    (spawn_node(service).await, dkg)
+}
+
+// 旧委员会节点的操作员写入同一份重分享计划
+fn approve(dir: &Path, names: &[String], plan: &ResharePlan) {
+    for name in names {
+        plan.write(&ResharePlan::path(&dir.join(name), &plan.session)).unwrap();
+    }
+}
+
+// 用重分享保存的份额启动节点，收集部分签名并在群公钥下验证
+async fn threshold_sign(nodes: &[&Arc<DkgNode>], session: &str, threshold: usize, group: &ThresholdGroup) {
+    let scheme = HashScheme::TryAndIncrement;
+    let mut aggregator = ThresholdAggregator::new(threshold);
+    for dkg in nodes.iter().take(threshold) {
+        let share = keystore::load(&dkg.share_path(session), PASSWORD).unwrap();
+        let addr = spawn_node(NodeService::from_signer(Arc::new(share))).await;
+        let node_signature = request_signature(&addr, MESSAGE, &SignOptions::new(scheme.clone())).await.unwrap();
+        let index = group.share_index(&node_signature.public_key).unwrap();
+        assert!(aggregator.add(index, node_signature.signature));
+    }
+    let signature = aggregator.combine().unwrap();
+    assert!(verify_signature_with(&scheme, MESSAGE, &signature, &group.group_public_key));
+}
+
+#[tokio::test]
+async fn committee_change_with_offline_old_node() {
+    let dir = temp_dir("change");
+    let channel = Channel::new(&dir);
+    let coordinator = channel.coordinator();
+    let master = Signer::random();
+    let old = deal(master.private_key(), 2, 3).unwrap();
+
+    // 旧委员会: 份额 1、2 在线，份额 3 的节点离线
+    let mut old_nodes = Vec::new();
+    let mut old_names = Vec::new();
+    let mut old_keystores = Vec::new();
+    for (index, share) in old.shares.iter().take(2) {
+        old_names.push(format!("old-{}", index));
+        let (addr, dkg, keystore_path) = spawn_share_node(&dir, &channel, &format!("old-{}", index), share).await;
+        old_nodes.push((addr, dkg));
+        old_keystores.push(keystore_path);
+    }
+    let mut dealers: Vec<String> = old_nodes.iter().map(|(addr, _)| addr.clone()).collect();
+    dealers.push(free_addr());
+
+    // 新委员会: 保留旧节点 2，加入三个新节点，门限改为 3
+    let mut new_nodes = vec![old_nodes[1].clone()];
+    for i in 0..3 {
+        new_nodes.push(spawn_dkg_node(&dir, &channel, &format!("new-{}", i), Signer::random()).await);
+    }
+    let recipients: Vec<String> = new_nodes.iter().map(|(addr, _)| addr.clone()).collect();
+
+    // 未经操作员批准的重分享被拒绝
+    let plan = ResharePlan::new("change", &old.group, 3, &recipients);
+    assert!(run_reshare(&coordinator, &plan, &old.group, &dealers, "change-group", 1).await.is_err());
+    // 计划与请求不一致时同样拒绝
+    approve(&dir, &old_names, &ResharePlan::new("change", &old.group, 2, &recipients));
+    assert!(run_reshare(&coordinator, &plan, &old.group, &dealers, "change-group", 1).await.is_err());
+
+    approve(&dir, &old_names, &plan);
+    let outcome = run_reshare(&coordinator, &plan, &old.group, &dealers, "change-group", 1).await.unwrap();
+    assert_eq!(outcome.qualified, vec![1, 2]);
+    assert_eq!(outcome.group.threshold, 3);
+    assert_eq!(outcome.group.shares.len(), 4);
+    assert_eq!(outcome.group.group_public_key.0, master.public_key().0);
+    assert!(outcome.registration.verify());
+    assert_eq!(outcome.registration.generation, 1);
+
+    // 离开的旧节点没有保存新份额，新委员会任意 3 个份额可以签名
+    assert!(!old_nodes[0].1.share_path("change").exists());
+    assert!(old_nodes[1].1.share_path("change").exists());
+    // 离开的旧节点作废了密钥文件并留下标记，重启时不会在该路径静默生成新密钥
+    assert!(!old_keystores[0].exists(), "{} was not retired", old_keystores[0].display());
+    assert!(keystore::retired_marker(&old_keystores[0]).exists());
+    let restart = keystore::load_or_generate(&old_keystores[0], PASSWORD).map(|_| ()).unwrap_err();
+    assert!(restart.to_string().contains("retired"), "{}", restart);
+    assert!(!old_keystores[0].exists());
+    // 留下的旧节点的密钥文件换成了新份额，运行中的节点也改用新份额签名
+    let new_share = keystore::load(&old_nodes[1].1.share_path("change"), PASSWORD).unwrap().public_key();
+    assert!(outcome.group.share_index(&new_share).is_some());
+    assert_eq!(keystore::load(&old_keystores[1], PASSWORD).unwrap().public_key().0, new_share.0);
+    assert!(!keystore::retired_marker(&old_keystores[1]).exists());
+    let identity = AggregatorIdentity::load(&channel.coordinator).unwrap();
+    let options = SignOptions::new(HashScheme::TryAndIncrement)
+        .with_transport(channel.transport())
+        .with_identity(Arc::new(identity));
+    let node_signature = request_signature(&old_nodes[1].0, MESSAGE, &options).await.unwrap();
+    assert_eq!(node_signature.public_key.0, new_share.0);
+    let signers: Vec<&Arc<DkgNode>> = new_nodes.iter().rev().map(|(_, dkg)| dkg).collect();
+    threshold_sign(&signers, "change", 3, &outcome.group).await;
+
+    // 计划用后即删，会话已结束，不能重复运行
+    assert!(!ResharePlan::path(&dir.join(&old_names[0]), "change").exists());
+    approve(&dir, &old_names, &plan);
+    assert!(run_reshare(&coordinator, &plan, &old.group, &dealers, "change-group", 1).await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn refresh_replaces_shares_of_the_same_committee() {
+    let dir = temp_dir("refresh");
+    let channel = Channel::new(&dir);
+    let master = Signer::random();
+    let old = deal(master.private_key(), 2, 3).unwrap();
+    let mut nodes = Vec::new();
+    let mut names = Vec::new();
+    for (index, share) in &old.shares {
+        names.push(format!("node-{}", index));
+        // 旧份额是之前的会话保存在 DKG 目录中的份额文件
+        keystore::save(&dir.join(format!("node-{}", index)).join("genesis.json"), share, PASSWORD).unwrap();
+        nodes.push(spawn_dkg_node(&dir, &channel, &format!("node-{}", index), copy_signer(share)).await);
+    }
+    let addrs: Vec<String> = nodes.iter().map(|(addr, _)| addr.clone()).collect();
+
+    let plan = ResharePlan::new("refresh", &old.group, 2, &addrs);
+    approve(&dir, &names, &plan);
+    let outcome = run_reshare(&channel.coordinator(), &plan, &old.group, &addrs, "refresh-group", 1).await.unwrap();
+    assert_eq!(outcome.qualified, vec![1, 2, 3]);
+    assert_eq!(outcome.group.group_public_key.0, old.group.group_public_key.0);
+    for (new_share, old_share) in outcome.group.shares.iter().zip(&old.group.shares) {
+        assert_eq!(new_share.index, old_share.index);
+        assert_ne!(new_share.public_key.0, old_share.public_key.0);
+    }
+    let dkgs: Vec<&Arc<DkgNode>> = nodes.iter().map(|(_, dkg)| dkg).collect();
+    threshold_sign(&dkgs, "refresh", 2, &outcome.group).await;
+    // 新份额保存在旧份额旁边，旧份额文件已作废
+    for (name, dkg) in names.iter().zip(&dkgs) {
+        assert!(dkg.share_path("refresh").exists());
+        assert!(!dkg.share_path("genesis").exists(), "{} kept its old share", name);
+    }
+
+    // 旧份额与新份额混合后不再得到有效签名
+    let refreshed = keystore::load(&nodes[0].1.share_path("refresh"), PASSWORD).unwrap();
+    let partials = [(1, refreshed.sign(MESSAGE).unwrap()), (2, old.shares[1].1.sign(MESSAGE).unwrap())];
+    assert!(!verify_signature(MESSAGE, &combine(&partials).unwrap(), &master.public_key()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn reshare_needs_old_threshold_of_dealers() {
+    let dir = temp_dir("quorum");
+    let channel = Channel::new(&dir);
+    let coordinator = channel.coordinator();
+    let master = Signer::random();
+    let old = deal(master.private_key(), 3, 4).unwrap();
+
+    // 只有两个旧份额在线，另外一个节点不持有旧份额
+    let mut dealers = Vec::new();
+    let mut names = Vec::new();
+    for (index, share) in old.shares.iter().take(2) {
+        names.push(format!("old-{}", index));
+        dealers.push(spawn_dkg_node(&dir, &channel, &format!("old-{}", index), copy_signer(share)).await.0);
+    }
+    dealers.push(spawn_dkg_node(&dir, &channel, "outsider", Signer::random()).await.0);
+    names.push("outsider".to_string());
+    let recipients = dealers.clone();
+
+    let plan = ResharePlan::new("quorum", &old.group, 2, &recipients);
+    approve(&dir, &names, &plan);
+    assert!(run_reshare(&coordinator, &plan, &old.group, &dealers, "quorum-group", 1).await.is_err());
+    // 门限超过新委员会规模或为 1 时在联系节点前拒绝
+    for threshold in [4, 1] {
+        let plan = ResharePlan::new("quorum-2", &old.group, threshold, &recipients);
+        assert!(run_reshare(&coordinator, &plan, &old.group, &dealers, "quorum-group", 1).await.is_err());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
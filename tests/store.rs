@@ -0,0 +1,207 @@
+// 追加写日志存储: 重新打开后的回放、写了一半的末行、损坏的日志以及压缩
+use std::path::{Path, PathBuf};
+use dss_core::store::{FileStore, MemoryStore, Store};
+
+fn temp_log(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-store-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("records.log")
+}
+
+fn load(path: &Path) -> Vec<(String, u64)> {
+    FileStore::<u64>::open(path).unwrap().load().unwrap()
+}
+
+fn line_count(path: &Path) -> usize {
+    std::fs::read_to_string(path).unwrap().lines().count()
+}
+
+#[test]
+fn records_survive_reopening() {
+    let path = temp_log("reopen");
+    {
+        let mut store = FileStore::<u64>::open(&path).unwrap();
+        assert!(store.load().unwrap().is_empty());
+        store.put("b", &2).unwrap();
+        store.put("a", &1).unwrap();
+        store.put("b", &3).unwrap();
+        store.put("c", &4).unwrap();
+        store.delete("c").unwrap();
+        store.put_deferred("d", &5).unwrap().wait().unwrap();
+    }
+    // 同一个键以最后一行为准，删除的键不再出现
+    let expected = vec![("a".to_string(), 1), ("b".to_string(), 3), ("d".to_string(), 5)];
+    assert_eq!(load(&path), expected);
+
+    // 重新打开后继续追加
+    FileStore::<u64>::open(&path).unwrap().put("a", &6).unwrap();
+    assert_eq!(load(&path)[0], ("a".to_string(), 6));
+    assert_eq!(line_count(&path), 7);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn torn_final_lines_are_discarded() {
+    let path = temp_log("torn");
+    FileStore::<u64>::open(&path).unwrap().put("a", &1).unwrap();
+    let mut log = std::fs::read(&path).unwrap();
+    log.extend_from_slice(br#"{"op":"put","key":"b","val"#);
+    std::fs::write(&path, &log).unwrap();
+
+    // 写了一半的末行在打开时被截掉，之后的追加不受影响
+    let mut store = FileStore::<u64>::open(&path).unwrap();
+    assert_eq!(store.load().unwrap(), vec![("a".to_string(), 1)]);
+    store.put("b", &2).unwrap();
+    drop(store);
+    assert_eq!(load(&path), vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+    assert_eq!(line_count(&path), 2);
+
+    // 末行完整但缺少换行符时补上换行，下一次追加不会与其拼成一行
+    let mut log = std::fs::read(&path).unwrap();
+    log.pop();
+    std::fs::write(&path, &log).unwrap();
+    FileStore::<u64>::open(&path).unwrap().put("c", &3).unwrap();
+    assert_eq!(load(&path).len(), 3);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn corruption_before_the_last_line_is_an_error() {
+    let path = temp_log("corrupt");
+    std::fs::write(
+        &path,
+        "{\"op\":\"put\",\"key\":\"a\",\"value\":1}\nnot json\n{\"op\":\"put\",\"key\":\"b\",\"value\":2}\n",
+    )
+    .unwrap();
+    let error = FileStore::<u64>::open(&path).err().unwrap();
+    assert!(error.to_string().contains("corrupted store log"), "{}", error);
+    // 日志保持原样，不会被截断
+    assert_eq!(line_count(&path), 3);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn logs_are_compacted_once_garbage_dominates() {
+    let path = temp_log("compact");
+    let mut store = FileStore::<u64>::open(&path).unwrap();
+    for i in 0..1024u64 {
+        store.put(&format!("key-{}", i % 4), &i).unwrap();
+    }
+    // 1020 行过期，尚未达到压缩阈值
+    assert_eq!(line_count(&path), 1024);
+    for i in 0..3u64 {
+        store.put(&format!("key-{}", i), &(5000 + i)).unwrap();
+    }
+    assert_eq!(line_count(&path), 1027);
+    // 第 1024 行过期时压缩为每个键一行
+    store.put("key-3", &5003).unwrap();
+    assert_eq!(line_count(&path), 4);
+    store.put("key-0", &6000).unwrap();
+    drop(store);
+
+    let records = load(&path);
+    assert_eq!(
+        records,
+        vec![
+            ("key-0".to_string(), 6000),
+            ("key-1".to_string(), 5001),
+            ("key-2".to_string(), 5002),
+            ("key-3".to_string(), 5003),
+        ]
+    );
+    assert_eq!(line_count(&path), 5);
+    assert!(!path.with_extension("compact").exists());
+
+    // 打开时同样按阈值压缩
+    let mut log = String::new();
+    for i in 0..2000 {
+        log.push_str(&format!("{{\"op\":\"put\",\"key\":\"k\",\"value\":{}}}\n", i));
+    }
+    std::fs::write(&path, log).unwrap();
+    assert_eq!(load(&path), vec![("k".to_string(), 1999)]);
+    assert_eq!(line_count(&path), 1);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn compaction_is_deferred_to_the_pending_sync() {
+    let path = temp_log("deferred");
+    let mut store = FileStore::<u64>::open(&path).unwrap();
+    for i in 0..1027u64 {
+        store.put(&format!("key-{}", i % 4), &i).unwrap();
+    }
+    // 达到阈值的写入只安排压缩，等待前日志保持原样
+    let pending = store.put_deferred("key-3", &5003).unwrap();
+    assert_eq!(line_count(&path), 1028);
+    // 压缩执行前的追加不会再安排压缩，压缩按执行时的日志进行
+    let later = store.put_deferred("key-0", &6000).unwrap();
+    assert_eq!(line_count(&path), 1029);
+    pending.wait().unwrap();
+    later.wait().unwrap();
+    assert_eq!(line_count(&path), 4);
+    assert!(!path.with_extension("compact").exists());
+
+    store.put("key-1", &6001).unwrap();
+    drop(store);
+    assert_eq!(
+        load(&path),
+        vec![
+            ("key-0".to_string(), 6000),
+            ("key-1".to_string(), 6001),
+            ("key-2".to_string(), 1026),
+            ("key-3".to_string(), 5003),
+        ]
+    );
+    assert_eq!(line_count(&path), 5);
+
+    // 未等待而丢弃的压缩之后重新安排
+    let mut store = FileStore::<u64>::open(&path).unwrap();
+    for i in 0..1100u64 {
+        let pending = store.put_deferred("key-0", &i).unwrap();
+        if i < 1099 {
+            drop(pending);
+        } else {
+            pending.wait().unwrap();
+        }
+    }
+    assert_eq!(line_count(&path), 4);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn writes_during_a_compaction_are_kept() {
+    let path = temp_log("concurrent");
+    let mut store = FileStore::<u64>::open(&path).unwrap();
+    for i in 0..1027u64 {
+        store.put(&format!("key-{}", i % 4), &i).unwrap();
+    }
+    let pending = store.put_deferred("key-3", &5003).unwrap();
+    // 压缩在其他线程执行期间继续追加，替换文件后不丢失记录
+    let compaction = std::thread::spawn(move || pending.wait().unwrap());
+    for i in 0..200u64 {
+        store.put(&format!("new-{}", i), &i).unwrap();
+    }
+    compaction.join().unwrap();
+    store.put("key-0", &6000).unwrap();
+    drop(store);
+
+    let records = load(&path);
+    assert_eq!(records.len(), 204);
+    assert!(records.contains(&("key-0".to_string(), 6000)));
+    assert!(records.contains(&("key-3".to_string(), 5003)));
+    assert!((0..200u64).all(|i| records.contains(&(format!("new-{}", i), i))));
+    assert!(line_count(&path) < 1028 + 201);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn memory_stores_keep_the_latest_value() {
+    let mut store = MemoryStore::new();
+    store.put("a", &1u64).unwrap();
+    store.put("a", &2).unwrap();
+    store.put_deferred("b", &3).unwrap().wait().unwrap();
+    store.delete("b").unwrap();
+    assert_eq!(store.load().unwrap(), vec![("a".to_string(), 2)]);
+}
@@ -0,0 +1,141 @@
+// 可信分发者的门限 BLS: 份额分发、拉格朗日系数、任意门限个部分签名的合成以及合成时使用的份额
+use rand_core::OsRng;
+use substrate_bn::{Fr, Group, G1};
+use dss_core::{PrivateKey, Signature, Signer};
+use dss_core::threshold::{combine, deal, lagrange_coefficient, lagrange_coefficient_at, ThresholdAggregator};
+use dss_core::verifier::verify_signature;
+
+const MESSAGE: &[u8] = b"threshold message";
+
+fn fr(value: u32) -> Fr {
+    Fr::from_str(&value.to_string()).unwrap()
+}
+
+fn partials(shares: &[(u32, Signer)], indices: &[u32]) -> Vec<(u32, Signature)> {
+    indices
+        .iter()
+        .map(|index| {
+            let (_, signer) = shares.iter().find(|(share, _)| share == index).unwrap();
+            (*index, signer.sign(MESSAGE).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn deal_splits_the_master_key_into_consistent_shares() {
+    let master = PrivateKey(Fr::random(&mut OsRng));
+    let dealing = deal(&master, 3, 5).unwrap();
+    let group = &dealing.group;
+    group.validate().unwrap();
+    assert_eq!(group.threshold, 3);
+    assert_eq!(group.group_public_key.0, Signer::new(PrivateKey(master.0)).public_key().0);
+    assert_eq!(dealing.shares.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    for (index, signer) in &dealing.shares {
+        assert_eq!(group.share_public_key(*index).unwrap().0, signer.public_key().0);
+        assert_eq!(group.share_index(&signer.public_key()), Some(*index));
+    }
+    assert!(group.share_public_key(6).is_none());
+
+    // 份额公钥与群公钥不一致的群被拒绝
+    let mut swapped = group.clone();
+    swapped.shares[4].public_key = swapped.shares[3].public_key;
+    assert!(swapped.validate().is_err());
+    let mut foreign = group.clone();
+    foreign.group_public_key = Signer::random().public_key();
+    assert!(foreign.validate().is_err());
+    let mut replaced = group.clone();
+    replaced.shares[0].public_key = Signer::random().public_key();
+    assert!(replaced.validate().is_err());
+
+    assert!(deal(&master, 0, 5).is_err());
+    assert!(deal(&master, 6, 5).is_err());
+    // 门限为 1 时每个份额都等于主私钥
+    let dealing = deal(&master, 1, 2).unwrap();
+    for (_, signer) in &dealing.shares {
+        assert_eq!(signer.public_key().0, dealing.group.group_public_key.0);
+    }
+}
+
+#[test]
+fn lagrange_coefficients_interpolate_at_zero() {
+    let indices = [1, 3, 4];
+    let coefficients: Vec<Fr> = indices.iter().map(|index| lagrange_coefficient(*index, &indices).unwrap()).collect();
+    // Σ λ_i·f(i) = f(0): 常数多项式插值为常数，f(x) = x 与 f(x) = x² 在 0 点为 0
+    let sum = |f: &dyn Fn(Fr) -> Fr| {
+        indices.iter().zip(&coefficients).fold(Fr::zero(), |acc, (index, coefficient)| acc + *coefficient * f(fr(*index)))
+    };
+    assert_eq!(sum(&|_| Fr::one()), Fr::one());
+    assert_eq!(sum(&|x| x), Fr::zero());
+    assert_eq!(sum(&|x| x * x), Fr::zero());
+    let seven = fr(7);
+    assert_eq!(sum(&|x| x * x + seven), seven);
+
+    // 单个份额的系数为 1
+    assert_eq!(lagrange_coefficient(5, &[5]).unwrap(), Fr::one());
+
+    // 在其他点插值: f(x) = x² + 7 在 2 点为 11，在已知点处系数为单位向量
+    let at_two: Vec<Fr> = indices.iter().map(|index| lagrange_coefficient_at(*index, &indices, 2).unwrap()).collect();
+    let value = indices
+        .iter()
+        .zip(&at_two)
+        .fold(Fr::zero(), |acc, (index, coefficient)| acc + *coefficient * (fr(*index) * fr(*index) + seven));
+    assert_eq!(value, fr(11));
+    assert_eq!(lagrange_coefficient_at(3, &indices, 3).unwrap(), Fr::one());
+    assert_eq!(lagrange_coefficient_at(1, &indices, 3).unwrap(), Fr::zero());
+}
+
+#[test]
+fn any_threshold_partials_combine_to_the_group_signature() {
+    let master = PrivateKey(Fr::random(&mut OsRng));
+    let dealing = deal(&master, 3, 5).unwrap();
+    let expected = Signer::new(master).sign(MESSAGE).unwrap();
+    for indices in [[1, 2, 3], [5, 1, 4], [2, 4, 5], [3, 5, 2]] {
+        let combined = combine(&partials(&dealing.shares, &indices)).unwrap();
+        assert_eq!(combined.0, expected.0, "shares {:?}", indices);
+        assert!(verify_signature(MESSAGE, &combined, &dealing.group.group_public_key));
+    }
+    // 多于门限个份额同样得到群签名
+    assert_eq!(combine(&partials(&dealing.shares, &[1, 2, 3, 4, 5])).unwrap().0, expected.0);
+
+    // 不足门限个份额得不到群签名
+    let combined = combine(&partials(&dealing.shares, &[1, 2])).unwrap();
+    assert!(!verify_signature(MESSAGE, &combined, &dealing.group.group_public_key));
+
+    // 序号为 0 或重复的份额被拒绝
+    let mut duplicated = partials(&dealing.shares, &[1, 2, 3]);
+    duplicated[2].0 = 1;
+    assert!(combine(&duplicated).is_err());
+    let mut zero = partials(&dealing.shares, &[1, 2, 3]);
+    zero[0].0 = 0;
+    assert!(combine(&zero).is_err());
+}
+
+#[test]
+fn aggregator_reports_the_shares_it_combines() {
+    let master = PrivateKey(Fr::random(&mut OsRng));
+    let dealing = deal(&master, 2, 4).unwrap();
+    let mut aggregator = ThresholdAggregator::new(2);
+    let received = partials(&dealing.shares, &[4, 2]);
+    assert!(aggregator.add(received[0].0, received[0].1));
+    assert!(aggregator.used_indices().is_empty());
+    assert!(aggregator.combine().is_err());
+    assert!(aggregator.add(received[1].0, received[1].1));
+    assert!(!aggregator.add(received[1].0, received[1].1));
+
+    // 收到多于门限个份额时只合成序号最小的 threshold 个
+    let late = partials(&dealing.shares, &[1]);
+    assert!(aggregator.add(late[0].0, late[0].1));
+    assert_eq!(aggregator.indices(), vec![1, 2, 4]);
+    assert_eq!(aggregator.used_indices(), vec![1, 2]);
+    let combined = aggregator.combine().unwrap();
+    assert_eq!(combined.0, combine(&partials(&dealing.shares, &[1, 2])).unwrap().0);
+    assert!(verify_signature(MESSAGE, &combined, &dealing.group.group_public_key));
+
+    // 未使用的份额即使无效也不影响结果
+    let mut tampered = ThresholdAggregator::new(2);
+    for (index, partial) in partials(&dealing.shares, &[1, 2]) {
+        tampered.add(index, partial);
+    }
+    tampered.add(4, Signature(G1::one()));
+    assert_eq!(tampered.combine().unwrap().0, combined.0);
+}
@@ -0,0 +1,126 @@
+// 节点通道的双向 TLS: 双方证书由本地 CA 签发，主节点按地址固定节点证书，节点可限定主节点证书
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use dss_core::Signer;
+use dss_core::epoch::KeySchedule;
+use dss_core::hash::HashScheme;
+use dss_core::node::{request_signature, NodeService, SignOptions};
+use dss_core::tls::{
+    create_ca, issue_certificate, parse_fingerprint, parse_peer_pin, Fingerprint, IssuedCertificate, TlsClient,
+    TlsIdentity, TlsServer, Transport,
+};
+mod common;
+use common::spawn_node;
+
+const MESSAGE: &[u8] = b"tls message";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dss-tls-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn issue(ca_dir: &Path, name: &str) -> IssuedCertificate {
+    issue_certificate(ca_dir, name, &["127.0.0.1".to_string(), "localhost".to_string()], ca_dir).unwrap()
+}
+
+fn identity(ca_dir: &Path, issued: &IssuedCertificate) -> TlsIdentity {
+    TlsIdentity::load(&ca_dir.join("ca.pem"), &issued.cert, &issued.key).unwrap()
+}
+
+fn client(ca_dir: &Path, issued: &IssuedCertificate, pins: &[(&str, Fingerprint)]) -> Transport {
+    let pins: HashMap<String, Fingerprint> = pins.iter().map(|(addr, pin)| (addr.to_string(), *pin)).collect();
+    Transport::Tls(Arc::new(TlsClient::new(identity(ca_dir, issued), pins).unwrap()))
+}
+
+async fn start_node(ca_dir: &Path, issued: &IssuedCertificate, allowed: Vec<Fingerprint>) -> (String, Arc<Signer>) {
+    let signer = Arc::new(Signer::random());
+    let keys = Arc::new(KeySchedule::new(signer.clone()));
+    let tls = Arc::new(TlsServer::new(identity(ca_dir, issued), allowed).unwrap());
+    (spawn_node(NodeService::new(keys).with_tls(tls)).await, signer)
+}
+
+async fn sign(transport: &Transport, addr: &str) -> std::io::Result<dss_core::node::NodeSignature> {
+    let options = SignOptions::new(HashScheme::TryAndIncrement).with_transport(transport.clone());
+    request_signature(addr, MESSAGE, &options).await
+}
+
+#[test]
+fn fingerprints_and_pins_parse() {
+    let hex = "ab".repeat(32);
+    let colons = vec!["ab"; 32].join(":");
+    assert_eq!(parse_fingerprint(&hex).unwrap(), [0xab; 32]);
+    assert_eq!(parse_fingerprint(&colons).unwrap(), [0xab; 32]);
+    assert!(parse_fingerprint("abcd").is_err());
+
+    let (addr, pin) = parse_peer_pin(&format!("127.0.0.1:9000={}", hex)).unwrap();
+    assert_eq!((addr.as_str(), pin), ("127.0.0.1:9000", [0xab; 32]));
+    assert!(parse_peer_pin(&hex).is_err());
+    assert!(parse_peer_pin(&format!("={}", hex)).is_err());
+}
+
+#[test]
+fn local_ca_issues_certificates() {
+    let dir = temp_dir("ca");
+    let ca = create_ca(&dir).unwrap();
+    assert_eq!(ca, dir.join("ca.pem"));
+    // 已有 CA 时不覆盖
+    assert_eq!(create_ca(&dir).unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+
+    let issued = issue(&dir, "node-1");
+    assert_eq!(issued.cert, dir.join("node-1.pem"));
+    assert_eq!(issued.key, dir.join("node-1-key.pem"));
+    assert_eq!(identity(&dir, &issued).fingerprint(), issued.fingerprint);
+    assert_ne!(issue(&dir, "node-2").fingerprint, issued.fingerprint);
+    assert!(issue_certificate(&dir, "../escape", &["127.0.0.1".to_string()], &dir).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn node_signs_over_mutual_tls() {
+    let dir = temp_dir("sign");
+    create_ca(&dir).unwrap();
+    let node_cert = issue(&dir, "node");
+    let aggregator_cert = issue(&dir, "aggregator");
+    let (addr, signer) = start_node(&dir, &node_cert, vec![aggregator_cert.fingerprint]).await;
+
+    // 固定了正确指纹的主节点可以请求签名
+    let transport = client(&dir, &aggregator_cert, &[(&addr, node_cert.fingerprint)]);
+    let node_signature = sign(&transport, &addr).await.unwrap();
+    assert_eq!(node_signature.public_key.0, signer.public_key().0);
+    // 未配置指纹时只校验证书链
+    let transport = client(&dir, &aggregator_cert, &[]);
+    assert!(sign(&transport, &addr).await.is_ok());
+
+    // 明文连接无法完成请求
+    assert!(request_signature(&addr, MESSAGE, &SignOptions::new(HashScheme::TryAndIncrement)).await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn mismatched_peers_are_rejected() {
+    let dir = temp_dir("reject");
+    create_ca(&dir).unwrap();
+    let node_cert = issue(&dir, "node");
+    let aggregator_cert = issue(&dir, "aggregator");
+    let other_cert = issue(&dir, "other");
+    let (addr, _) = start_node(&dir, &node_cert, vec![aggregator_cert.fingerprint]).await;
+
+    // 节点证书与固定的指纹不符
+    let transport = client(&dir, &aggregator_cert, &[(&addr, other_cert.fingerprint)]);
+    assert!(sign(&transport, &addr).await.is_err());
+
+    // 同一 CA 签发但不在节点允许列表中的主节点证书
+    let transport = client(&dir, &other_cert, &[]);
+    assert!(sign(&transport, &addr).await.is_err());
+
+    // 其他 CA 签发的证书
+    let foreign_dir = temp_dir("foreign");
+    create_ca(&foreign_dir).unwrap();
+    let foreign_cert = issue(&foreign_dir, "aggregator");
+    let transport = client(&foreign_dir, &foreign_cert, &[]);
+    assert!(sign(&transport, &addr).await.is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&foreign_dir).unwrap();
+}